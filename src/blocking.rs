@@ -0,0 +1,372 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+use crate::{
+    networking::{JSONResponse, OLDEST_API_VERSION, SettingsUpdate, VERSION_HEADER, path},
+    prelude::*,
+};
+
+/// The client side of the fngr actions, without an async runtime.
+pub trait Fngr {
+    fn login(&self, username: &str, key: &str, status: Option<&str>) -> Result<JSONResponse>;
+
+    fn logoff(&self, username: &str, key: &str) -> Result<JSONResponse>;
+
+    fn bump(&self, username: &str, key: &str) -> Result<JSONResponse>;
+
+    fn snooze(&self, username: &str, key: &str, duration: u64) -> Result<JSONResponse>;
+
+    /// Fingers `user`, anonymously unless `auth` holds a username and key.
+    fn finger(&self, user: &str, auth: Option<(&str, &str)>) -> Result<JSONResponse>;
+
+    /// The status texts `user` set lately, newest first.
+    fn history(&self, user: &str) -> Result<JSONResponse>;
+
+    /// The server's version, uptime, counts and message of the day.
+    fn info(&self) -> Result<JSONResponse>;
+
+    fn check(&self, username: &str, key: &str) -> Result<JSONResponse>;
+
+    fn settings(&self, username: &str, key: &str, update: &SettingsUpdate) -> Result<JSONResponse>;
+
+    /// Sets the user's bio, an empty one removes it.
+    fn set_bio(&self, username: &str, key: &str, bio: &str) -> Result<JSONResponse>;
+
+    /// Sets the user's website, an empty one removes it.
+    fn set_website(&self, username: &str, key: &str, website: &str) -> Result<JSONResponse>;
+
+    /// Sets the user's social handle, an empty one removes it.
+    fn set_social(&self, username: &str, key: &str, social: &str) -> Result<JSONResponse>;
+
+    /// Sets the user's pronouns, empty ones remove them.
+    fn set_pronouns(&self, username: &str, key: &str, pronouns: &str) -> Result<JSONResponse>;
+
+    /// Sets the user's location, an empty one removes it.
+    fn set_location(&self, username: &str, key: &str, location: &str) -> Result<JSONResponse>;
+
+    /// Sets the user's IANA timezone like `Europe/Berlin`, an empty one removes it.
+    fn set_timezone(&self, username: &str, key: &str, timezone: &str) -> Result<JSONResponse>;
+
+    /// Points the user's avatar at `url`, an empty one removes it.
+    fn set_avatar(&self, username: &str, key: &str, url: &str) -> Result<JSONResponse>;
+
+    /// Uploads `image` as the user's avatar, `content_type` is one of `networking::IMAGE_TYPES`.
+    fn upload_avatar(
+        &self,
+        username: &str,
+        key: &str,
+        content_type: &str,
+        image: &[u8],
+    ) -> Result<JSONResponse>;
+
+    fn list(&self) -> Result<JSONResponse>;
+
+    /// Lists the users `username` follows, the ones online most recently first.
+    fn following(&self, username: &str, key: &str) -> Result<JSONResponse>;
+
+    fn follow(&self, username: &str, key: &str, user: &str) -> Result<JSONResponse>;
+
+    fn unfollow(&self, username: &str, key: &str, user: &str) -> Result<JSONResponse>;
+
+    /// Fingers everyone in `group`, with how many of them are online.
+    fn group(&self, group: &str) -> Result<JSONResponse>;
+
+    fn join_group(&self, username: &str, key: &str, group: &str) -> Result<JSONResponse>;
+
+    fn leave_group(&self, username: &str, key: &str, group: &str) -> Result<JSONResponse>;
+
+    /// Registers `username`, the new key is returned in `JSONResponse::OK`.
+    fn register(&self, username: &str, regkey: Option<&str>) -> Result<JSONResponse>;
+
+    fn deregister(&self, username: &str, key: &str) -> Result<JSONResponse>;
+
+    /// Replaces the key of `username` with `new_key`, which must be a uuid.
+    fn passwd(&self, username: &str, key: &str, new_key: &str) -> Result<JSONResponse>;
+
+    /// Replaces the key of `username` with a random one, returned in `JSONResponse::OK`.
+    fn rotate_key(&self, username: &str, key: &str) -> Result<JSONResponse>;
+}
+
+/// Blocking counterpart of `fngr::Client` built on `std::net::TcpStream`.
+pub struct Client {
+    address: String,
+}
+
+impl Client {
+    /// `address` is the server's `host:port`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+        }
+    }
+
+    fn get(&self, action: &str, params: &[(&str, &str)]) -> Result<JSONResponse> {
+        self.send("GET", action, params, None)
+    }
+
+    /// Sends a request, with `body` and its content type as a `POST` body if given.
+    fn send(
+        &self,
+        method: &str,
+        action: &str,
+        params: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<JSONResponse> {
+        let mut stream = TcpStream::connect(&self.address)?;
+
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n{}: {}\r\n",
+            method,
+            path(action, params),
+            self.address,
+            VERSION_HEADER,
+            // the version that answers in `JSONResponse`
+            OLDEST_API_VERSION
+        );
+        if let Some((content_type, body)) = body {
+            request.push_str(&format!(
+                "Content-Type: {}\r\nContent-Length: {}\r\n",
+                content_type,
+                body.len()
+            ));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        if let Some((_, body)) = body {
+            stream.write_all(body)?;
+        }
+
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line)?;
+        if !line.starts_with("HTTP/1.1 ") {
+            return Err(FngrError::Parse(format!(
+                "invalid response from server: '{}'",
+                line.trim()
+            )));
+        }
+
+        let mut length = None;
+        loop {
+            line.clear();
+            stream.read_line(&mut line)?;
+
+            if line.is_empty() || line == "\r\n" || line == "\n" {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+
+        let mut body = vec![];
+        match length {
+            Some(length) => {
+                body.resize(length, 0);
+                stream.read_exact(&mut body)?;
+            }
+            None => {
+                stream.read_to_end(&mut body)?;
+            }
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+impl Fngr for Client {
+    fn login(&self, username: &str, key: &str, status: Option<&str>) -> Result<JSONResponse> {
+        let mut params = vec![("username", username), ("key", key)];
+        if let Some(status) = status {
+            params.push(("status", status));
+        }
+
+        self.get("login", &params)
+    }
+
+    fn logoff(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("logoff", &[("username", username), ("key", key)])
+    }
+
+    fn bump(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("bump", &[("username", username), ("key", key)])
+    }
+
+    fn snooze(&self, username: &str, key: &str, duration: u64) -> Result<JSONResponse> {
+        let duration = duration.to_string();
+        self.get(
+            "snooze",
+            &[
+                ("username", username),
+                ("key", key),
+                ("duration", &duration),
+            ],
+        )
+    }
+
+    fn finger(&self, user: &str, auth: Option<(&str, &str)>) -> Result<JSONResponse> {
+        let mut params = vec![("user", user)];
+        if let Some((username, key)) = auth {
+            params.push(("username", username));
+            params.push(("key", key));
+        }
+
+        self.get("finger", &params)
+    }
+
+    fn history(&self, user: &str) -> Result<JSONResponse> {
+        self.get("history", &[("user", user)])
+    }
+
+    fn info(&self) -> Result<JSONResponse> {
+        self.get("info", &[])
+    }
+
+    fn check(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("check", &[("username", username), ("key", key)])
+    }
+
+    fn settings(&self, username: &str, key: &str, update: &SettingsUpdate) -> Result<JSONResponse> {
+        let update = update.params();
+        let mut params = vec![("username", username), ("key", key)];
+        params.extend(update.iter().map(|(name, value)| (*name, value.as_str())));
+
+        self.get("settings", &params)
+    }
+
+    fn set_bio(&self, username: &str, key: &str, bio: &str) -> Result<JSONResponse> {
+        self.get(
+            "setbio",
+            &[("username", username), ("key", key), ("bio", bio)],
+        )
+    }
+
+    fn set_website(&self, username: &str, key: &str, website: &str) -> Result<JSONResponse> {
+        self.get(
+            "setwebsite",
+            &[("username", username), ("key", key), ("website", website)],
+        )
+    }
+
+    fn set_social(&self, username: &str, key: &str, social: &str) -> Result<JSONResponse> {
+        self.get(
+            "setsocial",
+            &[("username", username), ("key", key), ("social", social)],
+        )
+    }
+
+    fn set_pronouns(&self, username: &str, key: &str, pronouns: &str) -> Result<JSONResponse> {
+        self.get(
+            "setpronouns",
+            &[("username", username), ("key", key), ("pronouns", pronouns)],
+        )
+    }
+
+    fn set_location(&self, username: &str, key: &str, location: &str) -> Result<JSONResponse> {
+        self.get(
+            "setlocation",
+            &[("username", username), ("key", key), ("location", location)],
+        )
+    }
+
+    fn set_timezone(&self, username: &str, key: &str, timezone: &str) -> Result<JSONResponse> {
+        self.get(
+            "settimezone",
+            &[("username", username), ("key", key), ("timezone", timezone)],
+        )
+    }
+
+    fn set_avatar(&self, username: &str, key: &str, url: &str) -> Result<JSONResponse> {
+        self.get(
+            "setavatar",
+            &[("username", username), ("key", key), ("avatar", url)],
+        )
+    }
+
+    fn upload_avatar(
+        &self,
+        username: &str,
+        key: &str,
+        content_type: &str,
+        image: &[u8],
+    ) -> Result<JSONResponse> {
+        self.send(
+            "POST",
+            "setavatar",
+            &[("username", username), ("key", key)],
+            Some((content_type, image)),
+        )
+    }
+
+    fn list(&self) -> Result<JSONResponse> {
+        self.get("list", &[])
+    }
+
+    fn following(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get(
+            "list",
+            &[("username", username), ("key", key), ("following", "true")],
+        )
+    }
+
+    fn follow(&self, username: &str, key: &str, user: &str) -> Result<JSONResponse> {
+        self.get(
+            "follow",
+            &[("username", username), ("key", key), ("user", user)],
+        )
+    }
+
+    fn unfollow(&self, username: &str, key: &str, user: &str) -> Result<JSONResponse> {
+        self.get(
+            "unfollow",
+            &[("username", username), ("key", key), ("user", user)],
+        )
+    }
+
+    fn group(&self, group: &str) -> Result<JSONResponse> {
+        self.get("finger", &[("group", group)])
+    }
+
+    fn join_group(&self, username: &str, key: &str, group: &str) -> Result<JSONResponse> {
+        self.get(
+            "joingroup",
+            &[("username", username), ("key", key), ("group", group)],
+        )
+    }
+
+    fn leave_group(&self, username: &str, key: &str, group: &str) -> Result<JSONResponse> {
+        self.get(
+            "leavegroup",
+            &[("username", username), ("key", key), ("group", group)],
+        )
+    }
+
+    fn register(&self, username: &str, regkey: Option<&str>) -> Result<JSONResponse> {
+        let mut params = vec![("username", username)];
+        if let Some(regkey) = regkey {
+            params.push(("regkey", regkey));
+        }
+
+        self.get("register", &params)
+    }
+
+    fn deregister(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("deregister", &[("username", username), ("key", key)])
+    }
+
+    fn passwd(&self, username: &str, key: &str, new_key: &str) -> Result<JSONResponse> {
+        self.get(
+            "passwd",
+            &[("username", username), ("key", key), ("new_key", new_key)],
+        )
+    }
+
+    fn rotate_key(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("rotatekey", &[("username", username), ("key", key)])
+    }
+}
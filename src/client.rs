@@ -0,0 +1,380 @@
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+use crate::{
+    networking::{JSONResponse, OLDEST_API_VERSION, SettingsUpdate, VERSION_HEADER, path},
+    prelude::*,
+};
+
+/// Talks to a fngr server over http.
+///
+/// Every call opens a new connection, sends a single request and returns the server's json reply.
+/// Errors reported by the server come back as `JSONResponse::Error`, only transport and parsing failures are `Err`.
+pub struct Client {
+    address: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Client {
+    /// `address` is the server's `host:port`.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            headers: vec![],
+        }
+    }
+
+    /// Sends `name: value` with every request.
+    pub fn header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.to_owned(), value.to_string()));
+        self
+    }
+
+    pub async fn login(
+        &self,
+        username: &str,
+        key: &str,
+        status: Option<&str>,
+    ) -> Result<JSONResponse> {
+        let mut params = vec![("username", username), ("key", key)];
+        if let Some(status) = status {
+            params.push(("status", status));
+        }
+
+        self.get("login", &params).await
+    }
+
+    pub async fn logoff(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("logoff", &[("username", username), ("key", key)])
+            .await
+    }
+
+    pub async fn bump(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("bump", &[("username", username), ("key", key)])
+            .await
+    }
+
+    pub async fn snooze(&self, username: &str, key: &str, duration: u64) -> Result<JSONResponse> {
+        let duration = duration.to_string();
+        self.get(
+            "snooze",
+            &[
+                ("username", username),
+                ("key", key),
+                ("duration", &duration),
+            ],
+        )
+        .await
+    }
+
+    /// Fingers `user`, anonymously unless `auth` holds a username and key.
+    pub async fn finger(&self, user: &str, auth: Option<(&str, &str)>) -> Result<JSONResponse> {
+        let mut params = vec![("user", user)];
+        if let Some((username, key)) = auth {
+            params.push(("username", username));
+            params.push(("key", key));
+        }
+
+        self.get("finger", &params).await
+    }
+
+    /// The status texts `user` set lately, newest first.
+    pub async fn history(&self, user: &str) -> Result<JSONResponse> {
+        self.get("history", &[("user", user)]).await
+    }
+
+    /// The server's version, uptime, counts and message of the day.
+    pub async fn info(&self) -> Result<JSONResponse> {
+        self.get("info", &[]).await
+    }
+
+    pub async fn check(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("check", &[("username", username), ("key", key)])
+            .await
+    }
+
+    pub async fn settings(
+        &self,
+        username: &str,
+        key: &str,
+        update: &SettingsUpdate,
+    ) -> Result<JSONResponse> {
+        let update = update.params();
+        let mut params = vec![("username", username), ("key", key)];
+        params.extend(update.iter().map(|(name, value)| (*name, value.as_str())));
+
+        self.get("settings", &params).await
+    }
+
+    /// Sets the user's bio, an empty one removes it.
+    pub async fn set_bio(&self, username: &str, key: &str, bio: &str) -> Result<JSONResponse> {
+        self.get(
+            "setbio",
+            &[("username", username), ("key", key), ("bio", bio)],
+        )
+        .await
+    }
+
+    /// Sets the user's website, an empty one removes it.
+    pub async fn set_website(
+        &self,
+        username: &str,
+        key: &str,
+        website: &str,
+    ) -> Result<JSONResponse> {
+        self.get(
+            "setwebsite",
+            &[("username", username), ("key", key), ("website", website)],
+        )
+        .await
+    }
+
+    /// Sets the user's social handle, an empty one removes it.
+    pub async fn set_social(
+        &self,
+        username: &str,
+        key: &str,
+        social: &str,
+    ) -> Result<JSONResponse> {
+        self.get(
+            "setsocial",
+            &[("username", username), ("key", key), ("social", social)],
+        )
+        .await
+    }
+
+    /// Sets the user's pronouns, empty ones remove them.
+    pub async fn set_pronouns(
+        &self,
+        username: &str,
+        key: &str,
+        pronouns: &str,
+    ) -> Result<JSONResponse> {
+        self.get(
+            "setpronouns",
+            &[("username", username), ("key", key), ("pronouns", pronouns)],
+        )
+        .await
+    }
+
+    /// Sets the user's location, an empty one removes it.
+    pub async fn set_location(
+        &self,
+        username: &str,
+        key: &str,
+        location: &str,
+    ) -> Result<JSONResponse> {
+        self.get(
+            "setlocation",
+            &[("username", username), ("key", key), ("location", location)],
+        )
+        .await
+    }
+
+    /// Sets the user's IANA timezone like `Europe/Berlin`, an empty one removes it.
+    pub async fn set_timezone(
+        &self,
+        username: &str,
+        key: &str,
+        timezone: &str,
+    ) -> Result<JSONResponse> {
+        self.get(
+            "settimezone",
+            &[("username", username), ("key", key), ("timezone", timezone)],
+        )
+        .await
+    }
+
+    /// Points the user's avatar at `url`, an empty one removes it.
+    pub async fn set_avatar(&self, username: &str, key: &str, url: &str) -> Result<JSONResponse> {
+        self.get(
+            "setavatar",
+            &[("username", username), ("key", key), ("avatar", url)],
+        )
+        .await
+    }
+
+    /// Uploads `image` as the user's avatar, `content_type` is one of `networking::IMAGE_TYPES`.
+    pub async fn upload_avatar(
+        &self,
+        username: &str,
+        key: &str,
+        content_type: &str,
+        image: &[u8],
+    ) -> Result<JSONResponse> {
+        self.send(
+            "POST",
+            "setavatar",
+            &[("username", username), ("key", key)],
+            Some((content_type, image)),
+        )
+        .await
+    }
+
+    pub async fn list(&self) -> Result<JSONResponse> {
+        self.get("list", &[]).await
+    }
+
+    /// Lists the users `username` follows, the ones online most recently first.
+    pub async fn following(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get(
+            "list",
+            &[("username", username), ("key", key), ("following", "true")],
+        )
+        .await
+    }
+
+    pub async fn follow(&self, username: &str, key: &str, user: &str) -> Result<JSONResponse> {
+        self.get(
+            "follow",
+            &[("username", username), ("key", key), ("user", user)],
+        )
+        .await
+    }
+
+    pub async fn unfollow(&self, username: &str, key: &str, user: &str) -> Result<JSONResponse> {
+        self.get(
+            "unfollow",
+            &[("username", username), ("key", key), ("user", user)],
+        )
+        .await
+    }
+
+    /// Fingers everyone in `group`, with how many of them are online.
+    pub async fn group(&self, group: &str) -> Result<JSONResponse> {
+        self.get("finger", &[("group", group)]).await
+    }
+
+    pub async fn join_group(&self, username: &str, key: &str, group: &str) -> Result<JSONResponse> {
+        self.get(
+            "joingroup",
+            &[("username", username), ("key", key), ("group", group)],
+        )
+        .await
+    }
+
+    pub async fn leave_group(
+        &self,
+        username: &str,
+        key: &str,
+        group: &str,
+    ) -> Result<JSONResponse> {
+        self.get(
+            "leavegroup",
+            &[("username", username), ("key", key), ("group", group)],
+        )
+        .await
+    }
+
+    /// Registers `username`, the new key is returned in `JSONResponse::OK`.
+    pub async fn register(&self, username: &str, regkey: Option<&str>) -> Result<JSONResponse> {
+        let mut params = vec![("username", username)];
+        if let Some(regkey) = regkey {
+            params.push(("regkey", regkey));
+        }
+
+        self.get("register", &params).await
+    }
+
+    pub async fn deregister(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("deregister", &[("username", username), ("key", key)])
+            .await
+    }
+
+    /// Replaces the key of `username` with `new_key`, which must be a uuid.
+    pub async fn passwd(&self, username: &str, key: &str, new_key: &str) -> Result<JSONResponse> {
+        self.get(
+            "passwd",
+            &[("username", username), ("key", key), ("new_key", new_key)],
+        )
+        .await
+    }
+
+    /// Replaces the key of `username` with a random one, returned in `JSONResponse::OK`.
+    pub async fn rotate_key(&self, username: &str, key: &str) -> Result<JSONResponse> {
+        self.get("rotatekey", &[("username", username), ("key", key)])
+            .await
+    }
+
+    /// Sends `action` with `params` as a `GET`, for actions without a method of their own.
+    pub async fn get(&self, action: &str, params: &[(&str, &str)]) -> Result<JSONResponse> {
+        self.send("GET", action, params, None).await
+    }
+
+    /// Sends a request, with `body` and its content type as a `POST` body if given.
+    async fn send(
+        &self,
+        method: &str,
+        action: &str,
+        params: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<JSONResponse> {
+        let stream = TcpStream::connect(&self.address).await?;
+        let mut stream = BufReader::new(stream);
+
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n{}: {}\r\n",
+            method,
+            path(action, params),
+            self.address,
+            VERSION_HEADER,
+            // the version that answers in `JSONResponse`
+            OLDEST_API_VERSION
+        );
+        for (name, value) in &self.headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if let Some((content_type, body)) = body {
+            request.push_str(&format!(
+                "Content-Type: {}\r\nContent-Length: {}\r\n",
+                content_type,
+                body.len()
+            ));
+        }
+        request.push_str("\r\n");
+        stream.get_mut().write_all(request.as_bytes()).await?;
+        if let Some((_, body)) = body {
+            stream.get_mut().write_all(body).await?;
+        }
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        if !line.starts_with("HTTP/1.1 ") {
+            return Err(FngrError::Parse(format!(
+                "invalid response from server: '{}'",
+                line.trim()
+            )));
+        }
+
+        let mut length = None;
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await?;
+
+            if line.is_empty() || line == "\r\n" || line == "\n" {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+
+        let mut body = vec![];
+        match length {
+            Some(length) => {
+                body.resize(length, 0);
+                stream.read_exact(&mut body).await?;
+            }
+            None => {
+                stream.read_to_end(&mut body).await?;
+            }
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
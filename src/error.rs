@@ -0,0 +1,109 @@
+//! The errors of the library, so callers can match on what went wrong instead of reading
+//! messages.
+
+use std::{fmt::Display, num::ParseIntError, str::Utf8Error};
+
+use crate::networking::{ResponseStatus, TooLarge};
+
+pub type Result<T, E = FngrError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FngrError {
+    /// A request or response that isn't what it should be, with what is wrong with it.
+    Parse(String),
+    /// A request that went over the limits it is read with.
+    TooLarge(TooLarge),
+    /// A username and key, session or signature that doesn't check out.
+    AuthFailed,
+    /// No user has the username.
+    UserNotFound(String),
+    /// The username is taken already.
+    UserExists(String),
+    /// The users couldn't be read or saved.
+    Storage(Box<dyn std::error::Error + Send + Sync>),
+    /// Reading from or writing to a connection failed.
+    Io(std::io::Error),
+    /// A body that isn't the json it should be.
+    Json(serde_json::Error),
+    /// The server couldn't start, serve or stop, like with a config it can't load or an address
+    /// it can't bind.
+    Server(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl FngrError {
+    /// The status a server answers the error with.
+    pub fn status(&self) -> ResponseStatus {
+        match self {
+            FngrError::Parse(_) | FngrError::Json(_) => ResponseStatus::Bad,
+            FngrError::TooLarge(TooLarge::Body) => ResponseStatus::PayloadTooLarge,
+            FngrError::TooLarge(_) => ResponseStatus::HeadersTooLarge,
+            FngrError::AuthFailed => ResponseStatus::Unauth,
+            FngrError::UserNotFound(_) => ResponseStatus::NotFound,
+            FngrError::UserExists(_) => ResponseStatus::Conflict,
+            FngrError::Storage(_) | FngrError::Io(_) | FngrError::Server(_) => {
+                ResponseStatus::ServerError
+            }
+        }
+    }
+}
+
+impl Display for FngrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FngrError::Parse(e) => e.fmt(f),
+            FngrError::TooLarge(e) => e.fmt(f),
+            FngrError::AuthFailed => "invalid username or key".fmt(f),
+            FngrError::UserNotFound(username) => write!(f, "unknown user '{}'", username),
+            FngrError::UserExists(username) => write!(f, "username '{}' already taken", username),
+            FngrError::Storage(e) => write!(f, "storage: {}", e),
+            FngrError::Io(e) => e.fmt(f),
+            FngrError::Json(e) => e.fmt(f),
+            FngrError::Server(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FngrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FngrError::Storage(e) => Some(e.as_ref()),
+            // its message is the one of the error it wraps already
+            FngrError::Server(e) => e.source(),
+            FngrError::Io(e) => Some(e),
+            FngrError::Json(e) => Some(e),
+            FngrError::TooLarge(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<TooLarge> for FngrError {
+    fn from(e: TooLarge) -> Self {
+        FngrError::TooLarge(e)
+    }
+}
+
+impl From<std::io::Error> for FngrError {
+    fn from(e: std::io::Error) -> Self {
+        FngrError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for FngrError {
+    fn from(e: serde_json::Error) -> Self {
+        FngrError::Json(e)
+    }
+}
+
+impl From<Utf8Error> for FngrError {
+    fn from(e: Utf8Error) -> Self {
+        FngrError::Parse(e.to_string())
+    }
+}
+
+impl From<ParseIntError> for FngrError {
+    fn from(e: ParseIntError) -> Self {
+        FngrError::Parse(e.to_string())
+    }
+}
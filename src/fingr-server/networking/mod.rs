@@ -1,8 +0,0 @@
-mod request;
-mod response;
-mod status;
-
-pub use request::{Action, Request};
-pub use response::{JSONResponse, Response};
-// pub use response::Response;
-pub use status::ResponseStatus;
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+
+/// `~/.config/fngr/config.toml`, every field can be overridden on the command line.
+#[derive(Debug, Default, Deserialize)]
+pub struct ClientConfig {
+    /// `host:port` of the server.
+    pub server: Option<String>,
+    pub username: Option<String>,
+    pub key: Option<String>,
+}
+
+impl ClientConfig {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("fngr").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, or the default one; a missing default config is empty.
+    pub fn load(path: Option<PathBuf>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => match Self::path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(config) => Ok(toml::from_str(&config)?),
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(anyhow!("can't read {}: {}", path.display(), e)),
+        }
+    }
+}
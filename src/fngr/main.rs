@@ -0,0 +1,356 @@
+//! Command line client for fngr servers.
+
+mod config;
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Result, anyhow};
+use clap::{Parser, Subcommand};
+use config::ClientConfig;
+use fngr::{
+    Client, KeepAlive,
+    networking::{JSONResponse, SettingsUpdate},
+};
+
+#[derive(Parser)]
+#[command(version, about = "Talk to a fngr server")]
+struct Cli {
+    /// Config file, `~/.config/fngr/config.toml` by default
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// `host:port` of the server
+    #[arg(long, short, global = true)]
+    server: Option<String>,
+    #[arg(long, short, global = true)]
+    username: Option<String>,
+    #[arg(long, short, global = true)]
+    key: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show a user's status, as yourself if you have credentials
+    Finger { user: String },
+    /// Go online, optionally with a status text
+    Login {
+        #[arg(long)]
+        status: Option<String>,
+    },
+    /// Go offline
+    Logoff,
+    /// Stay online for another while
+    Bump,
+    /// Stay online without bumping for a number of seconds
+    Snooze { duration: u64 },
+    /// Show who fingered you
+    Check,
+    /// Show the server's version, uptime and message of the day
+    Info,
+    /// Set your bio, an empty one removes it
+    Setbio { bio: String },
+    /// Set your website, an empty one removes it
+    Setwebsite { website: String },
+    /// Set your social handle, an empty one removes it
+    Setsocial { social: String },
+    /// Set your pronouns, empty ones remove them
+    Setpronouns { pronouns: String },
+    /// Set where you are, an empty location removes it
+    Setlocation { location: String },
+    /// Set your IANA timezone like Europe/Berlin, an empty one removes it
+    Settimezone { timezone: String },
+    /// Upload an image file as your avatar or point it at a url, an empty url removes it
+    Setavatar { avatar: String },
+    /// Change your settings
+    Settings {
+        /// Keep your lookups out of other users' check
+        #[arg(long)]
+        private: Option<bool>,
+        /// Seconds without a bump before you are set offline, 0 for the server's default
+        #[arg(long)]
+        idle_timeout: Option<u64>,
+        /// Stay out of list
+        #[arg(long)]
+        hidden: Option<bool>,
+        /// Only let your friends finger you
+        #[arg(long)]
+        friends_only: Option<bool>,
+        /// Comma separated usernames of your friends, empty to clear them
+        #[arg(long, value_delimiter = ',')]
+        friends: Option<Vec<String>>,
+        /// Comma separated usernames who can't finger you or see you in list, empty to clear them
+        #[arg(long, value_delimiter = ',')]
+        blocked: Option<Vec<String>>,
+        /// An ntfy topic or topic url to get push notifications on, empty for none
+        #[arg(long)]
+        ntfy: Option<String>,
+        /// A gotify message url with an app token to get push notifications on, empty for none
+        #[arg(long)]
+        gotify: Option<String>,
+        /// What to push, online when someone you follow logs on and finger when someone fingers you
+        #[arg(long, value_delimiter = ',')]
+        push: Option<Vec<String>>,
+    },
+    /// Show every user
+    List {
+        /// Only the users you follow, the ones online most recently first
+        #[arg(long)]
+        following: bool,
+    },
+    /// Follow a user, so they show up in list --following
+    Follow { user: String },
+    /// Stop following a user
+    Unfollow { user: String },
+    /// Show everyone in a group and how many of them are online
+    Group { group: String },
+    /// Join a group, starting it if there is none
+    Join { group: String },
+    /// Leave a group
+    Leave { group: String },
+    /// Register a username and print its key
+    Register {
+        username: String,
+        /// Registration key, if the server needs one
+        #[arg(long)]
+        regkey: Option<String>,
+    },
+    /// Remove your account
+    Deregister,
+    /// Replace your key with one you chose, a uuid
+    Passwd { new_key: String },
+    /// Replace your key with a random one and print it
+    Rotatekey,
+    /// Log in and keep bumping so you stay online while this runs
+    Daemon {
+        #[arg(long)]
+        status: Option<String>,
+        /// Seconds between bumps
+        #[arg(long, default_value_t = 1800)]
+        interval: u64,
+        /// Up to how many seconds are added to each interval at random
+        #[arg(long, default_value_t = 60)]
+        jitter: u64,
+    },
+}
+
+/// Settings from the command line, falling back to the config file.
+struct Settings {
+    server: String,
+    username: Option<String>,
+    key: Option<String>,
+}
+
+impl Settings {
+    fn new(cli: &Cli) -> Result<Self> {
+        let config = ClientConfig::load(cli.config.clone())?;
+
+        Ok(Self {
+            server: cli.server.clone().or(config.server).ok_or(anyhow!(
+                "no server given, set `server` in the config or pass --server"
+            ))?,
+            username: cli.username.clone().or(config.username),
+            key: cli.key.clone().or(config.key),
+        })
+    }
+
+    fn credentials(&self) -> Result<(&str, &str)> {
+        match (&self.username, &self.key) {
+            (Some(username), Some(key)) => Ok((username, key)),
+            _ => Err(anyhow!(
+                "a username and key are needed, set them in the config or pass --username and --key"
+            )),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let settings = Settings::new(&cli)?;
+    let client = Client::new(&settings.server);
+
+    let response = match cli.command {
+        Command::Finger { user } => {
+            let auth = settings.credentials().ok();
+            client.finger(&user, auth).await?
+        }
+        Command::Login { status } => {
+            let (username, key) = settings.credentials()?;
+            client.login(username, key, status.as_deref()).await?
+        }
+        Command::Logoff => {
+            let (username, key) = settings.credentials()?;
+            client.logoff(username, key).await?
+        }
+        Command::Bump => {
+            let (username, key) = settings.credentials()?;
+            client.bump(username, key).await?
+        }
+        Command::Snooze { duration } => {
+            let (username, key) = settings.credentials()?;
+            client.snooze(username, key, duration).await?
+        }
+        Command::Check => {
+            let (username, key) = settings.credentials()?;
+            client.check(username, key).await?
+        }
+        Command::Info => client.info().await?,
+        Command::Setbio { bio } => {
+            let (username, key) = settings.credentials()?;
+            client.set_bio(username, key, &bio).await?
+        }
+        Command::Setwebsite { website } => {
+            let (username, key) = settings.credentials()?;
+            client.set_website(username, key, &website).await?
+        }
+        Command::Setsocial { social } => {
+            let (username, key) = settings.credentials()?;
+            client.set_social(username, key, &social).await?
+        }
+        Command::Setpronouns { pronouns } => {
+            let (username, key) = settings.credentials()?;
+            client.set_pronouns(username, key, &pronouns).await?
+        }
+        Command::Setlocation { location } => {
+            let (username, key) = settings.credentials()?;
+            client.set_location(username, key, &location).await?
+        }
+        Command::Settimezone { timezone } => {
+            let (username, key) = settings.credentials()?;
+            client.set_timezone(username, key, &timezone).await?
+        }
+        Command::Setavatar { avatar } => {
+            let (username, key) = settings.credentials()?;
+            if avatar.is_empty() || avatar.starts_with("https://") || avatar.starts_with("http://")
+            {
+                client.set_avatar(username, key, &avatar).await?
+            } else {
+                let content_type = match PathBuf::from(&avatar)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .map(str::to_ascii_lowercase)
+                    .as_deref()
+                {
+                    Some("png") => "image/png",
+                    Some("jpg" | "jpeg") => "image/jpeg",
+                    Some("gif") => "image/gif",
+                    Some("webp") => "image/webp",
+                    _ => return Err(anyhow!("avatars must be png, jpeg, gif or webp files")),
+                };
+                let image = std::fs::read(&avatar)?;
+                client
+                    .upload_avatar(username, key, content_type, &image)
+                    .await?
+            }
+        }
+        Command::Settings {
+            private,
+            idle_timeout,
+            hidden,
+            friends_only,
+            friends,
+            blocked,
+            ntfy,
+            gotify,
+            push,
+        } => {
+            let (username, key) = settings.credentials()?;
+            let update = SettingsUpdate {
+                private,
+                idle_timeout,
+                hidden,
+                friends_only,
+                friends,
+                blocked,
+                ntfy,
+                gotify,
+                push,
+            };
+            client.settings(username, key, &update).await?
+        }
+        Command::List { following: false } => client.list().await?,
+        Command::List { following: true } => {
+            let (username, key) = settings.credentials()?;
+            client.following(username, key).await?
+        }
+        Command::Follow { user } => {
+            let (username, key) = settings.credentials()?;
+            client.follow(username, key, &user).await?
+        }
+        Command::Unfollow { user } => {
+            let (username, key) = settings.credentials()?;
+            client.unfollow(username, key, &user).await?
+        }
+        Command::Group { group } => client.group(&group).await?,
+        Command::Join { group } => {
+            let (username, key) = settings.credentials()?;
+            client.join_group(username, key, &group).await?
+        }
+        Command::Leave { group } => {
+            let (username, key) = settings.credentials()?;
+            client.leave_group(username, key, &group).await?
+        }
+        Command::Register { username, regkey } => {
+            let response = client.register(&username, regkey.as_deref()).await?;
+            if let JSONResponse::OK(key) = &response {
+                println!("registered '{}', your key is {}", username, key);
+                println!("add `username` and `key` to your config to use it");
+                return Ok(());
+            }
+            response
+        }
+        Command::Deregister => {
+            let (username, key) = settings.credentials()?;
+            client.deregister(username, key).await?
+        }
+        Command::Passwd { new_key } => {
+            let (username, key) = settings.credentials()?;
+            client.passwd(username, key, &new_key).await?
+        }
+        Command::Rotatekey => {
+            let (username, key) = settings.credentials()?;
+            let response = client.rotate_key(username, key).await?;
+            if let JSONResponse::OK(key) = &response {
+                println!("your new key is {}", key);
+                println!("replace `key` in your config with it");
+                return Ok(());
+            }
+            response
+        }
+        Command::Daemon {
+            status,
+            interval,
+            jitter,
+        } => {
+            tracing_subscriber::fmt::init();
+            let (username, key) = settings.credentials()?;
+            let mut keepalive = KeepAlive::new(client, username, key)
+                .interval(Duration::from_secs(interval))
+                .jitter(Duration::from_secs(jitter));
+            if let Some(status) = status {
+                keepalive = keepalive.status(status);
+            }
+
+            tokio::select! {
+                _ = keepalive.run() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+
+            // go offline on the way out instead of waiting for the server to notice
+            let (username, key) = settings.credentials()?;
+            Client::new(&settings.server).logoff(username, key).await?
+        }
+    };
+
+    print(&response)
+}
+
+fn print(response: &JSONResponse) -> Result<()> {
+    if let JSONResponse::Error(e) = response {
+        return Err(anyhow!("{}", e));
+    }
+    print!("{}", response.to_text());
+
+    Ok(())
+}
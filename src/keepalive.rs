@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::{Client, networking::JSONResponse, prelude::*};
+
+/// Keeps a user online by logging in and then bumping on an interval.
+///
+/// Failed requests are retried with exponential backoff, and a bump the server refuses because
+/// the user went offline, e.g. after a server restart, logs in again.
+pub struct KeepAlive {
+    client: Client,
+    username: String,
+    key: String,
+    status: Option<String>,
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+}
+
+impl KeepAlive {
+    /// Bumps every 30 minutes with up to a minute of jitter, well within the server's hour.
+    pub fn new(client: Client, username: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            client,
+            username: username.into(),
+            key: key.into(),
+            status: None,
+            interval: Duration::from_secs(30 * 60),
+            jitter: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Status text to log in with.
+    pub fn status(mut self, status: impl Into<String>) -> Self {
+        self.status = Some(status.into());
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Up to how much random time is added to each interval, so many clients don't bump in step.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The longest wait between retries of a failed request.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Runs until the task is dropped.
+    pub async fn run(self) {
+        let mut logged_in = false;
+        let mut failures = 0;
+
+        loop {
+            let result = if logged_in {
+                self.client.bump(&self.username, &self.key).await
+            } else {
+                self.client
+                    .login(&self.username, &self.key, self.status.as_deref())
+                    .await
+            };
+
+            let wait = match result {
+                Ok(JSONResponse::Error(e)) => {
+                    if logged_in {
+                        warn!("bump refused, logging in again: {}", e);
+                        logged_in = false;
+                        continue;
+                    }
+
+                    failures += 1;
+                    warn!("login refused: {}", e);
+                    self.backoff(failures)
+                }
+                Ok(_) => {
+                    if !logged_in {
+                        info!("logged in as '{}'", self.username);
+                    }
+                    logged_in = true;
+                    failures = 0;
+                    self.interval + self.jitter.mul_f64(rand::random())
+                }
+                Err(e) => {
+                    failures += 1;
+                    warn!("request failed: {}", e);
+                    self.backoff(failures)
+                }
+            };
+
+            debug!("next request in {}s", wait.as_secs());
+            sleep(wait).await;
+        }
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        let backoff = Duration::from_secs(1u64 << failures.min(16));
+        backoff.min(self.max_backoff) + self.jitter.mul_f64(rand::random()).min(backoff)
+    }
+}
@@ -0,0 +1,408 @@
+#[cfg(feature = "async")]
+use std::future::Future;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "async")]
+pub mod client;
+pub mod error;
+#[cfg(feature = "async")]
+pub mod keepalive;
+pub mod networking;
+#[cfg(feature = "async")]
+pub mod plugin;
+pub mod prelude;
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "async")]
+pub use client::Client;
+pub use error::FngrError;
+#[cfg(feature = "async")]
+pub use keepalive::KeepAlive;
+#[cfg(feature = "async")]
+pub use plugin::{Plugin, Plugins};
+#[cfg(feature = "server")]
+pub use server::{Server, ServerBuilder};
+
+#[cfg(feature = "async")]
+use networking::{Action, Request, Response};
+#[cfg(feature = "async")]
+use prelude::*;
+
+/// The actions a fngr server answers.
+///
+/// Every action receives the shared server state and the parsed request and produces the response to write back.
+/// Implement this to embed fngr or to back it with your own state.
+#[cfg(feature = "async")]
+pub trait Fngr {
+    /// Handle to the server state shared between connections, e.g. `Arc<Mutex<...>>`.
+    type SelfLock: Send + 'static;
+    /// What an action fails with when it can't produce a response at all, like [`FngrError`].
+    type Error: Send + 'static;
+
+    fn login(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn logoff(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn finger(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn check(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// The status texts a user set lately, newest first.
+    fn history(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// How often a user was fingered, logged on and online, or the most fingered users of the
+    /// week without one.
+    fn stats(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// About the server, for clients to show before anyone logs in.
+    fn info(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn bump(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn snooze(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn list(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn register(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    fn deregister(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Sets an online user to the `presence` they asked for, like away or do not disturb.
+    fn set_presence(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Adds `user` to the users the user follows.
+    fn follow(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Removes `user` from the users the user follows.
+    fn unfollow(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Adds the user to `group`, starting it if there is none.
+    fn join_group(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Takes the user out of `group`.
+    fn leave_group(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Turns on a second factor for the user and answers with the `otpauth://` uri to enroll it.
+    fn enroll_2fa(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Turns the user's second factor off again.
+    fn disable_2fa(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Sets the ed25519 `pubkey` the user signs requests with, an empty one removes it.
+    fn set_pubkey(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Replaces the user's key with the `new_key` they chose.
+    fn passwd(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Replaces the user's key with a random one and answers with it.
+    fn rotate_key(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Answers `/.well-known/webfinger` lookups (RFC 7033).
+    fn webfinger(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Answers with the web page served at `/`.
+    fn index(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Answers with an OpenAPI document describing every action.
+    fn openapi(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the settings of the authenticated user.
+    fn settings(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the bio of the authenticated user, an empty one removes it.
+    fn set_bio(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the website of the authenticated user, an empty one removes it.
+    fn set_website(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the social handle of the authenticated user, an empty one removes it.
+    fn set_social(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the pronouns of the authenticated user, empty ones remove them.
+    fn set_pronouns(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the location of the authenticated user, an empty one removes it.
+    fn set_location(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the IANA timezone of the authenticated user, an empty one removes it.
+    fn set_timezone(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Changes the avatar of the authenticated user to an uploaded image or a url,
+    /// an empty url removes it.
+    fn set_avatar(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Answers with the avatar of a user.
+    fn avatar(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Answers with an atom feed of recent changes, of one user if `user` is given.
+    fn feed(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Takes a presence change pushed by a peer server.
+    fn peer(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Sets `user` offline and ends their sessions, for admins.
+    fn admin_logoff(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Removes `user` with everything about them, for admins.
+    fn admin_delete(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Gives `user` a new key and answers with it, for admins.
+    fn admin_reset_key(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Answers with how the server is doing, for admins.
+    fn admin_stats(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Re-reads the config and the users like `SIGHUP` does, for admins.
+    fn admin_reload(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Bans `user`, for `duration` seconds if given, for admins.
+    fn admin_ban(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Lifts the ban of `user`, for admins.
+    fn admin_unban(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Makes a single use invite code for registering, for admins.
+    fn admin_invite(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Lists the registrations waiting for approval, for admins.
+    fn admin_pending(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Lets the pending `user` log in, for admins.
+    fn admin_approve(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// Sets the `members` of `group`, which only admins can change from then on, for admins.
+    fn admin_group(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send;
+
+    /// The plugins the server was built with, none unless an implementation has them.
+    fn plugins(_state: &Self::SelfLock) -> impl Future<Output = Plugins> + Send {
+        async { Plugins::default() }
+    }
+
+    /// Runs the action named by the request through the plugins.
+    fn run_request(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send {
+        async move {
+            let plugins = Self::plugins(&state).await;
+            if plugins.is_empty() {
+                return Self::dispatch(state, req).await;
+            }
+
+            let mut req = req;
+            if let Some(response) = plugins.on_request(&mut req) {
+                return Ok(response);
+            }
+            // the action takes the request, the plugins get a copy
+            let seen = req.clone();
+            let response = Self::dispatch(state, req).await?;
+            Ok(plugins.on_response(&seen, response))
+        }
+    }
+
+    /// Runs the action named by the request, without the plugins.
+    fn dispatch(
+        state: Self::SelfLock,
+        req: Request,
+    ) -> impl Future<Output = Result<Response, Self::Error>> + Send {
+        async move {
+            match req.action {
+                Action::Index => Self::index(state, req).await,
+                Action::OpenApi => Self::openapi(state, req).await,
+                Action::Login => Self::login(state, req).await,
+                Action::Logoff => Self::logoff(state, req).await,
+                Action::Finger => Self::finger(state, req).await,
+                Action::Check => Self::check(state, req).await,
+                Action::History => Self::history(state, req).await,
+                Action::Stats => Self::stats(state, req).await,
+                Action::Info => Self::info(state, req).await,
+                Action::Bump => Self::bump(state, req).await,
+                Action::List => Self::list(state, req).await,
+                Action::Register => Self::register(state, req).await,
+                Action::Deregister => Self::deregister(state, req).await,
+                Action::SetPresence => Self::set_presence(state, req).await,
+                Action::Follow => Self::follow(state, req).await,
+                Action::Unfollow => Self::unfollow(state, req).await,
+                Action::JoinGroup => Self::join_group(state, req).await,
+                Action::LeaveGroup => Self::leave_group(state, req).await,
+                Action::Enroll2fa => Self::enroll_2fa(state, req).await,
+                Action::Disable2fa => Self::disable_2fa(state, req).await,
+                Action::SetPubkey => Self::set_pubkey(state, req).await,
+                Action::Passwd => Self::passwd(state, req).await,
+                Action::RotateKey => Self::rotate_key(state, req).await,
+                Action::Snooze => Self::snooze(state, req).await,
+                Action::WebFinger => Self::webfinger(state, req).await,
+                Action::Settings => Self::settings(state, req).await,
+                Action::SetBio => Self::set_bio(state, req).await,
+                Action::SetWebsite => Self::set_website(state, req).await,
+                Action::SetSocial => Self::set_social(state, req).await,
+                Action::SetPronouns => Self::set_pronouns(state, req).await,
+                Action::SetLocation => Self::set_location(state, req).await,
+                Action::SetTimezone => Self::set_timezone(state, req).await,
+                Action::SetAvatar => Self::set_avatar(state, req).await,
+                Action::Avatar => Self::avatar(state, req).await,
+                Action::Feed => Self::feed(state, req).await,
+                Action::Peer => Self::peer(state, req).await,
+                Action::AdminLogoff => Self::admin_logoff(state, req).await,
+                Action::AdminDelete => Self::admin_delete(state, req).await,
+                Action::AdminResetKey => Self::admin_reset_key(state, req).await,
+                Action::AdminStats => Self::admin_stats(state, req).await,
+                Action::AdminReload => Self::admin_reload(state, req).await,
+                Action::AdminBan => Self::admin_ban(state, req).await,
+                Action::AdminUnban => Self::admin_unban(state, req).await,
+                Action::AdminInvite => Self::admin_invite(state, req).await,
+                Action::AdminPending => Self::admin_pending(state, req).await,
+                Action::AdminApprove => Self::admin_approve(state, req).await,
+                Action::AdminGroup => Self::admin_group(state, req).await,
+                // servers that speak websocket take the connection over before it gets here
+                Action::WebSocket => Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    networking::JSONResponse::Error("/ws needs a websocket upgrade".to_owned()),
+                )),
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! The flat response format of api version 2, where every response is a single object with a
+//! `type` and the fields that go with it, instead of the externally tagged [`JSONResponse`] of
+//! version 1.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    JSONInfo, JSONLookup, JSONProfile, JSONResponse, JSONStats, JSONStatus, JSONStatusChange,
+    JSONUserStats,
+};
+
+/// What an [`ApiResponse`] carries, in its `type` field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseType {
+    #[default]
+    Ok,
+    Error,
+    User,
+    Users,
+    Count,
+    Group,
+    Login,
+    Deregistered,
+    Lookups,
+    History,
+    Log,
+    Stats,
+    UserStats,
+    Leaderboard,
+    Info,
+}
+
+/// A response of api version 2. Only the fields that go with its `type` are set, the rest are
+/// left out of the json.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ApiResponse {
+    #[serde(rename = "type")]
+    pub kind: ResponseType,
+    /// What went wrong, with `error`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// For people to read, with `ok`, `login` and `deregistered`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// With `user`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<ApiUser>,
+    /// With `users`, and the members with `group`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub users: Option<Vec<ApiUser>>,
+    /// The names a batch finger didn't find, with `users`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub not_found: Vec<String>,
+    /// With `count`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// The group's name, with `group`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// How many of the members are online, with `group`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub online: Option<usize>,
+    /// With `login`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub already_online: Option<bool>,
+    /// With `login`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// With `deregistered`, for taking the name back during the cooldown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grace_token: Option<String>,
+    /// With `lookups`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookups: Option<Vec<JSONLookup>>,
+    /// With `history`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<JSONStatusChange>>,
+    /// With `log`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<Vec<String>>,
+    /// With `stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<JSONStats>,
+    /// With `user_stats`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_stats: Option<JSONUserStats>,
+    /// With `leaderboard`, most fingered first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leaderboard: Option<Vec<JSONUserStats>>,
+    /// With `info`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<JSONInfo>,
+}
+
+/// A user with their status, and their profile unless it is a presence update.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ApiUser {
+    pub username: String,
+    pub status: JSONStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<JSONProfile>,
+}
+
+impl ApiResponse {
+    fn new(kind: ResponseType) -> Self {
+        Self {
+            kind,
+            ..Self::default()
+        }
+    }
+}
+
+impl From<JSONResponse> for ApiResponse {
+    fn from(value: JSONResponse) -> Self {
+        match value {
+            JSONResponse::Error(error) => Self {
+                error: Some(error),
+                ..Self::new(ResponseType::Error)
+            },
+            JSONResponse::OK(message) => Self {
+                message: Some(message),
+                ..Self::new(ResponseType::Ok)
+            },
+            JSONResponse::User {
+                username,
+                status,
+                profile,
+            } => Self {
+                user: Some(ApiUser {
+                    username,
+                    status,
+                    profile: profile.map(|profile| *profile),
+                }),
+                ..Self::new(ResponseType::User)
+            },
+            JSONResponse::List(list) => {
+                let (users, not_found) = users(list);
+                Self {
+                    users: Some(users),
+                    not_found,
+                    ..Self::new(ResponseType::Users)
+                }
+            }
+            // only batch fingers have these, inside a list
+            JSONResponse::NotFound(name) => Self {
+                users: Some(vec![]),
+                not_found: vec![name],
+                ..Self::new(ResponseType::Users)
+            },
+            JSONResponse::Count(count) => Self {
+                count: Some(count),
+                ..Self::new(ResponseType::Count)
+            },
+            JSONResponse::Group {
+                name,
+                online,
+                members,
+            } => Self {
+                group: Some(name),
+                online: Some(online),
+                users: Some(users(members).0),
+                ..Self::new(ResponseType::Group)
+            },
+            JSONResponse::Login {
+                message,
+                already_online,
+                session,
+            } => Self {
+                message: Some(message),
+                already_online: Some(already_online),
+                session: Some(session),
+                ..Self::new(ResponseType::Login)
+            },
+            JSONResponse::Deregistered {
+                message,
+                grace_token,
+            } => Self {
+                message: Some(message),
+                grace_token,
+                ..Self::new(ResponseType::Deregistered)
+            },
+            JSONResponse::Lookups(lookups) => Self {
+                lookups: Some(lookups),
+                ..Self::new(ResponseType::Lookups)
+            },
+            JSONResponse::History(history) => Self {
+                history: Some(history),
+                ..Self::new(ResponseType::History)
+            },
+            JSONResponse::Log(log) => Self {
+                log: Some(log),
+                ..Self::new(ResponseType::Log)
+            },
+            JSONResponse::Stats(stats) => Self {
+                stats: Some(stats),
+                ..Self::new(ResponseType::Stats)
+            },
+            JSONResponse::UserStats(stats) => Self {
+                user_stats: Some(stats),
+                ..Self::new(ResponseType::UserStats)
+            },
+            JSONResponse::Leaderboard(board) => Self {
+                leaderboard: Some(board),
+                ..Self::new(ResponseType::Leaderboard)
+            },
+            JSONResponse::Info(info) => Self {
+                info: Some(info),
+                ..Self::new(ResponseType::Info)
+            },
+        }
+    }
+}
+
+/// The users in `list` and the names of the ones that weren't found.
+fn users(list: Vec<JSONResponse>) -> (Vec<ApiUser>, Vec<String>) {
+    let mut users = vec![];
+    let mut not_found = vec![];
+    for item in list {
+        match ApiResponse::from(item) {
+            ApiResponse {
+                user: Some(user), ..
+            } => users.push(user),
+            response => not_found.extend(response.not_found),
+        }
+    }
+
+    (users, not_found)
+}
+
+impl Display for ApiResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            serde_json::to_string(self).map_err(|_| std::fmt::Error)?
+        )
+    }
+}
+
+impl JSONResponse {
+    /// The response as json in the format of api `version`, [`ApiResponse`] from version 2 on.
+    pub fn render(&self, version: u32) -> String {
+        if version >= 2 {
+            ApiResponse::from(self.clone()).to_string()
+        } else {
+            self.to_string()
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    fmt::Write,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{JSONProfile, JSONResponse, JSONStatus, JSONStatusChange, Request};
+use crate::prelude::*;
+
+/// How a response body is rendered, picked per request by [`Format::negotiate`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// For programs, and what anyone gets who doesn't ask for something else.
+    #[default]
+    Json,
+    /// For people at a terminal.
+    Text,
+    /// The plain text in a minimal page, for browsers.
+    Html,
+}
+
+impl Format {
+    /// The format named by the `format` parameter, or else the one the `Accept` header prefers.
+    ///
+    /// `*/*` and anything else we don't serve count for nothing, so clients that don't ask keep getting json.
+    pub fn negotiate(req: &Request) -> Self {
+        if let Some(format) = req.format.as_deref().and_then(|f| f.parse().ok()) {
+            return format;
+        }
+
+        let Some(accept) = req.headers.get("accept") else {
+            return Self::Json;
+        };
+
+        let mut best: Option<(Self, f32)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';').map(str::trim);
+            let format = match parts.next().unwrap_or("").to_ascii_lowercase().as_str() {
+                "application/json" => Self::Json,
+                "text/plain" => Self::Text,
+                "text/html" | "application/xhtml+xml" => Self::Html,
+                _ => continue,
+            };
+            let q = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            // on a tie the first one listed wins
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((format, q));
+            }
+        }
+
+        best.map(|(format, _)| format).unwrap_or_default()
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Text => "text/plain; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    pub fn render(&self, response: &JSONResponse) -> String {
+        match self {
+            Self::Json => response.to_string(),
+            Self::Text => response.to_text(),
+            Self::Html => format!(
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>fngr</title></head>\n<body><pre>{}</pre></body>\n</html>\n",
+                escape_html(&response.to_text())
+            ),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = FngrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "html" => Ok(Self::Html),
+            _ => Err(FngrError::Parse(format!("unknown format '{}'", s))),
+        }
+    }
+}
+
+impl JSONResponse {
+    /// The response as lines of plain text for a person to read.
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+        match self {
+            Self::Error(e) => writeln!(output, "error: {}", e).unwrap(),
+            Self::User {
+                username,
+                status,
+                profile,
+            } => {
+                output.push_str(&user_line(username, status));
+                if let Some(profile) = profile {
+                    output.push_str(&profile_lines(profile));
+                }
+            }
+            Self::List(list) => {
+                if list.is_empty() {
+                    output.push_str("nobody\n");
+                }
+                for item in list {
+                    match item {
+                        Self::User {
+                            username, status, ..
+                        } => output.push_str(&user_line(username, status)),
+                        item => output.push_str(&item.to_text()),
+                    }
+                }
+            }
+            Self::OK(message) => writeln!(output, "{}", message).unwrap(),
+            Self::Lookups(lookups) => {
+                if lookups.is_empty() {
+                    output.push_str("nobody\n");
+                }
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                for lookup in lookups {
+                    let times = if lookup.count == 1 {
+                        "once".to_owned()
+                    } else {
+                        format!("{} times", lookup.count)
+                    };
+                    let mut line = format!(
+                        "{:<16} {:<10} {:>8} ago {}",
+                        lookup.username,
+                        times,
+                        duration(now.saturating_sub(lookup.last)),
+                        lookup.ip.as_deref().unwrap_or("")
+                    );
+                    if let Some(at) = lookup.you_fingered {
+                        line = format!(
+                            "{} (you fingered them {} ago)",
+                            line.trim_end(),
+                            duration(since(at))
+                        );
+                    }
+                    writeln!(output, "{}", line.trim_end()).unwrap();
+                }
+            }
+            Self::History(history) => output.push_str(&history_lines(history)),
+            Self::Count(count) => writeln!(output, "{}", count).unwrap(),
+            Self::NotFound(username) => writeln!(output, "{:<16} not found", username).unwrap(),
+            Self::Group {
+                name,
+                online,
+                members,
+            } => {
+                writeln!(output, "{}: {}/{} online", name, online, members.len()).unwrap();
+                for member in members {
+                    match member {
+                        Self::User {
+                            username, status, ..
+                        } => output.push_str(&user_line(username, status)),
+                        member => output.push_str(&member.to_text()),
+                    }
+                }
+            }
+            Self::Log(lines) => {
+                for line in lines {
+                    writeln!(output, "{}", line).unwrap();
+                }
+            }
+            Self::Login {
+                message,
+                already_online,
+                ..
+            } => {
+                writeln!(output, "{}", message).unwrap();
+                if *already_online {
+                    output.push_str("(you were already online)\n");
+                }
+            }
+            Self::Deregistered {
+                message,
+                grace_token,
+            } => {
+                writeln!(output, "{}", message).unwrap();
+                if let Some(token) = grace_token {
+                    writeln!(
+                        output,
+                        "to claim the name back before its cooldown ends, register with grace={}",
+                        token
+                    )
+                    .unwrap();
+                }
+            }
+            Self::Stats(stats) => {
+                writeln!(output, "users:    {}", stats.users).unwrap();
+                writeln!(output, "online:   {}", stats.online).unwrap();
+                writeln!(output, "remote:   {}", stats.remote_users).unwrap();
+                writeln!(output, "sessions: {}", stats.sessions).unwrap();
+                writeln!(output, "uptime:   {}", duration(stats.uptime_secs)).unwrap();
+            }
+            Self::UserStats(stats) => {
+                writeln!(output, "fingered:  {}", stats.fingered).unwrap();
+                writeln!(output, "this week: {}", stats.fingered_this_week).unwrap();
+                writeln!(output, "logins:    {}", stats.logins).unwrap();
+                writeln!(output, "online:    {}", duration(stats.online_secs)).unwrap();
+                if let Some(rank) = stats.rank {
+                    writeln!(output, "#{} most fingered this week", rank).unwrap();
+                }
+            }
+            Self::Leaderboard(board) => {
+                if board.is_empty() {
+                    output.push_str("nobody\n");
+                }
+                for stats in board {
+                    writeln!(
+                        output,
+                        "{:>3}. {:<16} {}",
+                        stats.rank.unwrap_or_default(),
+                        stats.username,
+                        stats.fingered_this_week
+                    )
+                    .unwrap();
+                }
+            }
+            Self::Info(info) => {
+                writeln!(output, "version:      {}", info.version).unwrap();
+                writeln!(output, "uptime:       {}", duration(info.uptime_secs)).unwrap();
+                writeln!(output, "users:        {}", info.users).unwrap();
+                writeln!(output, "online:       {}", info.online).unwrap();
+                writeln!(
+                    output,
+                    "registration: {}",
+                    info.registration.as_deref().unwrap_or("closed")
+                )
+                .unwrap();
+                if let Some(motd) = &info.motd {
+                    writeln!(output, "\n{}", motd.trim_end()).unwrap();
+                }
+            }
+        }
+        output
+    }
+}
+
+fn user_line(username: &str, status: &JSONStatus) -> String {
+    let state = status.presence.to_string();
+    let line = format!(
+        "{:<16} {:<8} {:>8}  {}",
+        username,
+        state,
+        duration(status.since),
+        status.text.as_deref().unwrap_or("")
+    );
+    format!("{}\n", line.trim_end())
+}
+
+fn profile_lines(profile: &JSONProfile) -> String {
+    let mut output = String::new();
+    if let Some(pronouns) = &profile.pronouns {
+        writeln!(output, "pronouns: {}", pronouns).unwrap();
+    }
+    if let Some(location) = &profile.location {
+        writeln!(output, "location: {}", location).unwrap();
+    }
+    if let (Some(timezone), Some(time)) = (&profile.timezone, &profile.local_time) {
+        // rfc 3339 has the wall clock time at 11..16
+        let time = time.get(11..16).unwrap_or(time);
+        writeln!(output, "time:     {} ({})", time, timezone).unwrap();
+    }
+    if let Some(website) = &profile.website {
+        writeln!(output, "website:  {}", website).unwrap();
+    }
+    if let Some(social) = &profile.social {
+        writeln!(output, "social:   {}", social).unwrap();
+    }
+    if let Some(bio) = &profile.bio {
+        writeln!(output, "bio:      {}", bio).unwrap();
+    }
+    if let Some(avatar) = &profile.avatar {
+        writeln!(output, "avatar:   {}", avatar).unwrap();
+    }
+    if let Some(mail) = &profile.mail {
+        writeln!(output, "mail:     {}", mail).unwrap();
+    }
+    if profile.follows_you {
+        output.push_str("follows you\n");
+    }
+    if let Some(fingered) = profile.fingered_you {
+        writeln!(output, "fingered you {} ago", duration(since(fingered))).unwrap();
+    }
+    if let Some(rank) = profile.fingered_rank {
+        writeln!(output, "#{} most fingered this week", rank).unwrap();
+    }
+    if let Some(history) = &profile.history {
+        output.push_str("history:\n");
+        output.push_str(&history_lines(history));
+    }
+    output
+}
+
+fn history_lines(history: &[JSONStatusChange]) -> String {
+    let mut output = String::new();
+    if history.is_empty() {
+        output.push_str("nothing\n");
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    for change in history {
+        let line = format!(
+            "{:>8} ago {}",
+            duration(now.saturating_sub(change.at)),
+            change.text.as_deref().unwrap_or("(cleared)")
+        );
+        writeln!(output, "{}", line).unwrap();
+    }
+    output
+}
+
+/// Seconds since the unix timestamp `at`.
+fn since(at: u64) -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .saturating_sub(at)
+}
+
+/// How long something took, from a number of seconds.
+fn duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h{}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,236 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// How available a user is, everything but `Offline` counts as online.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Availability {
+    Online,
+    Away,
+    #[serde(rename = "dnd")]
+    DoNotDisturb,
+    #[default]
+    Offline,
+}
+
+impl Availability {
+    pub fn is_online(self) -> bool {
+        self != Self::Offline
+    }
+}
+
+impl FromStr for Availability {
+    type Err = FngrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "online" => Ok(Self::Online),
+            "away" => Ok(Self::Away),
+            "dnd" | "do_not_disturb" => Ok(Self::DoNotDisturb),
+            "offline" => Ok(Self::Offline),
+            _ => Err(FngrError::Parse(format!(
+                "'{}' is not one of online, away, dnd or offline",
+                s
+            ))),
+        }
+    }
+}
+
+impl Display for Availability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Online => "online",
+            Self::Away => "away",
+            Self::DoNotDisturb => "dnd",
+            Self::Offline => "offline",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONStatus {
+    /// Whether the user is logged in, whatever their `presence`.
+    pub online: bool,
+    /// Missing from older servers, see `online` then.
+    #[serde(default)]
+    pub presence: Availability,
+    pub text: Option<String>,
+    pub since: u64,
+    /// Seconds until an online user is set offline unless they bump.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    /// When the user last logged on, logged off or bumped, as an ISO-8601 time.
+    #[serde(default)]
+    pub last_seen: Option<String>,
+}
+
+/// What a user tells others about themselves.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONProfile {
+    pub bio: Option<String>,
+    pub website: Option<String>,
+    pub social: Option<String>,
+    #[serde(default)]
+    pub pronouns: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    /// IANA name like `Europe/Berlin`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// The time in the user's timezone as RFC 3339, set along with `timezone`.
+    #[serde(default)]
+    pub local_time: Option<String>,
+    /// Either a url elsewhere or `/avatar?user=...` on the same server.
+    #[serde(default)]
+    pub avatar: Option<String>,
+    /// Like `No mail.`, only when fingering a user whose mailbox the server knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mail: Option<String>,
+    /// Recent status texts, newest first, only when fingering with `history=1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<Vec<JSONStatusChange>>,
+    /// Whether the user follows whoever fingered them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follows_you: bool,
+    /// Unix timestamp of when the user last fingered whoever fingered them, if they did lately.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingered_you: Option<u64>,
+    /// The user's place among the most fingered users of the week, if they are on the board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingered_rank: Option<usize>,
+}
+
+/// A status text a user set, as returned by `history`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONStatusChange {
+    /// `None` when they cleared it.
+    pub text: Option<String>,
+    /// Unix timestamp of when they set it.
+    pub at: u64,
+}
+
+/// Someone who fingered a user, as returned by `check`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONLookup {
+    /// `anonymous` unless they were logged in.
+    pub username: String,
+    /// Only recorded if the server is configured to.
+    pub ip: Option<String>,
+    pub count: u32,
+    /// Unix timestamps of their first and last lookup since the last `check`.
+    pub first: u64,
+    pub last: u64,
+    /// Unix timestamp of when you last fingered them, if you did lately.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub you_fingered: Option<u64>,
+}
+
+/// How the server is doing, as returned by `admin/stats`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONStats {
+    pub users: usize,
+    pub online: usize,
+    /// Users of peer servers.
+    pub remote_users: usize,
+    pub sessions: usize,
+    pub uptime_secs: u64,
+}
+
+/// About the server, as returned by `info`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONInfo {
+    pub version: String,
+    pub uptime_secs: u64,
+    pub users: usize,
+    pub online: usize,
+    /// How new users get in, `open`, `invite` or `approval`. Left out when registration is
+    /// closed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration: Option<String>,
+    /// The message of the day, if the admin set one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motd: Option<String>,
+}
+
+/// What a user has been up to, as returned by `stats`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct JSONUserStats {
+    pub username: String,
+    /// How often others fingered them.
+    pub fingered: u64,
+    /// The same since monday, in UTC.
+    pub fingered_this_week: u64,
+    pub logins: u64,
+    pub online_secs: u64,
+    /// Their place among the most fingered users of the week, if they are on the board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub enum JSONResponse {
+    Error(String),
+    User {
+        username: String,
+        status: JSONStatus,
+        /// Left out of presence updates.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        profile: Option<Box<JSONProfile>>,
+    },
+    List(Vec<Self>),
+    OK(String),
+    Log(Vec<String>),
+    Lookups(Vec<JSONLookup>),
+    History(Vec<JSONStatusChange>),
+    /// How many users `list` would have returned, with `count=true`.
+    Count(usize),
+    /// A user a batch finger didn't find, by the name it was asked for.
+    NotFound(String),
+    /// The members of a group, as returned by `finger?group=...`.
+    Group {
+        name: String,
+        /// How many of the members are online.
+        online: usize,
+        members: Vec<Self>,
+    },
+    Login {
+        message: String,
+        already_online: bool,
+        session: String,
+    },
+    Deregistered {
+        message: String,
+        grace_token: Option<String>,
+    },
+    Stats(JSONStats),
+    UserStats(JSONUserStats),
+    /// The most fingered users of the week, most first.
+    Leaderboard(Vec<JSONUserStats>),
+    Info(JSONInfo),
+}
+
+impl JSONResponse {
+    /// The text meant for a person to read, for responses that have one.
+    pub fn message_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Self::Error(message)
+            | Self::OK(message)
+            | Self::Login { message, .. }
+            | Self::Deregistered { message, .. } => Some(message),
+            _ => None,
+        }
+    }
+}
+
+impl Display for JSONResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            serde_json::to_string(self).map_err(|_| std::fmt::Error)?
+        )
+    }
+}
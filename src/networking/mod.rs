@@ -0,0 +1,28 @@
+mod api;
+mod format;
+mod json;
+mod presence;
+mod request;
+#[cfg(feature = "async")]
+mod response;
+mod status;
+
+pub use api::{ApiResponse, ApiUser, ResponseType};
+pub use format::Format;
+pub use json::{
+    Availability, JSONInfo, JSONLookup, JSONProfile, JSONResponse, JSONStats, JSONStatus,
+    JSONStatusChange, JSONUserStats,
+};
+pub use presence::{Change, Presence};
+#[cfg(feature = "async")]
+pub use request::Limits;
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub use request::SettingsUpdate;
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub(crate) use request::path;
+pub use request::{
+    API_VERSION, Action, IMAGE_TYPES, Method, OLDEST_API_VERSION, Request, TooLarge, VERSION_HEADER,
+};
+#[cfg(feature = "async")]
+pub use response::Response;
+pub use status::ResponseStatus;
@@ -0,0 +1,47 @@
+//! Changes in who is online, for anything that wants to follow them.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::JSONStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Login,
+    Logoff,
+    /// Set offline by the server after going too long without a bump.
+    Offline,
+    /// Set away by the server after going too long without a bump.
+    Away,
+    /// Changed their presence with `setpresence`.
+    Presence,
+}
+
+/// A change as webhooks, peers and plugins get it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    #[serde(rename = "event")]
+    pub change: Change,
+    pub username: String,
+    pub status: JSONStatus,
+}
+
+/// Like `alice is online: coding`, for chat rooms.
+impl fmt::Display for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let username = &self.username;
+        match self.change {
+            Change::Login => write!(f, "{} is online", username)?,
+            Change::Logoff => return write!(f, "{} logged off", username),
+            Change::Offline => return write!(f, "{} went offline", username),
+            Change::Away => return write!(f, "{} is away", username),
+            Change::Presence => write!(f, "{} is now {}", username, self.status.presence)?,
+        }
+        match &self.status.text {
+            Some(text) => write!(f, ": {}", text),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,903 @@
+use crate::prelude::*;
+use percent_encoding::percent_decode_str;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use std::{collections::HashMap, fmt::Display, net::IpAddr, str::FromStr};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+// largest image body, servers can set a lower limit of their own
+#[cfg(feature = "async")]
+const MAX_UPLOAD: usize = 1024 * 1024;
+
+/// The newest version of the api this crate speaks, asked for with a prefix like `/v2` or the
+/// [`VERSION_HEADER`], and told in the same header of every response.
+pub const API_VERSION: u32 = 2;
+/// What bare paths without the header get, with the [`JSONResponse`](super::JSONResponse) format.
+pub const OLDEST_API_VERSION: u32 = 1;
+pub const VERSION_HEADER: &str = "X-Fngr-Version";
+
+/// Content types of the images a request body may carry.
+pub const IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+#[derive(Clone)]
+pub struct Request {
+    pub method: Method,
+    pub action: Action,
+    /// The api version asked for, [`OLDEST_API_VERSION`] for bare paths without the header.
+    pub api_version: u32,
+    pub username: Option<String>,
+    pub key: Option<String>,
+    pub finger_user: Option<String>,
+    pub status: Option<String>,
+    pub duration: Option<String>,
+    pub grace: Option<String>,
+    pub resource: Option<String>,
+    pub private: Option<String>,
+    pub idle_timeout: Option<String>,
+    pub hidden: Option<String>,
+    pub friends_only: Option<String>,
+    /// Comma separated usernames.
+    pub friends: Option<String>,
+    /// Comma separated usernames.
+    pub blocked: Option<String>,
+    /// An ntfy topic or topic url to push notifications to.
+    pub ntfy: Option<String>,
+    /// A gotify message url with an app token to push notifications to.
+    pub gotify: Option<String>,
+    /// What to push, comma separated `online` and `finger`.
+    pub push: Option<String>,
+    pub online: Option<String>,
+    /// One of `online`, `away`, `dnd` or `offline`.
+    pub presence: Option<String>,
+    pub history: Option<String>,
+    pub prefix: Option<String>,
+    pub search: Option<String>,
+    pub sort: Option<String>,
+    pub count: Option<String>,
+    pub limit: Option<String>,
+    pub offset: Option<String>,
+    pub following: Option<String>,
+    pub since: Option<String>,
+    pub bio: Option<String>,
+    pub website: Option<String>,
+    pub social: Option<String>,
+    pub pronouns: Option<String>,
+    pub location: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar: Option<String>,
+    /// Why an admin bans a user.
+    pub reason: Option<String>,
+    /// A totp code, for users with a second factor.
+    pub code: Option<String>,
+    /// A hex ed25519 public key for `setpubkey`.
+    pub pubkey: Option<String>,
+    /// The key to replace the user's with in `passwd`.
+    pub new_key: Option<String>,
+    /// The server's `auth_key`, to register with.
+    pub regkey: Option<String>,
+    /// A code from `admin/invite` to register with.
+    pub invite: Option<String>,
+    pub group: Option<String>,
+    /// Comma separated usernames, for `admin/group`.
+    pub members: Option<String>,
+    /// Overrides the `Accept` header, one of `json`, `text` or `html`.
+    pub format: Option<String>,
+    /// An image sent as the `POST` body, one of [`IMAGE_TYPES`].
+    pub body: Option<Vec<u8>>,
+    /// Header names are lowercase, repeated headers are joined with `, `.
+    pub headers: HashMap<String, String>,
+    /// Whether the client wants the connection kept open after the response.
+    pub keep_alive: bool,
+    /// Address of the client, if it connected over ip.
+    pub peer: Option<IpAddr>,
+}
+
+impl Request {
+    /// Builds a request from an already read request line and headers.
+    pub fn new(
+        method: Method,
+        target: &str,
+        version: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<Self> {
+        let (path, query) = split_target(target)?;
+        let (api_version, path) = api_version(path, &headers)?;
+        let action = path.parse()?;
+
+        let connection = headers
+            .get("connection")
+            .map(|c| c.to_ascii_lowercase())
+            .unwrap_or_default();
+        let keep_alive = match version {
+            "HTTP/1.1" => !connection.contains("close"),
+            "HTTP/1.0" => connection.contains("keep-alive"),
+            v => {
+                return Err(FngrError::Parse(format!(
+                    "unsupported http version: '{}'",
+                    v
+                )));
+            }
+        };
+
+        let mut request = Request {
+            method,
+            action,
+            api_version,
+            username: None,
+            key: None,
+            finger_user: None,
+            status: None,
+            duration: None,
+            grace: None,
+            resource: None,
+            private: None,
+            idle_timeout: None,
+            hidden: None,
+            friends_only: None,
+            friends: None,
+            blocked: None,
+            ntfy: None,
+            gotify: None,
+            push: None,
+            online: None,
+            presence: None,
+            history: None,
+            prefix: None,
+            search: None,
+            sort: None,
+            count: None,
+            limit: None,
+            offset: None,
+            following: None,
+            since: None,
+            bio: None,
+            website: None,
+            social: None,
+            pronouns: None,
+            location: None,
+            timezone: None,
+            avatar: None,
+            reason: None,
+            code: None,
+            pubkey: None,
+            new_key: None,
+            regkey: None,
+            invite: None,
+            group: None,
+            members: None,
+            format: None,
+            body: None,
+            headers,
+            keep_alive,
+            peer: None,
+        };
+
+        request.set_urlencoded(query)?;
+
+        Ok(request)
+    }
+
+    /// Sets the parameters carried in a `POST` body, overriding any from the query.
+    pub fn set_body(&mut self, content_type: &str, body: &[u8]) -> Result<()> {
+        // drop parameters like `; charset=utf-8`
+        let content_type = content_type.split(';').next().unwrap_or("").trim();
+
+        match content_type {
+            "application/x-www-form-urlencoded" => {
+                self.set_urlencoded(std::str::from_utf8(body)?)?;
+            }
+            "application/json" => {
+                let body: HashMap<String, serde_json::Value> = serde_json::from_slice(body)?;
+                for (name, value) in body {
+                    match value {
+                        serde_json::Value::String(value) => self.set_param(&name, &value),
+                        serde_json::Value::Number(value) => {
+                            self.set_param(&name, &value.to_string())
+                        }
+                        serde_json::Value::Null => {}
+                        _ => {
+                            return Err(FngrError::Parse(format!(
+                                "'{}' must be a string or a number",
+                                name
+                            )));
+                        }
+                    }
+                }
+            }
+            t if IMAGE_TYPES.contains(&t) => self.body = Some(body.to_vec()),
+            t => {
+                return Err(FngrError::Parse(format!(
+                    "unsupported content type: '{}'",
+                    t
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the parameters of a query string or form body like `user=foo&status=out+to+lunch`.
+    fn set_urlencoded(&mut self, params: &str) -> Result<()> {
+        for param in params.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            self.set_param(&decode(name)?, &decode(value)?);
+        }
+
+        Ok(())
+    }
+
+    /// Sets a request parameter by its query string name, unknown names are ignored.
+    pub fn set_param(&mut self, name: &str, value: &str) {
+        let value = Some(value.to_owned());
+
+        match name {
+            "username" => self.username = value,
+            "key" => self.key = value,
+            // a repeated `user` is a batch finger, like `user=alice,bob`
+            "user" => {
+                self.finger_user = match self.finger_user.take() {
+                    Some(users) => value.map(|value| format!("{},{}", users, value)),
+                    None => value,
+                }
+            }
+            "status" => self.status = value,
+            "duration" => self.duration = value,
+            "grace" => self.grace = value,
+            "resource" => self.resource = value,
+            "private" => self.private = value,
+            "idle_timeout" => self.idle_timeout = value,
+            "hidden" => self.hidden = value,
+            "friends_only" => self.friends_only = value,
+            "friends" => self.friends = value,
+            "blocked" => self.blocked = value,
+            "ntfy" => self.ntfy = value,
+            "gotify" => self.gotify = value,
+            "push" => self.push = value,
+            "online" => self.online = value,
+            "presence" => self.presence = value,
+            "history" => self.history = value,
+            "prefix" => self.prefix = value,
+            "search" => self.search = value,
+            "sort" => self.sort = value,
+            "count" => self.count = value,
+            "limit" => self.limit = value,
+            "offset" => self.offset = value,
+            "following" => self.following = value,
+            "since" => self.since = value,
+            "bio" => self.bio = value,
+            "website" => self.website = value,
+            "social" => self.social = value,
+            "pronouns" => self.pronouns = value,
+            "location" => self.location = value,
+            "timezone" => self.timezone = value,
+            "avatar" => self.avatar = value,
+            "reason" => self.reason = value,
+            "code" => self.code = value,
+            "pubkey" => self.pubkey = value,
+            "new_key" => self.new_key = value,
+            "regkey" => self.regkey = value,
+            "invite" => self.invite = value,
+            "group" => self.group = value,
+            "members" => self.members = value,
+            "format" => self.format = value,
+            _ => {}
+        }
+    }
+}
+
+/// How much of a request [`Request::parse`] is willing to read.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Longest request line in bytes.
+    pub request_line: usize,
+    /// Most bytes all the headers may take together.
+    pub header_bytes: usize,
+    /// Most headers a request may have.
+    pub headers: usize,
+    /// Largest body that isn't an image, in bytes.
+    pub body: usize,
+}
+
+#[cfg(feature = "async")]
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            request_line: 8 * 1024,
+            header_bytes: 16 * 1024,
+            headers: 64,
+            body: 16 * 1024,
+        }
+    }
+}
+
+/// The part of a request that went over its [`Limits`], so servers can answer with the right
+/// status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TooLarge {
+    RequestLine,
+    Headers,
+    Body,
+}
+
+impl Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TooLarge::RequestLine => "request line is too long",
+            TooLarge::Headers => "request headers are too large",
+            TooLarge::Body => "request body is too large",
+        }
+        .fmt(f)
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// Reads a line of at most `max` bytes into `buffer`, which is `too_large` if it is longer.
+#[cfg(feature = "async")]
+async fn read_line(
+    stream: &mut (impl AsyncBufRead + Unpin),
+    buffer: &mut String,
+    max: usize,
+    too_large: TooLarge,
+) -> Result<usize> {
+    let read = (&mut *stream).take(max as u64).read_line(buffer).await?;
+    if read == max && !buffer.ends_with('\n') {
+        return Err(too_large.into());
+    }
+
+    Ok(read)
+}
+
+#[cfg(feature = "async")]
+impl Request {
+    /// Reads the next request from the stream, `None` if it was closed before one started.
+    /// Anything over `limits` is refused with a [`TooLarge`] error. A server under a prefix, like
+    /// `/fngr`, passes it as `base_path` to have it taken off every path, or `""`.
+    pub async fn parse(
+        mut stream: impl AsyncBufRead + Unpin,
+        limits: &Limits,
+        base_path: &str,
+    ) -> Result<Option<Self>> {
+        let mut line_buffer = String::new();
+
+        // empty lines before a request line are allowed and ignored
+        loop {
+            line_buffer.clear();
+            let read = read_line(
+                &mut stream,
+                &mut line_buffer,
+                limits.request_line,
+                TooLarge::RequestLine,
+            )
+            .await?;
+            if read == 0 {
+                return Ok(None);
+            }
+
+            if !line_buffer.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = line_buffer.split_whitespace();
+        let method: Method = parts
+            .next()
+            .ok_or(FngrError::Parse("invalid request type".to_owned()))?
+            .parse()?;
+        let target = parts
+            .next()
+            .ok_or(FngrError::Parse("missing path".to_owned()))?
+            .to_owned();
+        let version = parts
+            .next()
+            .ok_or(FngrError::Parse("missing http version".to_owned()))?
+            .to_owned();
+
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let mut header_bytes = 0;
+        let mut header_count = 0;
+
+        loop {
+            line_buffer.clear();
+            let read = read_line(
+                &mut stream,
+                &mut line_buffer,
+                limits.header_bytes - header_bytes,
+                TooLarge::Headers,
+            )
+            .await?;
+            if read == 0 {
+                return Err(FngrError::Parse(
+                    "connection closed in the middle of a request".to_owned(),
+                ));
+            }
+
+            if line_buffer == "\n" || line_buffer == "\r\n" {
+                break;
+            }
+
+            header_bytes += read;
+            header_count += 1;
+            if header_count > limits.headers {
+                return Err(TooLarge::Headers.into());
+            }
+
+            let (name, value) = line_buffer.split_once(':').ok_or(FngrError::Parse(format!(
+                "invalid header: '{}'",
+                line_buffer.trim()
+            )))?;
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            headers
+                .entry(name)
+                .and_modify(|v| {
+                    v.push_str(", ");
+                    v.push_str(value);
+                })
+                .or_insert(value.to_owned());
+        }
+
+        let target = strip_base(&target, base_path)?;
+        let mut request = Self::new(method, &target, &version, headers)?;
+
+        // the body is only ever framed by its length, anything else could be read as the next
+        // request
+        if request.headers.contains_key("transfer-encoding") {
+            return Err(FngrError::Parse(
+                "transfer encodings aren't supported, send a content length".to_owned(),
+            ));
+        }
+        let length: Option<usize> = request
+            .headers
+            .get("content-length")
+            .map(|length| length.parse())
+            .transpose()?;
+
+        if method == Method::Post {
+            let length =
+                length.ok_or(FngrError::Parse("a content length is required".to_owned()))?;
+
+            let content_type = request
+                .headers
+                .get("content-type")
+                .cloned()
+                .unwrap_or_default();
+            let max = if content_type.starts_with("image/") {
+                MAX_UPLOAD
+            } else {
+                limits.body
+            };
+
+            if length > max {
+                return Err(TooLarge::Body.into());
+            }
+
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await?;
+
+            request.set_body(&content_type, &body)?;
+        } else if let Some(length) = length.filter(|length| *length > 0) {
+            // other methods don't use a body, but it is read past so the next request starts
+            // where it should
+            if length > limits.body {
+                return Err(TooLarge::Body.into());
+            }
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).await?;
+        }
+
+        Ok(Some(request))
+    }
+}
+
+/// Decodes a percent-encoded query component, where `+` is a space.
+fn decode(s: &str) -> Result<String> {
+    let s = s.replace('+', " ");
+    Ok(percent_decode_str(&s).decode_utf8()?.into_owned())
+}
+
+/// The path and query of a request target, absolute targets like `http://host/list` reduced to
+/// them.
+fn origin_form(target: &str) -> &str {
+    match target.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+        None => target,
+    }
+}
+
+/// `target` with `base_path` taken off, so `/fngr/list` under `/fngr` is `/list`.
+#[cfg(feature = "async")]
+fn strip_base<'a>(target: &'a str, base_path: &str) -> Result<std::borrow::Cow<'a, str>> {
+    use std::borrow::Cow;
+
+    if base_path.is_empty() {
+        return Ok(Cow::Borrowed(target));
+    }
+
+    let target = origin_form(target);
+    match target.strip_prefix(base_path) {
+        Some(rest) if rest.starts_with('/') => Ok(Cow::Borrowed(rest)),
+        Some(rest) if rest.is_empty() || rest.starts_with('?') => {
+            Ok(Cow::Owned(format!("/{}", rest)))
+        }
+        _ => Err(FngrError::Parse(format!(
+            "'{}' is not under {}",
+            target, base_path
+        ))),
+    }
+}
+
+/// Splits a request target into the path without its leading `/` and the query.
+/// Absolute targets like `http://host/list` are reduced to their path first.
+fn split_target(target: &str) -> Result<(&str, &str)> {
+    let target = origin_form(target);
+
+    let target = target
+        .strip_prefix('/')
+        .ok_or(FngrError::Parse(format!("invalid path: '{}'", target)))?;
+
+    Ok(target.split_once('?').unwrap_or((target, "")))
+}
+
+/// The api version a request asks for and its path without a prefix like `v2/`. Bare paths are
+/// the same as `v1/` ones.
+fn api_version<'a>(path: &'a str, headers: &HashMap<String, String>) -> Result<(u32, &'a str)> {
+    let prefixed = path.strip_prefix('v').and_then(|rest| {
+        let (version, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        let digits = !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit());
+        digits.then(|| (version.parse::<u32>(), rest))
+    });
+    let (from_path, path) = match prefixed {
+        Some((Ok(version), rest)) => (Some(version), rest),
+        Some((Err(_), _)) => return Err(FngrError::Parse(format!("invalid path: '{}'", path))),
+        None => (None, path),
+    };
+    let from_header =
+        match headers.get(&VERSION_HEADER.to_ascii_lowercase()) {
+            Some(version) => Some(version.trim().parse::<u32>().map_err(|_| {
+                FngrError::Parse(format!("invalid {}: '{}'", VERSION_HEADER, version))
+            })?),
+            None => None,
+        };
+
+    let version = match (from_path, from_header) {
+        (Some(path), Some(header)) if path != header => {
+            return Err(FngrError::Parse(format!(
+                "the path asks for api version {} but {} for {}",
+                path, VERSION_HEADER, header
+            )));
+        }
+        (from_path, from_header) => from_path.or(from_header).unwrap_or(OLDEST_API_VERSION),
+    };
+    if !(OLDEST_API_VERSION..=API_VERSION).contains(&version) {
+        return Err(FngrError::Parse(format!(
+            "api version {} isn't supported, only {} to {}",
+            version, OLDEST_API_VERSION, API_VERSION
+        )));
+    }
+
+    Ok((version, path))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    /// A cors preflight, answered by the server without running the action.
+    Options,
+}
+
+impl FromStr for Method {
+    type Err = FngrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GET" => Ok(Self::Get),
+            "HEAD" => Ok(Self::Head),
+            "POST" => Ok(Self::Post),
+            "OPTIONS" => Ok(Self::Options),
+            _ => Err(FngrError::Parse(format!("invalid request type: '{}'", s))),
+        }
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let method = match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Options => "OPTIONS",
+        };
+        write!(f, "{}", method)
+    }
+}
+
+/// Builds the path of a request for `action` with `params` as its query.
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub(crate) fn path(action: &str, params: &[(&str, &str)]) -> String {
+    let query = params
+        .iter()
+        .map(|(k, v)| {
+            format!(
+                "{}={}",
+                utf8_percent_encode(k, NON_ALPHANUMERIC),
+                utf8_percent_encode(v, NON_ALPHANUMERIC)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    if query.is_empty() {
+        format!("/{}", action)
+    } else {
+        format!("/{}?{}", action, query)
+    }
+}
+
+/// Settings to change with a `settings` request, `None` leaves a setting as it is.
+#[cfg(any(feature = "async", feature = "blocking"))]
+#[derive(Debug, Clone, Default)]
+pub struct SettingsUpdate {
+    /// Keeps the user's lookups out of other users' `check`.
+    pub private: Option<bool>,
+    /// Seconds without a bump before the user is set offline, `0` goes back to the server's default.
+    pub idle_timeout: Option<u64>,
+    /// Keeps the user out of `list`.
+    pub hidden: Option<bool>,
+    /// Only lets `friends` finger the user.
+    pub friends_only: Option<bool>,
+    /// Replaces the user's friends, an empty list clears them.
+    pub friends: Option<Vec<String>>,
+    /// Replaces the users the user blocked, an empty list clears them.
+    pub blocked: Option<Vec<String>>,
+    /// An ntfy topic, or the url of one, to push notifications to, empty for none.
+    pub ntfy: Option<String>,
+    /// A gotify message url with an app token to push notifications to, empty for none.
+    pub gotify: Option<String>,
+    /// What to push, `online` when someone you follow logs on and `finger` when someone
+    /// fingers you. An empty list pushes nothing.
+    pub push: Option<Vec<String>>,
+}
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+impl SettingsUpdate {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![];
+        if let Some(private) = self.private {
+            params.push(("private", private.to_string()));
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            params.push(("idle_timeout", idle_timeout.to_string()));
+        }
+        if let Some(hidden) = self.hidden {
+            params.push(("hidden", hidden.to_string()));
+        }
+        if let Some(friends_only) = self.friends_only {
+            params.push(("friends_only", friends_only.to_string()));
+        }
+        if let Some(friends) = &self.friends {
+            params.push(("friends", friends.join(",")));
+        }
+        if let Some(blocked) = &self.blocked {
+            params.push(("blocked", blocked.join(",")));
+        }
+        if let Some(ntfy) = &self.ntfy {
+            params.push(("ntfy", ntfy.clone()));
+        }
+        if let Some(gotify) = &self.gotify {
+            params.push(("gotify", gotify.clone()));
+        }
+        if let Some(push) = &self.push {
+            params.push(("push", push.join(",")));
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The web page at `/`.
+    Index,
+    /// The OpenAPI description of the api at `/openapi.json`.
+    OpenApi,
+    Login,
+    Logoff,
+    Finger,
+    Check,
+    History,
+    /// A user's counters, or the week's most fingered users.
+    Stats,
+    /// The server's version, uptime, counts and message of the day.
+    Info,
+    Bump,
+    List,
+    Register,
+    Deregister,
+    SetPresence,
+    Follow,
+    Unfollow,
+    JoinGroup,
+    LeaveGroup,
+    Enroll2fa,
+    Disable2fa,
+    SetPubkey,
+    Passwd,
+    RotateKey,
+    Snooze,
+    WebFinger,
+    WebSocket,
+    Settings,
+    SetBio,
+    SetWebsite,
+    SetSocial,
+    SetPronouns,
+    SetLocation,
+    SetTimezone,
+    SetAvatar,
+    Avatar,
+    Feed,
+    Peer,
+    AdminLogoff,
+    AdminDelete,
+    AdminResetKey,
+    AdminStats,
+    AdminReload,
+    AdminBan,
+    AdminUnban,
+    AdminInvite,
+    AdminPending,
+    AdminApprove,
+    AdminGroup,
+}
+
+impl FromStr for Action {
+    type Err = FngrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Ok(Self::Index),
+            "openapi.json" => Ok(Self::OpenApi),
+            "finger" => Ok(Self::Finger),
+            "login" => Ok(Self::Login),
+            "bump" => Ok(Self::Bump),
+            "list" => Ok(Self::List),
+            "register" => Ok(Self::Register),
+            "deregister" => Ok(Self::Deregister),
+            "setpresence" => Ok(Self::SetPresence),
+            "follow" => Ok(Self::Follow),
+            "unfollow" => Ok(Self::Unfollow),
+            "joingroup" => Ok(Self::JoinGroup),
+            "leavegroup" => Ok(Self::LeaveGroup),
+            "enroll2fa" => Ok(Self::Enroll2fa),
+            "disable2fa" => Ok(Self::Disable2fa),
+            "setpubkey" => Ok(Self::SetPubkey),
+            "passwd" => Ok(Self::Passwd),
+            "rotatekey" => Ok(Self::RotateKey),
+            "logoff" => Ok(Self::Logoff),
+            "check" => Ok(Self::Check),
+            "history" => Ok(Self::History),
+            "stats" => Ok(Self::Stats),
+            "info" => Ok(Self::Info),
+            "snooze" => Ok(Self::Snooze),
+            ".well-known/webfinger" => Ok(Self::WebFinger),
+            "ws" => Ok(Self::WebSocket),
+            "settings" => Ok(Self::Settings),
+            "setbio" => Ok(Self::SetBio),
+            "setwebsite" => Ok(Self::SetWebsite),
+            "setsocial" => Ok(Self::SetSocial),
+            "setpronouns" => Ok(Self::SetPronouns),
+            "setlocation" => Ok(Self::SetLocation),
+            "settimezone" => Ok(Self::SetTimezone),
+            "setavatar" => Ok(Self::SetAvatar),
+            "avatar" => Ok(Self::Avatar),
+            "feed" => Ok(Self::Feed),
+            "peer" => Ok(Self::Peer),
+            "admin/logoff" => Ok(Self::AdminLogoff),
+            "admin/delete" => Ok(Self::AdminDelete),
+            "admin/resetkey" => Ok(Self::AdminResetKey),
+            "admin/stats" => Ok(Self::AdminStats),
+            "admin/reload" => Ok(Self::AdminReload),
+            "admin/ban" => Ok(Self::AdminBan),
+            "admin/unban" => Ok(Self::AdminUnban),
+            "admin/invite" => Ok(Self::AdminInvite),
+            "admin/pending" => Ok(Self::AdminPending),
+            "admin/approve" => Ok(Self::AdminApprove),
+            "admin/group" => Ok(Self::AdminGroup),
+            _ => Err(FngrError::Parse(format!("unrecognized action '{}'", s))),
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = match self {
+            Self::Index => "",
+            Self::OpenApi => "openapi.json",
+            Self::Finger => "finger",
+            Self::Login => "login",
+            Self::Bump => "bump",
+            Self::List => "list",
+            Self::Register => "register",
+            Self::Deregister => "deregister",
+            Self::SetPresence => "setpresence",
+            Self::Follow => "follow",
+            Self::Unfollow => "unfollow",
+            Self::JoinGroup => "joingroup",
+            Self::LeaveGroup => "leavegroup",
+            Self::Enroll2fa => "enroll2fa",
+            Self::Disable2fa => "disable2fa",
+            Self::SetPubkey => "setpubkey",
+            Self::Passwd => "passwd",
+            Self::RotateKey => "rotatekey",
+            Self::Logoff => "logoff",
+            Self::Check => "check",
+            Self::History => "history",
+            Self::Stats => "stats",
+            Self::Info => "info",
+            Self::Snooze => "snooze",
+            Self::WebFinger => ".well-known/webfinger",
+            Self::WebSocket => "ws",
+            Self::Settings => "settings",
+            Self::SetBio => "setbio",
+            Self::SetWebsite => "setwebsite",
+            Self::SetSocial => "setsocial",
+            Self::SetPronouns => "setpronouns",
+            Self::SetLocation => "setlocation",
+            Self::SetTimezone => "settimezone",
+            Self::SetAvatar => "setavatar",
+            Self::Avatar => "avatar",
+            Self::Feed => "feed",
+            Self::Peer => "peer",
+            Self::AdminLogoff => "admin/logoff",
+            Self::AdminDelete => "admin/delete",
+            Self::AdminResetKey => "admin/resetkey",
+            Self::AdminStats => "admin/stats",
+            Self::AdminReload => "admin/reload",
+            Self::AdminBan => "admin/ban",
+            Self::AdminUnban => "admin/unban",
+            Self::AdminInvite => "admin/invite",
+            Self::AdminPending => "admin/pending",
+            Self::AdminApprove => "admin/approve",
+            Self::AdminGroup => "admin/group",
+        };
+        write!(f, "{}", path)
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn transfer_encodings_are_refused() {
+        let raw: &[u8] =
+            b"POST /login HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        let e = Request::parse(raw, &Limits::default(), "")
+            .await
+            .err()
+            .expect("a chunked request was parsed");
+        assert_eq!(e.status(), crate::networking::ResponseStatus::Bad);
+    }
+
+    #[tokio::test]
+    async fn bodies_of_other_methods_are_read_past() {
+        let mut raw: &[u8] =
+            b"GET /list HTTP/1.1\r\nContent-Length: 20\r\n\r\nGET /admin HTTP/1.1\nGET /info HTTP/1.1\r\n\r\n";
+        let limits = Limits::default();
+        let first = Request::parse(&mut raw, &limits, "")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.action, Action::List);
+        let second = Request::parse(&mut raw, &limits, "")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.action, Action::Info);
+    }
+}
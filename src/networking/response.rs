@@ -0,0 +1,162 @@
+use std::{collections::HashMap, io::Cursor};
+
+use maplit::hashmap;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{Format, JSONResponse, status::ResponseStatus};
+use crate::prelude::*;
+
+pub struct Response {
+    status: ResponseStatus,
+    headers: HashMap<String, String>,
+    data: Cursor<Vec<u8>>,
+    /// Kept so the body can be rendered again in another [`Format`].
+    json: Option<JSONResponse>,
+}
+
+impl Response {
+    /// A json response, see [`Response::negotiate`] for the other formats.
+    pub fn from(status: ResponseStatus, data: impl Into<JSONResponse>) -> Self {
+        let json = data.into();
+        let mut response = Self::from_bytes(status, json.to_string().into_bytes())
+            .header("Content-Type", Format::Json.content_type());
+        response.json = Some(json);
+        response
+    }
+
+    /// A json error response with the status `e` is answered with.
+    pub fn error(e: &FngrError) -> Self {
+        Self::from(e.status(), JSONResponse::Error(e.to_string()))
+    }
+
+    /// A response with a binary body, set its `Content-Type` with [`Response::header`].
+    pub fn from_bytes(status: ResponseStatus, bytes: Vec<u8>) -> Self {
+        let headers = hashmap! {
+            "Content-Type".to_owned() => "application/octet-stream".to_string(),
+            "Content-Length".to_owned() => bytes.len().to_string(),
+        };
+
+        Self {
+            status,
+            headers,
+            data: Cursor::new(bytes),
+            json: None,
+        }
+    }
+
+    /// Changes the body of a json response with `f`, other responses are left as they are. Do
+    /// this before [`Response::version`].
+    pub fn map_json(mut self, f: impl FnOnce(&mut JSONResponse)) -> Self {
+        let Some(json) = &mut self.json else {
+            return self;
+        };
+
+        f(json);
+        let bytes = json.to_string().into_bytes();
+        self.headers
+            .insert("Content-Length".to_owned(), bytes.len().to_string());
+        self.data = Cursor::new(bytes);
+        self
+    }
+
+    /// Renders the body of a json response in the format of api `version`, see
+    /// [`JSONResponse::render`]. Do this before [`Response::negotiate`].
+    pub fn version(mut self, version: u32) -> Self {
+        let Some(json) = &self.json else {
+            return self;
+        };
+
+        let bytes = json.render(version).into_bytes();
+        self.headers
+            .insert("Content-Length".to_owned(), bytes.len().to_string());
+        self.data = Cursor::new(bytes);
+        self
+    }
+
+    /// Renders the body of a json response in `format`, other responses are left as they are.
+    pub fn negotiate(mut self, format: Format) -> Self {
+        let Some(json) = self.json.as_ref().filter(|_| format != Format::Json) else {
+            return self;
+        };
+
+        let bytes = format.render(json).into_bytes();
+        self.headers
+            .insert("Content-Type".to_owned(), format.content_type().to_owned());
+        self.headers
+            .insert("Content-Length".to_owned(), bytes.len().to_string());
+        self.data = Cursor::new(bytes);
+        self
+    }
+
+    /// The same response with `bytes` as its body, which is no longer rendered from json.
+    pub fn with_body(mut self, bytes: Vec<u8>) -> Self {
+        self.headers
+            .insert("Content-Length".to_owned(), bytes.len().to_string());
+        self.data = Cursor::new(bytes);
+        self.json = None;
+        self
+    }
+
+    /// The same response as a `304 Not Modified`, without its body and what describes it.
+    pub fn not_modified(mut self) -> Self {
+        self.status = ResponseStatus::NotModified;
+        self.headers.remove("Content-Type");
+        self.headers.remove("Content-Length");
+        self.data = Cursor::new(vec![]);
+        self.json = None;
+        self
+    }
+
+    pub fn status(&self) -> ResponseStatus {
+        self.status
+    }
+
+    /// The value of the header `key`, as it was set.
+    pub fn header_value(&self, key: &str) -> Option<&str> {
+        self.headers.get(key).map(String::as_str)
+    }
+
+    /// The body of a json response.
+    pub fn json(&self) -> Option<&JSONResponse> {
+        self.json.as_ref()
+    }
+
+    /// Length of the body in bytes.
+    pub fn body_len(&self) -> usize {
+        self.data.get_ref().len()
+    }
+
+    pub fn header(mut self, key: &str, value: impl ToString) -> Self {
+        self.headers.insert(key.to_owned(), value.to_string());
+        self
+    }
+
+    fn status_and_headers(&self) -> String {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect::<Vec<_>>()
+            .join("\r\n");
+
+        format!("HTTP/1.1 {}\r\n{headers}\r\n\r\n", self.status)
+    }
+
+    pub async fn write<O: AsyncWrite + Unpin>(mut self, stream: &mut O) -> Result<()> {
+        stream
+            .write_all(self.status_and_headers().as_bytes())
+            .await?;
+        tokio::io::copy(&mut self.data, stream).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+
+    /// Writes the status and headers only, as the answer to a `HEAD` request.
+    pub async fn write_head<O: AsyncWrite + Unpin>(self, stream: &mut O) -> Result<()> {
+        stream
+            .write_all(self.status_and_headers().as_bytes())
+            .await?;
+        stream.flush().await?;
+        Ok(())
+    }
+}
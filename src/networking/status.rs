@@ -0,0 +1,72 @@
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseStatus {
+    NotFound,
+    Ok,
+    NoContent,
+    Found,
+    NotModified,
+    Unauth,
+    Forbidden,
+    Bad,
+    Conflict,
+    RequestTimeout,
+    PayloadTooLarge,
+    Locked,
+    TooManyRequests,
+    HeadersTooLarge,
+    ServerError,
+    BadGateway,
+    LoopDetected,
+}
+
+impl ResponseStatus {
+    /// The numeric http status code.
+    pub fn code(&self) -> u16 {
+        match self {
+            ResponseStatus::NotFound => 404,
+            ResponseStatus::Ok => 200,
+            ResponseStatus::NoContent => 204,
+            ResponseStatus::Found => 302,
+            ResponseStatus::NotModified => 304,
+            ResponseStatus::Unauth => 401,
+            ResponseStatus::Forbidden => 403,
+            ResponseStatus::Bad => 400,
+            ResponseStatus::Conflict => 409,
+            ResponseStatus::RequestTimeout => 408,
+            ResponseStatus::PayloadTooLarge => 413,
+            ResponseStatus::Locked => 423,
+            ResponseStatus::TooManyRequests => 429,
+            ResponseStatus::HeadersTooLarge => 431,
+            ResponseStatus::ServerError => 500,
+            ResponseStatus::BadGateway => 502,
+            ResponseStatus::LoopDetected => 508,
+        }
+    }
+}
+
+impl Display for ResponseStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResponseStatus::NotFound => "404 Not Found",
+            ResponseStatus::Ok => "200 OK",
+            ResponseStatus::NoContent => "204 No Content",
+            ResponseStatus::Found => "302 Found",
+            ResponseStatus::NotModified => "304 Not Modified",
+            ResponseStatus::Unauth => "401 Unauthorized",
+            ResponseStatus::Forbidden => "403 Forbidden",
+            ResponseStatus::Bad => "400 Bad Request",
+            ResponseStatus::Conflict => "409 Conflict",
+            ResponseStatus::RequestTimeout => "408 Request Timeout",
+            ResponseStatus::PayloadTooLarge => "413 Content Too Large",
+            ResponseStatus::Locked => "423 Locked",
+            ResponseStatus::TooManyRequests => "429 Too Many Requests",
+            ResponseStatus::HeadersTooLarge => "431 Request Header Fields Too Large",
+            ResponseStatus::ServerError => "500 Server Error",
+            ResponseStatus::BadGateway => "502 Bad Gateway",
+            ResponseStatus::LoopDetected => "508 Loop Detected",
+        }
+        .fmt(f)
+    }
+}
@@ -0,0 +1,68 @@
+//! Extending a server without changing it, see [`Plugin`].
+
+use std::{fmt, sync::Arc};
+
+use crate::networking::{Presence, Request, Response};
+
+/// Something that sees every request before its action runs, every response before it is
+/// written and every presence change, like a filter or a notifier. Plugins are registered when
+/// the server is built and run in that order.
+pub trait Plugin: Send + Sync {
+    /// For logs.
+    fn name(&self) -> &str;
+
+    /// Called before the action runs, and may change the request. Returning a response answers
+    /// the request with it instead, and the plugins after this one don't see it.
+    fn on_request(&self, _req: &mut Request) -> Option<Response> {
+        None
+    }
+
+    /// Called with the response to `req` before it is written, and may change or replace it.
+    fn on_response(&self, _req: &Request, response: Response) -> Response {
+        response
+    }
+
+    /// Called when someone logs on, logs off or changes their presence.
+    fn on_presence_change(&self, _presence: &Presence) {}
+}
+
+/// The plugins of a server, cheap to clone.
+#[derive(Clone, Default)]
+pub struct Plugins(Arc<[Box<dyn Plugin>]>);
+
+impl Plugins {
+    pub fn new(plugins: Vec<Box<dyn Plugin>>) -> Self {
+        Self(plugins.into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Runs [`Plugin::on_request`] of every plugin, until one answers.
+    pub fn on_request(&self, req: &mut Request) -> Option<Response> {
+        self.0.iter().find_map(|plugin| plugin.on_request(req))
+    }
+
+    /// Runs [`Plugin::on_response`] of every plugin.
+    pub fn on_response(&self, req: &Request, response: Response) -> Response {
+        self.0.iter().fold(response, |response, plugin| {
+            plugin.on_response(req, response)
+        })
+    }
+
+    /// Runs [`Plugin::on_presence_change`] of every plugin.
+    pub fn on_presence_change(&self, presence: &Presence) {
+        for plugin in self.0.iter() {
+            plugin.on_presence_change(presence);
+        }
+    }
+}
+
+impl fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|plugin| plugin.name()))
+            .finish()
+    }
+}
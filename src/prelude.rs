@@ -0,0 +1,2 @@
+pub use crate::error::{FngrError, Result};
+pub use tracing::{debug, error, info, subscriber, warn};
@@ -0,0 +1,149 @@
+//! One line per answered request, separate from the diagnostic logs.
+
+use std::{io::Write, net::IpAddr, path::Path, time::Duration};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+
+use crate::server::prelude::*;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// The common log format of web servers.
+    #[default]
+    Common,
+    /// One json object per line.
+    Json,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+pub struct AccessLog {
+    writer: NonBlocking,
+    format: AccessLogFormat,
+    // flushes what is still buffered when the log is dropped
+    _guard: WorkerGuard,
+}
+
+/// What is logged about a request.
+pub struct Entry {
+    pub peer: Option<IpAddr>,
+    pub method: Option<String>,
+    pub action: Option<String>,
+    pub username: Option<String>,
+    /// Also in the `X-Request-Id` header of the response.
+    pub id: String,
+    pub status: u16,
+    pub bytes: usize,
+    pub latency: Duration,
+}
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    time: String,
+    peer: Option<IpAddr>,
+    method: Option<&'a str>,
+    action: Option<&'a str>,
+    username: Option<&'a str>,
+    request_id: &'a str,
+    status: u16,
+    bytes: usize,
+    latency_ms: f64,
+}
+
+impl AccessLog {
+    /// Appends to `path`, which with rotation gets the date or hour as a suffix.
+    pub fn open(path: &Path, format: AccessLogFormat, rotation: AccessLogRotation) -> Result<Self> {
+        is_relative("access log", path)?;
+
+        let directory = path.parent().unwrap_or(Path::new("."));
+        let file_name = path
+            .file_name()
+            .ok_or(anyhow!("'{}' is not a file", path.display()))?;
+        let rotation = match rotation {
+            AccessLogRotation::Never => Rotation::NEVER,
+            AccessLogRotation::Hourly => Rotation::HOURLY,
+            AccessLogRotation::Daily => Rotation::DAILY,
+        };
+
+        let appender = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(file_name.to_string_lossy())
+            .build(directory)?;
+        let (writer, _guard) = tracing_appender::non_blocking(appender);
+
+        info!("writing access log to {}", path.display());
+        Ok(Self {
+            writer,
+            format,
+            _guard,
+        })
+    }
+
+    pub fn record(&self, entry: &Entry) {
+        let line = match self.format {
+            AccessLogFormat::Common => common(entry),
+            AccessLogFormat::Json => match json(entry) {
+                Ok(line) => line,
+                Err(e) => {
+                    error!("can't write access log entry: {}", e);
+                    return;
+                }
+            },
+        };
+
+        if let Err(e) = writeln!(self.writer.clone(), "{}", line) {
+            error!("can't write access log entry: {}", e);
+        }
+    }
+}
+
+/// `peer - username [time] "method /action" status bytes latency_ms request_id`
+fn common(entry: &Entry) -> String {
+    // requests we couldn't parse have no method or action
+    let request = match (&entry.method, &entry.action) {
+        (Some(method), Some(action)) => format!("{} /{}", method, action),
+        _ => "-".to_owned(),
+    };
+
+    format!(
+        "{} - {} [{}] \"{}\" {} {} {:.3} {}",
+        entry
+            .peer
+            .map(|peer| peer.to_string())
+            .unwrap_or("-".to_owned()),
+        entry.username.as_deref().unwrap_or("-"),
+        Local::now().format("%d/%b/%Y:%H:%M:%S %z"),
+        request,
+        entry.status,
+        entry.bytes,
+        entry.latency.as_secs_f64() * 1000.0,
+        entry.id
+    )
+}
+
+fn json(entry: &Entry) -> Result<String> {
+    Ok(serde_json::to_string(&JsonEntry {
+        time: Local::now().to_rfc3339(),
+        peer: entry.peer,
+        method: entry.method.as_deref(),
+        action: entry.action.as_deref(),
+        username: entry.username.as_deref(),
+        request_id: &entry.id,
+        status: entry.status,
+        bytes: entry.bytes,
+        latency_ms: entry.latency.as_secs_f64() * 1000.0,
+    })?)
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use ldap3::{LdapConnAsync, LdapConnSettings, dn_escape};
+
+use super::AuthProvider;
+use crate::server::{config::LdapConfig, prelude::*};
+
+// how long the directory gets to connect and to answer the bind
+const TIMEOUT: Duration = Duration::from_secs(10);
+// the result code of a bind with a wrong password or an unknown dn
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// Checks keys by binding to a directory as the user, with the key as their password.
+#[derive(Clone)]
+pub struct LdapAuth {
+    config: LdapConfig,
+}
+
+impl LdapAuth {
+    pub fn new(config: LdapConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl AuthProvider for LdapAuth {
+    async fn verify(&self, username: &str, key: &str) -> Result<bool> {
+        // a bind without a password is anonymous, which most directories let through
+        if key.is_empty() {
+            return Ok(false);
+        }
+
+        let settings = LdapConnSettings::new().set_conn_timeout(TIMEOUT);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await?;
+        ldap3::drive!(conn);
+
+        let dn = self
+            .config
+            .bind_dn
+            .replace("{username}", &dn_escape(username));
+        let result = ldap.with_timeout(TIMEOUT).simple_bind(&dn, key).await?;
+        let _ = ldap.unbind().await;
+
+        match result.rc {
+            0 => Ok(true),
+            INVALID_CREDENTIALS => Ok(false),
+            rc => Err(anyhow!(
+                "bind as '{}' failed with {}: {}",
+                dn,
+                rc,
+                result.text
+            )),
+        }
+    }
+}
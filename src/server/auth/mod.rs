@@ -0,0 +1,58 @@
+//! Where keys are checked when they aren't the ones the server handed out itself.
+//!
+//! A backend implements [`AuthProvider`] and gets a variant in [`Auth`], which the server picks
+//! from its config. With a backend the server only keeps presence and profiles, and users come
+//! into being the first time they authenticate.
+
+#[cfg(feature = "ldap")]
+mod ldap;
+
+use crate::server::{
+    config::{AuthKind, Config},
+    prelude::*,
+};
+#[cfg(feature = "ldap")]
+pub use ldap::LdapAuth;
+
+pub trait AuthProvider {
+    /// Whether `key` is what `username` authenticates with, an error if that can't be told.
+    fn verify(&self, username: &str, key: &str) -> impl Future<Output = Result<bool>> + Send;
+}
+
+#[derive(Clone)]
+pub enum Auth {
+    #[cfg(feature = "ldap")]
+    Ldap(LdapAuth),
+}
+
+impl Auth {
+    /// Opens the backend `config` asks for, `None` for the server's own keys.
+    pub fn open(config: &Config) -> Result<Option<Self>> {
+        match config.auth {
+            AuthKind::Local => Ok(None),
+            #[cfg(feature = "ldap")]
+            AuthKind::Ldap => {
+                let ldap = config
+                    .ldap
+                    .clone()
+                    .ok_or(anyhow!("auth = \"ldap\" needs an [ldap] section"))?;
+                Ok(Some(Auth::Ldap(LdapAuth::new(ldap))))
+            }
+            #[cfg(not(feature = "ldap"))]
+            AuthKind::Ldap => Err(anyhow!(
+                "ldap auth needs a server built with the ldap feature"
+            )),
+        }
+    }
+}
+
+impl AuthProvider for Auth {
+    // without any backend built in there is no `Auth` to call this on
+    #[cfg_attr(not(feature = "ldap"), allow(unused_variables))]
+    async fn verify(&self, username: &str, key: &str) -> Result<bool> {
+        match *self {
+            #[cfg(feature = "ldap")]
+            Auth::Ldap(ref auth) => auth.verify(username, key).await,
+        }
+    }
+}
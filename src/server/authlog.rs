@@ -0,0 +1,318 @@
+//! One line per failed credential check, lockout, ban or rate limit of someone trying
+//! credentials, and refused registration, for fail2ban and the like.
+//!
+//! The format of a line is kept as it is, filters are written against it:
+//!
+//! ```text
+//! 2024-05-01T12:00:00Z fngr-auth: failure ip=203.0.113.7 user=pockets action=login status=401
+//! 2024-05-01T12:00:00Z fngr-auth: locked ip=203.0.113.7 user=pockets action=login status=423
+//! 2024-05-01T12:00:00Z fngr-auth: banned ip=203.0.113.7 user=pockets action=bump status=403
+//! 2024-05-01T12:00:00Z fngr-auth: limited ip=203.0.113.7 user=pockets action=login status=429
+//! 2024-05-01T12:00:00Z fngr-auth: registration ip=203.0.113.7 user=pockets action=register status=403
+//! ```
+//!
+//! `ip` and `user` are `-` when unknown.
+
+use std::{fmt::Display, io::Write, net::IpAddr, path::Path};
+
+use crate::networking::{Action, Request};
+use chrono::{DateTime, SecondsFormat, Utc};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::RollingFileAppender,
+};
+
+use crate::server::{accesslog::Entry, prelude::*};
+
+/// Why a request got a line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// A wrong key, admin key, session, signature or second factor.
+    Failure,
+    /// Refused for too many failures before.
+    Locked,
+    /// A banned address or user that tried credentials.
+    Banned,
+    /// Rate limited while trying credentials.
+    Limited,
+    /// A registration that wasn't allowed.
+    Registration,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Failure => "failure",
+            Kind::Locked => "locked",
+            Kind::Banned => "banned",
+            Kind::Limited => "limited",
+            Kind::Registration => "registration",
+        }
+        .fmt(f)
+    }
+}
+
+pub struct AuthLog {
+    writer: NonBlocking,
+    // flushes what is still buffered when the log is dropped
+    _guard: WorkerGuard,
+}
+
+impl AuthLog {
+    /// Appends to `path`, rotating it is left to logrotate so fail2ban can follow it.
+    pub fn open(path: &Path) -> Result<Self> {
+        is_relative("auth log", path)?;
+
+        let directory = path.parent().unwrap_or(Path::new("."));
+        let file_name = path
+            .file_name()
+            .ok_or(anyhow!("'{}' is not a file", path.display()))?;
+        let appender = RollingFileAppender::builder()
+            .filename_prefix(file_name.to_string_lossy())
+            .build(directory)?;
+        let (writer, _guard) = tracing_appender::non_blocking(appender);
+
+        info!("writing auth failures to {}", path.display());
+        Ok(Self { writer, _guard })
+    }
+
+    /// Writes a line for `req`, refused with `status` when its credentials were checked.
+    pub fn refused(&self, kind: Kind, req: &Request, status: u16) {
+        let action = req.action.to_string();
+        self.write(&line(
+            Utc::now(),
+            kind,
+            req.peer,
+            req.username.as_deref(),
+            Some(&action),
+            status,
+        ));
+    }
+
+    /// Writes a line for `entry` if it was a refused registration, or rate limited while trying
+    /// credentials. The rest is written by the credential checks, which know why they refused.
+    pub fn record(&self, entry: &Entry) {
+        let Some(kind) = kind(entry) else {
+            return;
+        };
+
+        self.write(&line(
+            Utc::now(),
+            kind,
+            entry.peer,
+            entry.username.as_deref(),
+            entry.action.as_deref(),
+            entry.status,
+        ));
+    }
+
+    fn write(&self, line: &str) {
+        if let Err(e) = writeln!(self.writer.clone(), "{}", line) {
+            error!("can't write auth log entry: {}", e);
+        }
+    }
+}
+
+fn kind(entry: &Entry) -> Option<Kind> {
+    let register = entry.action.as_deref() == Some(Action::Register.to_string().as_str());
+    match entry.status {
+        401 | 403 | 429 if register => Some(Kind::Registration),
+        // the rate limit answers before the action runs, so a username is all there is to go by
+        429 if entry.username.is_some() => Some(Kind::Limited),
+        _ => None,
+    }
+}
+
+/// `time fngr-auth: kind ip=peer user=username action=action status=status`
+fn line(
+    at: DateTime<Utc>,
+    kind: Kind,
+    peer: Option<IpAddr>,
+    username: Option<&str>,
+    action: Option<&str>,
+    status: u16,
+) -> String {
+    format!(
+        "{} fngr-auth: {} ip={} user={} action={} status={}",
+        at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        kind,
+        peer.map(|peer| peer.to_string()).unwrap_or("-".to_owned()),
+        field(username),
+        field(action),
+        status
+    )
+}
+
+/// A value that can't break the line up, as usernames are only checked on registration.
+fn field(value: Option<&str>) -> String {
+    match value {
+        Some(value) if !value.is_empty() => value
+            .chars()
+            .map(|c| {
+                if c.is_whitespace() || c.is_control() {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect(),
+        _ => "-".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::networking;
+    use crate::server::testing::{self, TempDir};
+
+    const PEER: &str = "203.0.113.7";
+
+    /// The lines of the auth log at `path`, without their timestamps, which must be rfc 3339.
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| {
+                let (at, rest) = line.split_once(' ').unwrap();
+                DateTime::parse_from_rfc3339(at).unwrap();
+                rest.to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_format() {
+        let at = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let peer = PEER.parse().ok();
+        assert_eq!(
+            line(at, Kind::Failure, peer, Some("pockets"), Some("login"), 401),
+            "2024-05-01T12:00:00Z fngr-auth: failure ip=203.0.113.7 user=pockets action=login status=401"
+        );
+        assert_eq!(
+            line(at, Kind::Registration, None, Some("a b\n"), None, 403),
+            "2024-05-01T12:00:00Z fngr-auth: registration ip=- user=a_b_ action=- status=403"
+        );
+    }
+
+    #[test]
+    fn refused_register() {
+        let entry = |action: Action, username: Option<&str>, status| Entry {
+            peer: PEER.parse().ok(),
+            method: Some("GET".to_owned()),
+            action: Some(action.to_string()),
+            username: username.map(str::to_owned),
+            id: "id".to_owned(),
+            status,
+            bytes: 0,
+            latency: Duration::ZERO,
+        };
+
+        let refused = entry(Action::Register, Some("mallory"), 403);
+        assert_eq!(kind(&refused), Some(Kind::Registration));
+        let at = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(
+            line(
+                at,
+                Kind::Registration,
+                refused.peer,
+                refused.username.as_deref(),
+                refused.action.as_deref(),
+                refused.status
+            ),
+            "2024-05-01T12:00:00Z fngr-auth: registration ip=203.0.113.7 user=mallory action=register status=403"
+        );
+
+        assert_eq!(
+            kind(&entry(Action::Login, Some("alice"), 429)),
+            Some(Kind::Limited)
+        );
+        // what the credential checks leave to themselves, or isn't about credentials at all
+        assert_eq!(kind(&entry(Action::Login, Some("alice"), 401)), None);
+        assert_eq!(kind(&entry(Action::List, None, 401)), None);
+        assert_eq!(kind(&entry(Action::List, None, 429)), None);
+    }
+
+    #[tokio::test]
+    async fn failed_login() {
+        let dir = TempDir::new();
+        let path = dir.path().join("auth.log");
+        let state = testing::fingr(
+            &dir,
+            &[
+                ("auth_log", &path.display().to_string()),
+                ("lockout_threshold", "2"),
+            ],
+        )
+        .await;
+        let key = testing::register(&state, "alice").await;
+
+        let login = |key: &str| {
+            let mut req = testing::get(&format!("/login?username=alice&key={}", key));
+            req.peer = PEER.parse().ok();
+            req
+        };
+        let wrong = uuid::Uuid::from_bytes([7; 16]).to_string();
+        testing::send(&state, login(&wrong)).await;
+        testing::request(&state, "/list?following=1").await;
+        testing::request(&state, "/finger?user=alice").await;
+        testing::send(&state, login(&wrong)).await;
+        // locked out now, even with the right key
+        testing::send(&state, login(&key)).await;
+
+        // the log is written in the background until it is dropped
+        drop(state);
+        assert_eq!(
+            lines(&path),
+            [
+                "fngr-auth: failure ip=203.0.113.7 user=alice action=login status=401",
+                "fngr-auth: failure ip=203.0.113.7 user=alice action=login status=401",
+                "fngr-auth: locked ip=203.0.113.7 user=alice action=login status=423",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_users_fail_like_wrong_keys() {
+        let dir = TempDir::new();
+        let path = dir.path().join("auth.log");
+        let state = testing::fingr(
+            &dir,
+            &[
+                ("auth_log", &path.display().to_string()),
+                ("lockout_threshold", "2"),
+            ],
+        )
+        .await;
+        let key = testing::register(&state, "alice").await;
+
+        let login = |username: &str, key: &str| {
+            let mut req = testing::get(&format!("/login?username={}&key={}", username, key));
+            req.peer = PEER.parse().ok();
+            req
+        };
+        for _ in 0..2 {
+            let refused = testing::send(&state, login("nobody", &key)).await;
+            assert_eq!(refused.status(), networking::ResponseStatus::Unauth);
+        }
+        // the address is locked out, even for a user that exists
+        let locked = testing::send(&state, login("alice", &key)).await;
+        assert_eq!(locked.status(), networking::ResponseStatus::Locked);
+
+        drop(state);
+        assert_eq!(
+            lines(&path),
+            [
+                "fngr-auth: failure ip=203.0.113.7 user=nobody action=login status=401",
+                "fngr-auth: failure ip=203.0.113.7 user=nobody action=login status=401",
+                "fngr-auth: locked ip=203.0.113.7 user=alice action=login status=423",
+            ]
+        );
+    }
+}
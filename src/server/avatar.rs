@@ -0,0 +1,48 @@
+//! Avatars uploaded to the server, kept as files named after a hash of the username.
+
+use std::path::{Path, PathBuf};
+
+use sha_rs::{Sha, Sha256};
+
+use crate::server::prelude::*;
+
+/// The content type of an image going by its first bytes, if it is a kind we accept.
+pub fn sniff(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if image.starts_with(b"GIF87a") || image.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if image.len() >= 12 && &image[..4] == b"RIFF" && &image[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+fn path(dir: &Path, username: &str) -> PathBuf {
+    // usernames can hold anything, the hash is always a safe file name
+    dir.join(Sha256::new().digest(username.as_bytes()))
+}
+
+pub async fn save(dir: &Path, username: &str, image: &[u8]) -> Result<()> {
+    is_relative("avatar dir", dir)?;
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(path(dir, username), image).await?;
+    Ok(())
+}
+
+pub async fn read(dir: &Path, username: &str) -> Result<Vec<u8>> {
+    is_relative("avatar dir", dir)?;
+    Ok(tokio::fs::read(path(dir, username)).await?)
+}
+
+/// Removes the uploaded avatar of `username`, if there is one.
+pub async fn remove(dir: &Path, username: &str) -> Result<()> {
+    match tokio::fs::remove_file(path(dir, username)).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
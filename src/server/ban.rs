@@ -0,0 +1,89 @@
+//! Keeping addresses and usernames out, on top of the bans of single users kept in the store.
+
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::server::prelude::*;
+
+/// An address or a block of them like `203.0.113.0/24` or `2001:db8::/32`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // v4 clients of a dual stack listener show up as `::ffff:a.b.c.d`
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            ip => ip,
+        };
+
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = FngrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, prefix) = s.split_once('/').unwrap_or((s, ""));
+        let network: IpAddr = network.parse().map_err(|_| {
+            FngrError::Parse(format!("'{}' is not an address or a range of them", s))
+        })?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            "" => max,
+            prefix => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| FngrError::Parse(format!("'{}' has an invalid prefix length", s)))?,
+        };
+
+        Ok(Self { network, prefix })
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = FngrError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpRange> for String {
+    fn from(value: IpRange) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// Whether `ip` is in any of `ranges`.
+pub fn is_banned_ip(ranges: &[IpRange], ip: Option<IpAddr>) -> bool {
+    ip.is_some_and(|ip| ranges.iter().any(|range| range.contains(ip)))
+}
+
+/// Whether `username` is one of `names`, ignoring case so lookalikes are kept out too.
+pub fn is_banned_name(names: &[String], username: &str) -> bool {
+    names.iter().any(|name| name.eq_ignore_ascii_case(username))
+}
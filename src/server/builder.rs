@@ -0,0 +1,130 @@
+//! Running a fngr server inside another program, on its tokio runtime.
+
+use std::pin::Pin;
+
+use tokio::net::TcpListener;
+
+use crate::{
+    Plugin, Plugins,
+    server::{
+        Fingr, Listeners,
+        config::{Config, ConfigSource},
+        prelude::*,
+        store::Store,
+        systemd,
+    },
+};
+
+type Shutdown = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A fngr server, built with [`Server::builder`].
+pub struct Server;
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+}
+
+/// Sets up a [`Server`] and serves it:
+///
+/// ```no_run
+/// # async fn run() -> Result<(), fngr::FngrError> {
+/// use fngr::server::config::{Config, ConfigSource};
+///
+/// let source = ConfigSource {
+///     path: Some("/etc/myapp/fngr.toml".into()),
+///     ..Default::default()
+/// };
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:38273").await?;
+/// fngr::Server::builder()
+///     .config(Config::load(&source).await?)
+///     .listener(listener)
+///     .serve()
+///     .await
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ServerBuilder {
+    config: Option<Config>,
+    store: Option<Store>,
+    listeners: Vec<TcpListener>,
+    plugins: Vec<Box<dyn Plugin>>,
+    shutdown: Option<Shutdown>,
+}
+
+impl ServerBuilder {
+    /// The server's config, loaded from `/etc/fngr-server/config` without one.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Where users are kept, the one the config names without one.
+    pub fn store(mut self, store: Store) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Serves the http api on `listener`. Given any, they take the place of every address in
+    /// the config, finger, tls and unix sockets included.
+    pub fn listener(mut self, listener: TcpListener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Adds a plugin, run after the ones added before it.
+    pub fn plugin(mut self, plugin: impl Plugin + 'static) -> Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Stops the server once `signal` resolves. Without one it runs until its future is dropped.
+    pub fn shutdown(mut self, signal: impl Future<Output = ()> + Send + 'static) -> Self {
+        self.shutdown = Some(Box::pin(signal));
+        self
+    }
+
+    /// Serves until the shutdown signal, then saves the statuses. Unlike the `fingr-server`
+    /// binary it leaves signals, systemd and the process' user alone.
+    pub async fn serve(self) -> Result<(), FngrError> {
+        let (config, source) = match self.config {
+            Some(config) => (config, None),
+            None => {
+                let source = ConfigSource::default();
+                (Config::load(&source).await?, Some(source))
+            }
+        };
+        let store = match self.store {
+            Some(store) => store,
+            None => Store::open(&config)?,
+        };
+
+        let mut fingr = Fingr::new(config, source, store, Plugins::new(self.plugins))
+            .await
+            .map_err(server_error)?;
+        fingr.lock = Some(fingr.lock().map_err(server_error)?);
+        let listeners = if self.listeners.is_empty() {
+            fingr
+                .bind(&mut systemd::Activated::default())
+                .await
+                .map_err(server_error)?
+        } else {
+            Listeners {
+                http: self.listeners,
+                ..Default::default()
+            }
+        };
+
+        let (state, tasks) = fingr.start(listeners);
+        info!("server started.");
+        match self.shutdown {
+            Some(signal) => signal.await,
+            None => std::future::pending().await,
+        }
+
+        info!("shutting down...");
+        drop(tasks);
+        Fingr::stop(&state).await.map_err(server_error)
+    }
+}
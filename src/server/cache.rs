@@ -0,0 +1,93 @@
+//! Conditional requests, so clients polling `list` or `finger` get a `304 Not Modified` instead of
+//! the same body again.
+//!
+//! Tags are weak: `since` and `expires_in` count up and down with every second, but a body only
+//! gets a new tag when something else in it changes.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    time::SystemTime,
+};
+
+use crate::networking::{Format, Method, Request, Response, ResponseStatus};
+use chrono::{DateTime, Utc};
+
+/// The validators a request sent, to be answered with only a status if they still hold.
+pub struct Conditional {
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl Conditional {
+    pub fn from_request(request: &Request) -> Self {
+        let conditional = matches!(request.method, Method::Get | Method::Head);
+        let header = |name: &str| request.headers.get(name).filter(|_| conditional).cloned();
+
+        Self {
+            if_none_match: header("if-none-match"),
+            if_modified_since: header("if-modified-since"),
+        }
+    }
+
+    /// `response`, or a `304 Not Modified` if the client has it already.
+    pub fn apply(&self, response: Response) -> Response {
+        if response.status() != ResponseStatus::Ok {
+            return response;
+        }
+
+        // `If-Modified-Since` only counts without `If-None-Match`
+        let fresh = match (&self.if_none_match, response.header_value("ETag")) {
+            (Some(tags), Some(etag)) => tags
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak(tag) == weak(etag)),
+            (Some(_), None) => false,
+            (None, _) => self
+                .if_modified_since
+                .as_deref()
+                .and_then(parse_date)
+                .zip(response.header_value("Last-Modified").and_then(parse_date))
+                .is_some_and(|(since, modified)| modified <= since),
+        };
+
+        if fresh {
+            response.not_modified()
+        } else {
+            response
+        }
+    }
+}
+
+/// Tags `response` as built from the users list and peers at `versions`, in `format` and api
+/// version `api_version` for `viewer`.
+pub fn validate(
+    response: Response,
+    versions: (u64, u64),
+    modified: SystemTime,
+    format: Format,
+    api_version: u32,
+    viewer: Option<&str>,
+) -> Response {
+    let mut hasher = DefaultHasher::new();
+    (versions, format.content_type(), api_version, viewer).hash(&mut hasher);
+
+    response
+        .header("ETag", format!("W/\"{:016x}\"", hasher.finish()))
+        .header(
+            "Last-Modified",
+            DateTime::<Utc>::from(modified).format("%a, %d %b %Y %H:%M:%S GMT"),
+        )
+        .header("Cache-Control", "no-cache")
+}
+
+/// A tag without its weak marker, as weak comparison ignores it.
+fn weak(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+/// An http date, which is the rfc 2822 format with `GMT` as the zone.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date.trim())
+        .ok()
+        .map(|date| date.to_utc())
+}
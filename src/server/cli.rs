@@ -0,0 +1,227 @@
+//! Command line of the server binary.
+
+use std::path::PathBuf;
+
+use crate::Plugins;
+use clap::{Parser, Subcommand};
+use uuid::Uuid;
+
+use crate::server::{
+    Fingr, avatar,
+    config::{CONFIG_VERSION, Config, ConfigSource},
+    logging,
+    prelude::*,
+    stdio,
+    store::{self, Store},
+    userlist::{self, UserList, hash_key},
+};
+
+#[derive(Parser)]
+#[command(version, about = "A finger server with an http api")]
+pub struct Cli {
+    /// Config file, `/etc/fngr-server/config` by default
+    #[arg(long, short, global = true)]
+    config: Option<PathBuf>,
+    /// Override a config key, like `--set port=38273`, over the file and `FNGR_*` variables
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String)>,
+    /// Same as the `check-config` command
+    #[arg(long)]
+    check_config: bool,
+    /// Same as the `stdio` command
+    #[arg(long)]
+    stdio: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the server, the default without a command
+    Serve,
+    /// Load the config and the users and report any problems
+    CheckConfig,
+    /// Answer one connection on stdin and stdout, for inetd
+    Stdio,
+    /// Manage users directly in the store
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Print the hash of a key as stored in the users list, for a new key if none is given
+    HashPassword { key: Option<Uuid> },
+    /// Convert a users list of an older server to the current format, keeping the original
+    Migrate {
+        /// Users list to convert, the configured one by default
+        users_list: Option<PathBuf>,
+        /// Only report what would be converted
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Register a user and print their key
+    Add { username: String },
+    /// Remove a user
+    Remove { username: String },
+    /// List every user
+    List,
+}
+
+/// Runs what `cli` asks for, serving without a command.
+pub async fn run(cli: Cli) -> Result<(), FngrError> {
+    execute(cli).await.map_err(server_error)
+}
+
+async fn execute(cli: Cli) -> Result<()> {
+    let command = match (cli.command, cli.check_config, cli.stdio) {
+        (None, false, false) => Command::Serve,
+        (Some(command), false, false) => command,
+        (None, true, false) => Command::CheckConfig,
+        (None, false, true) => Command::Stdio,
+        _ => {
+            return Err(anyhow!(
+                "--check-config and --stdio stand in for a command, give only one of them"
+            ));
+        }
+    };
+
+    let config = ConfigSource {
+        path: cli.config.or_else(default_config),
+        overrides: cli.overrides,
+    };
+
+    // the log section is read before there is a log, its problems show up on the second load
+    let _log = match command {
+        Command::Serve | Command::Stdio => {
+            let log = Config::load(&config).await?.log;
+            if matches!(command, Command::Stdio) && log.file.is_none() {
+                // inetd passes the connection as stdout and stderr, where logs would end up in
+                // a response
+                tracing_subscriber::fmt().with_writer(std::io::sink).init();
+                None
+            } else {
+                Some(logging::init(&log)?)
+            }
+        }
+        // keep stdout for the output of the other commands
+        _ => {
+            tracing_subscriber::fmt()
+                .with_writer(std::io::stderr)
+                .init();
+            None
+        }
+    };
+
+    match command {
+        Command::Serve => {
+            info!("loading fingr server resources...");
+            // plugins come from programs that embed the server
+            Fingr::init(config, Plugins::default()).await?.run().await
+        }
+        Command::Stdio => stdio::serve(Fingr::init(config, Plugins::default()).await?).await,
+        Command::CheckConfig => {
+            let config = Config::load(&config).await?;
+            let users = UserList::load(Store::open(&config)?, config.idle_timeout()).await?;
+            println!(
+                "config is valid, version {}, {} users",
+                CONFIG_VERSION,
+                users.len()
+            );
+            if users.skipped() > 0 {
+                println!("{} malformed users were skipped", users.skipped());
+            }
+            Ok(())
+        }
+        Command::User(command) => user(config, command).await,
+        Command::HashPassword { key } => {
+            let key = key.unwrap_or_else(|| Uuid::from_bytes(rand::random()));
+            println!("key:  {}", key);
+            println!("hash: {}", hash_key(key));
+            Ok(())
+        }
+        Command::Migrate {
+            users_list,
+            dry_run,
+        } => migrate(config, users_list, dry_run).await,
+    }
+}
+
+async fn migrate(config: ConfigSource, path: Option<PathBuf>, dry_run: bool) -> Result<()> {
+    let path = match path {
+        Some(path) => path,
+        None => Config::load(&config).await?.users_list,
+    };
+
+    let Some(converted) = store::migrate(&path, dry_run).await? else {
+        println!("{} is already in the current format", path.display());
+        return Ok(());
+    };
+
+    for skipped in &converted.skipped {
+        println!("skipped {}", skipped);
+    }
+    if dry_run {
+        println!(
+            "{} is {}, {} users would be migrated",
+            path.display(),
+            converted.format,
+            converted.users.len()
+        );
+    } else {
+        println!(
+            "migrated {} users from {}, the original is kept in {}",
+            converted.users.len(),
+            converted.format,
+            path.with_extension("legacy").display()
+        );
+    }
+
+    Ok(())
+}
+
+async fn user(config: ConfigSource, command: UserCommand) -> Result<()> {
+    let config = Config::load(&config).await?;
+    let mut users = UserList::load(Store::open(&config)?, config.idle_timeout()).await?;
+
+    match command {
+        UserCommand::Add { username } => {
+            if let Some(reason) = userlist::invalid_username(&username) {
+                return Err(anyhow!("{}: '{}'", reason, username));
+            }
+            let key = users.register(username.to_owned(), false).await?;
+            println!("registered '{}' with key {}", username, key);
+        }
+        UserCommand::Remove { username } => {
+            if !users.contains_key(&username) {
+                return Err(anyhow!("there is no user '{}'", username));
+            }
+            users.remove(username.to_owned()).await?;
+            avatar::remove(&config.avatar_dir, &username).await?;
+            println!("removed '{}'", username);
+        }
+        UserCommand::List => {
+            let mut usernames: Vec<&String> = users.keys().collect();
+            usernames.sort();
+            for username in usernames {
+                println!("{}", username);
+            }
+            return Ok(());
+        }
+    }
+
+    eprintln!("a running server picks this up after a SIGHUP");
+    Ok(())
+}
+
+/// Debug builds run from the repository with its example config.
+fn default_config() -> Option<PathBuf> {
+    cfg!(debug_assertions).then(|| PathBuf::from("./finger.config"))
+}
+
+fn parse_override(arg: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or(format!("'{}' is not KEY=VALUE", arg))?;
+    Ok((key.trim().to_owned(), value.to_owned()))
+}
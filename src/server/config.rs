@@ -0,0 +1,927 @@
+use crate::networking::{Change, Limits};
+use crate::server::{
+    accesslog::{AccessLogFormat, AccessLogRotation},
+    ban::IpRange,
+    hook::Event,
+    i18n::{self, Translations},
+    logging::{self, LogFormat, LogRotation},
+    messages::Messages,
+    prelude::*,
+    push,
+    utmp::SystemUsers,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use zeroize::{Zeroize, Zeroizing};
+
+// the config format this server reads, bumped when a key changes meaning or goes away
+pub const CONFIG_VERSION: u32 = 1;
+// environment variables starting with this override the config key named by the rest
+const ENV_PREFIX: &str = "FNGR_";
+
+/// Where the config comes from: its file, then `FNGR_*` variables, then `--set` flags, each
+/// overriding keys of the one before.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub path: Option<PathBuf>,
+    /// Config keys and their values in toml, like `port` and `38273`.
+    pub overrides: Vec<(String, String)>,
+}
+
+pub struct Config {
+    /// Where the http api listens, like `0.0.0.0:38273` and `[::]:38273`.
+    pub socket_paths: Vec<String>,
+    pub finger_socket_paths: Vec<String>,
+    pub tls: Option<TlsConfig>,
+    pub unix_socket: Option<PathBuf>,
+    pub public_url: String,
+    /// A prefix like `/fngr` that every path starts with behind a reverse proxy, or empty.
+    pub base_path: String,
+    /// Proxies whose `Forwarded` and `X-Forwarded-For` headers are believed.
+    pub trusted_proxies: Vec<IpRange>,
+    pub cors: Option<CorsConfig>,
+    pub users_list: PathBuf,
+    pub status_file: PathBuf,
+    pub store: StoreKind,
+    pub database: PathBuf,
+    pub registration: bool,
+    pub registration_mode: RegistrationMode,
+    pub invites_file: PathBuf,
+    pub groups_file: PathBuf,
+    pub auth_key: Option<Zeroizing<String>>,
+    pub admin_key: Option<Zeroizing<String>>,
+    pub banned_names: Vec<String>,
+    pub banned_ips: Vec<IpRange>,
+    pub auth: AuthKind,
+    pub ldap: Option<LdapConfig>,
+    pub moderation: ModerationConfig,
+    pub lock: PathBuf,
+    pub max_snooze: u64,
+    pub name_cooldown_secs: u64,
+    pub session_ttl: u64,
+    pub rate_limit_burst: u32,
+    pub rate_limit_refill: f64,
+    pub lockout_threshold: u32,
+    pub lockout_secs: u64,
+    pub max_connections: usize,
+    pub run_as_user: Option<String>,
+    pub run_as_group: Option<String>,
+    pub read_timeout_secs: u64,
+    pub write_timeout_secs: u64,
+    pub limits: Limits,
+    pub access_log: Option<PathBuf>,
+    pub access_log_format: AccessLogFormat,
+    pub access_log_rotation: AccessLogRotation,
+    pub auth_log: Option<PathBuf>,
+    pub log: LogConfig,
+    pub webhooks: Vec<WebhookConfig>,
+    pub matrix: Option<MatrixConfig>,
+    pub irc: Option<IrcConfig>,
+    pub hooks: Vec<HookConfig>,
+    pub max_hooks: usize,
+    pub hook_timeout_secs: u64,
+    pub log_lookup_ips: bool,
+    pub max_lookups: usize,
+    pub lookup_retention_secs: u64,
+    pub max_status_history: usize,
+    pub max_glob_results: usize,
+    pub idle_timeout_secs: u64,
+    pub worker_interval_secs: u64,
+    pub avatar_dir: PathBuf,
+    pub max_avatar_bytes: usize,
+    pub feed_size: usize,
+    pub finger_forwarding: bool,
+    pub max_finger_hops: u32,
+    pub peers: Vec<PeerConfig>,
+    pub peer_sync_secs: u64,
+    pub system_users: SystemUsers,
+    pub utmp: PathBuf,
+    pub wtmp: PathBuf,
+    pub mail_spool: PathBuf,
+    pub mailboxes: HashMap<String, PathBuf>,
+    pub motd: Option<String>,
+    pub messages: Messages,
+    /// The language of text and html responses when the client's aren't there.
+    pub language: String,
+    pub translations: Arc<Translations>,
+    pub pages: PagesConfig,
+    /// Lets users get push notifications through ntfy and gotify.
+    pub push_notifications: bool,
+    /// Where bare ntfy topics are posted.
+    pub ntfy_server: String,
+    // file: File,
+}
+
+impl Config {
+    pub async fn load(source: &ConfigSource) -> Result<Self, FngrError> {
+        let p = if let Some(p) = source.path.clone() {
+            is_relative("config", &p).map_err(server_error)?;
+            p
+        } else {
+            PathBuf::from("/etc/fngr-server/config")
+        };
+
+        info!("loading config from {}", p.display());
+
+        let overrides: Vec<_> = env_overrides()
+            .chain(source.overrides.iter().cloned())
+            .collect();
+        if !overrides.is_empty() {
+            let mut keys: Vec<_> = overrides.iter().map(|(key, _)| key.as_str()).collect();
+            keys.sort_unstable();
+            keys.dedup();
+            info!("overriding {}", keys.join(", "));
+        }
+
+        let init = InitialConfig::load(&p, &overrides).await.map_err(|e| {
+            FngrError::Server(format!("invalid config {}: {}", p.display(), e).into())
+        })?;
+        init.validate().map_err(|e| {
+            FngrError::Server(format!("invalid config {}: {}", p.display(), e).into())
+        })?;
+
+        let listen = init.listen().map_err(server_error)?;
+        let socket_paths: Vec<_> = listen
+            .iter()
+            .map(|(host, port)| format!("{}:{}", host, port))
+            .collect();
+        let base_path = init
+            .base_path
+            .as_deref()
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_owned();
+        let public_url = init
+            .public_url
+            .unwrap_or(format!("http://{}{}", socket_paths[0], base_path))
+            .trim_end_matches('/')
+            .to_owned();
+
+        // finger and tls are served on every host the http api is
+        let mut hosts: Vec<&str> = vec![];
+        for (host, _) in &listen {
+            if !hosts.contains(&host.as_str()) {
+                hosts.push(host);
+            }
+        }
+        let on_hosts = |port: u16| -> Vec<String> {
+            hosts
+                .iter()
+                .map(|host| format!("{}:{}", host, port))
+                .collect()
+        };
+        let finger_socket_paths = init.finger_port.map(on_hosts).unwrap_or_default();
+
+        let tls = match (init.tls_port, init.tls_cert, init.tls_key) {
+            (Some(port), Some(cert), Some(key)) => Some(TlsConfig {
+                socket_paths: on_hosts(port),
+                cert,
+                key,
+            }),
+            (Some(_), _, _) => {
+                return Err(FngrError::Server(
+                    "tls_port requires tls_cert and tls_key".into(),
+                ));
+            }
+            (None, _, _) => None,
+        };
+        let users_list = PathBuf::from(init.users_list);
+        let status_file = init
+            .status_file
+            .unwrap_or(users_list.with_extension("status"));
+        let database = init.database.unwrap_or(users_list.with_extension("db"));
+        let invites_file = init
+            .invites_file
+            .unwrap_or(users_list.with_extension("invites"));
+        let groups_file = init
+            .groups_file
+            .unwrap_or(users_list.with_extension("groups"));
+        let avatar_dir = init
+            .avatar_dir
+            .unwrap_or(users_list.with_file_name("avatars"));
+        let defaults = Limits::default();
+        let limits = Limits {
+            request_line: init.max_request_line.unwrap_or(defaults.request_line),
+            header_bytes: init.max_header_bytes.unwrap_or(defaults.header_bytes),
+            headers: init.max_headers.unwrap_or(defaults.headers),
+            body: init.max_body_bytes.unwrap_or(defaults.body),
+        };
+        let auth_key = Secret::resolve("auth_key", init.auth_key, init.auth_key_file)
+            .await
+            .map_err(server_error)?;
+        let admin_key = Secret::resolve("admin_key", init.admin_key, init.admin_key_file)
+            .await
+            .map_err(server_error)?;
+        let translations =
+            Translations::load(init.translations.unwrap_or_default()).map_err(server_error)?;
+        let language = init
+            .language
+            .map(|language| language.to_ascii_lowercase())
+            .unwrap_or(i18n::ENGLISH.to_owned());
+        if !translations.has(&language) {
+            return Err(FngrError::Server(
+                format!("there are no messages in language '{}'", language).into(),
+            ));
+        }
+        let lock = init.lock;
+        // let file = fs;
+        let regis = init.registration;
+
+        if auth_key.is_none() && regis {
+            warn!("registration is enabled and authentication key is empty: anybody can register")
+        }
+
+        Ok(Self {
+            socket_paths,
+            finger_socket_paths,
+            tls,
+            unix_socket: init.unix_socket,
+            public_url,
+            base_path,
+            trusted_proxies: init.trusted_proxies.unwrap_or_default(),
+            cors: init.cors,
+            users_list,
+            status_file,
+            store: init.store.unwrap_or_default(),
+            admin_key: admin_key.filter(|key| !key.is_empty()),
+            banned_names: init.banned_names.unwrap_or_default(),
+            banned_ips: init.banned_ips.unwrap_or_default(),
+            auth: init.auth.unwrap_or_default(),
+            ldap: init.ldap,
+            moderation: init.moderation.unwrap_or_default(),
+            database,
+            auth_key,
+            lock: lock.unwrap_or(PathBuf::from("/var/finger.lock")),
+            max_snooze: init.max_snooze.unwrap_or(3600 * 4),
+            name_cooldown_secs: init.name_cooldown_secs.unwrap_or(0),
+            session_ttl: init.session_ttl.unwrap_or(86400),
+            rate_limit_burst: init.rate_limit_burst.unwrap_or(60),
+            rate_limit_refill: init.rate_limit_refill.unwrap_or(1.0),
+            lockout_threshold: init.lockout_threshold.unwrap_or(5),
+            lockout_secs: init.lockout_secs.unwrap_or(300),
+            max_connections: init.max_connections.unwrap_or(1024).max(1),
+            run_as_user: init.run_as_user,
+            run_as_group: init.run_as_group,
+            // zero timeouts would drop every connection before it could send anything
+            read_timeout_secs: init.read_timeout_secs.unwrap_or(30).max(1),
+            write_timeout_secs: init.write_timeout_secs.unwrap_or(30).max(1),
+            limits,
+            access_log: init.access_log,
+            access_log_format: init.access_log_format.unwrap_or_default(),
+            access_log_rotation: init.access_log_rotation.unwrap_or_default(),
+            auth_log: init.auth_log,
+            log: init.log.unwrap_or_default(),
+            webhooks: init.webhooks.unwrap_or_default(),
+            matrix: init.matrix,
+            irc: init.irc,
+            hooks: init.hooks.unwrap_or_default(),
+            max_hooks: init.max_hooks.unwrap_or(4).max(1),
+            hook_timeout_secs: init.hook_timeout_secs.unwrap_or(10).max(1),
+            log_lookup_ips: init.log_lookup_ips.unwrap_or(false),
+            max_lookups: init.max_lookups.unwrap_or(100),
+            lookup_retention_secs: init.lookup_retention_secs.unwrap_or(86400 * 7),
+            max_status_history: init.max_status_history.unwrap_or(20),
+            max_glob_results: init.max_glob_results.unwrap_or(50),
+            idle_timeout_secs: init.idle_timeout_secs.unwrap_or(3600),
+            // a zero interval would have the worker spin
+            worker_interval_secs: init.worker_interval_secs.unwrap_or(60).max(1),
+            avatar_dir,
+            max_avatar_bytes: init.max_avatar_bytes.unwrap_or(256 * 1024),
+            feed_size: init.feed_size.unwrap_or(100),
+            finger_forwarding: init.finger_forwarding.unwrap_or(false),
+            max_finger_hops: init.max_finger_hops.unwrap_or(3),
+            peers: init.peers.unwrap_or_default(),
+            peer_sync_secs: init.peer_sync_secs.unwrap_or(300).max(1),
+            system_users: init.system_users.unwrap_or_default(),
+            utmp: init.utmp.unwrap_or(PathBuf::from("/var/run/utmp")),
+            wtmp: init.wtmp.unwrap_or(PathBuf::from("/var/log/wtmp")),
+            mail_spool: init.mail_spool.unwrap_or(PathBuf::from("/var/mail")),
+            mailboxes: init.mailboxes.unwrap_or_default(),
+            motd: init.motd.filter(|motd| !motd.trim().is_empty()),
+            messages: init.messages.unwrap_or_default(),
+            language,
+            translations: Arc::new(translations),
+            pages: init.pages.unwrap_or_default(),
+            push_notifications: init.push_notifications.unwrap_or(false),
+            ntfy_server: init.ntfy_server.unwrap_or("https://ntfy.sh".to_owned()),
+            // file,
+            registration: regis,
+            registration_mode: init.registration_mode.unwrap_or_default(),
+            invites_file,
+            groups_file,
+        })
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    /// How long a client gets to send a whole request, or the next one on a kept alive
+    /// connection.
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout_secs)
+    }
+
+    /// How long a client gets to take a response off our hands.
+    pub fn write_timeout(&self) -> Duration {
+        Duration::from_secs(self.write_timeout_secs)
+    }
+
+    /// Names of the settings that differ from `new` but only take effect on a restart.
+    pub fn needs_restart(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = vec![];
+        if self.socket_paths != new.socket_paths {
+            changed.push("listen");
+        }
+        if self.finger_socket_paths != new.finger_socket_paths {
+            changed.push("finger_port");
+        }
+        if self.tls != new.tls {
+            changed.push("tls");
+        }
+        if self.unix_socket != new.unix_socket {
+            changed.push("unix_socket");
+        }
+        if self.users_list != new.users_list
+            || self.status_file != new.status_file
+            || self.store != new.store
+            || self.database != new.database
+            || self.invites_file != new.invites_file
+            || self.groups_file != new.groups_file
+        {
+            changed.push("store");
+        }
+        if self.max_connections != new.max_connections {
+            changed.push("max_connections");
+        }
+        if self.max_hooks != new.max_hooks {
+            changed.push("max_hooks");
+        }
+        if self.lock != new.lock {
+            changed.push("lock");
+        }
+        if self.run_as_user != new.run_as_user || self.run_as_group != new.run_as_group {
+            changed.push("run_as_user/run_as_group");
+        }
+        if self.access_log != new.access_log
+            || self.access_log_format != new.access_log_format
+            || self.access_log_rotation != new.access_log_rotation
+        {
+            changed.push("access_log");
+        }
+        if self.auth_log != new.auth_log {
+            changed.push("auth_log");
+        }
+        if self.log != new.log {
+            changed.push("log");
+        }
+        if self.matrix != new.matrix {
+            changed.push("matrix");
+        }
+        if self.irc != new.irc {
+            changed.push("irc");
+        }
+        changed
+    }
+}
+
+/// A url that gets presence changes posted to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Signs every payload with hmac-sha256 in the `X-Fngr-Signature` header.
+    pub secret: Option<String>,
+    /// Which changes to post, every change if empty.
+    #[serde(default)]
+    pub events: Vec<Change>,
+}
+
+/// A matrix room that presence changes are announced in and `!finger` is answered in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatrixConfig {
+    /// Like `https://matrix.org`.
+    pub homeserver: String,
+    /// The access token of the bot's account.
+    pub token: String,
+    /// A room id like `!abc:matrix.org` or an alias like `#fngr:matrix.org`, joined on start.
+    pub room: String,
+    /// Which changes to announce, every change if empty.
+    #[serde(default)]
+    pub events: Vec<Change>,
+}
+
+/// An irc channel that presence changes are announced in and `!finger` is answered in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IrcConfig {
+    /// Like `irc.libera.chat:6697`.
+    pub server: String,
+    /// Whether to connect with tls, true if left out.
+    pub tls: Option<bool>,
+    /// `fngr` if left out.
+    pub nick: Option<String>,
+    /// Sent with `PASS`, which most networks take for the nick's password.
+    pub password: Option<String>,
+    /// Like `#fngr`.
+    pub channel: String,
+    /// Which changes to announce, every change if empty.
+    #[serde(default)]
+    pub events: Vec<Change>,
+}
+
+/// A command run on events, with the event as json on stdin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    pub events: Vec<Event>,
+    /// The program and its arguments, run without a shell.
+    pub command: Vec<String>,
+}
+
+/// Another fngr server that we exchange presence with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeerConfig {
+    /// Its `host:port`, also the suffix its users get in `list`.
+    pub host: String,
+    /// Shared with the peer, which must have the same key configured for us.
+    pub key: String,
+}
+
+/// A secret as written in the config: the secret itself, `{ file = "/path" }` to read it from a
+/// file or `{ env = "NAME" }` to take it from an environment variable.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Secret {
+    Inline(String),
+    File { file: PathBuf },
+    Env { env: String },
+}
+
+impl Secret {
+    /// The secret `name` refers to, either as `name` or as `{name}_file`.
+    async fn resolve(
+        name: &str,
+        secret: Option<Self>,
+        file: Option<PathBuf>,
+    ) -> Result<Option<Zeroizing<String>>> {
+        let secret = match (secret, file) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("set {} or {}_file, not both", name, name));
+            }
+            (None, None) => return Ok(None),
+            (None, Some(file)) | (Some(Secret::File { file }), None) => file,
+            (Some(Secret::Inline(secret)), None) => return Ok(Some(Zeroizing::new(secret))),
+            (Some(Secret::Env { env }), None) => {
+                return match std::env::var(&env) {
+                    Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+                    Err(_) => Err(anyhow!("{} is read from ${}, which isn't set", name, env)),
+                };
+            }
+        };
+
+        is_relative(name, &secret)?;
+        let mut contents = Zeroizing::new(
+            tokio::fs::read_to_string(&secret)
+                .await
+                .map_err(|e| anyhow!("can't read {} from {}: {}", name, secret.display(), e))?,
+        );
+        // files tend to end in a newline that isn't part of the secret
+        let len = contents.trim_end_matches(['\r', '\n']).len();
+        contents.truncate(len);
+
+        Ok(Some(contents))
+    }
+}
+
+#[derive(PartialEq)]
+pub struct TlsConfig {
+    pub socket_paths: Vec<String>,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Which backend checks the keys of users.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthKind {
+    /// The hashes of the keys handed out by `register`.
+    #[default]
+    Local,
+    /// Binding to a directory as the user, with their password as the key.
+    Ldap,
+}
+
+/// The directory to bind to with `auth = "ldap"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LdapConfig {
+    /// Like `ldaps://ldap.example.org`.
+    pub url: String,
+    /// The dn to bind as, with `{username}` where the username goes.
+    pub bind_dn: String,
+}
+
+/// Which other sites may call the api from a browser.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Like `https://example.org`, or `*` for any site.
+    pub origins: Vec<String>,
+    /// `GET`, `HEAD` and `POST` if left out.
+    pub methods: Option<Vec<String>>,
+    /// Request headers pages may send, `Authorization` and `Content-Type` if left out.
+    pub headers: Option<Vec<String>>,
+    /// How long browsers may remember a preflight, 600 seconds if left out.
+    pub max_age_secs: Option<u64>,
+}
+
+/// Templates for the finger pages browsers get.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PagesConfig {
+    /// The server's template, the built in one if left out.
+    pub template: Option<PathBuf>,
+    /// Where users' own `<username>.html` templates and `<username>.css` stylesheets are.
+    pub dir: Option<PathBuf>,
+}
+
+/// Where the server's own log goes and how much of it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogConfig {
+    /// Like `info`, or `info,fngr::server::peer=debug` for more from one module.
+    pub level: Option<String>,
+    pub format: Option<LogFormat>,
+    /// Stdout if left out.
+    pub file: Option<PathBuf>,
+    pub rotation: Option<LogRotation>,
+    /// When `rotation` is `size`, 10 if left out.
+    pub max_size_mb: Option<u64>,
+    /// Rotated files to keep, 5 with `size` and all of them otherwise if left out.
+    pub max_files: Option<usize>,
+}
+
+/// What users may write in their status texts and profiles.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModerationConfig {
+    /// Longest text in characters, on top of the limits of each profile field.
+    pub max_length: Option<usize>,
+    /// Refused as whole words anywhere in a text, ignoring case.
+    #[serde(default)]
+    pub banned_words: Vec<String>,
+    /// Asked about every text, see [`crate::server::moderation::Check::Command`].
+    pub command: Option<PathBuf>,
+}
+
+/// Who gets an account by registering.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationMode {
+    /// Anybody with the `auth_key`, if there is one.
+    #[default]
+    Open,
+    /// Only with a single use code from `admin/invite`.
+    Invite,
+    /// Anybody, but they can't log in until an admin approves them.
+    Approval,
+}
+
+/// Which backend users and statuses are kept in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+// unknown keys are refused, so a typo doesn't quietly leave a setting at its default
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InitialConfig {
+    version: Option<u32>,
+    address: Option<String>,
+    port: Option<u16>,
+    listen: Option<Vec<String>>,
+    finger_port: Option<u16>,
+    tls_port: Option<u16>,
+    tls_cert: Option<PathBuf>,
+    tls_key: Option<PathBuf>,
+    unix_socket: Option<PathBuf>,
+    public_url: Option<String>,
+    base_path: Option<String>,
+    trusted_proxies: Option<Vec<IpRange>>,
+    cors: Option<CorsConfig>,
+    users_list: String,
+    status_file: Option<PathBuf>,
+    store: Option<StoreKind>,
+    database: Option<PathBuf>,
+    registration: bool,
+    registration_mode: Option<RegistrationMode>,
+    invites_file: Option<PathBuf>,
+    groups_file: Option<PathBuf>,
+    auth_key: Option<Secret>,
+    auth_key_file: Option<PathBuf>,
+    admin_key: Option<Secret>,
+    admin_key_file: Option<PathBuf>,
+    banned_names: Option<Vec<String>>,
+    banned_ips: Option<Vec<IpRange>>,
+    auth: Option<AuthKind>,
+    ldap: Option<LdapConfig>,
+    moderation: Option<ModerationConfig>,
+    lock: Option<PathBuf>,
+    max_snooze: Option<u64>,
+    name_cooldown_secs: Option<u64>,
+    session_ttl: Option<u64>,
+    rate_limit_burst: Option<u32>,
+    rate_limit_refill: Option<f64>,
+    lockout_threshold: Option<u32>,
+    lockout_secs: Option<u64>,
+    max_connections: Option<usize>,
+    run_as_user: Option<String>,
+    run_as_group: Option<String>,
+    read_timeout_secs: Option<u64>,
+    write_timeout_secs: Option<u64>,
+    max_request_line: Option<usize>,
+    max_header_bytes: Option<usize>,
+    max_headers: Option<usize>,
+    max_body_bytes: Option<usize>,
+    access_log: Option<PathBuf>,
+    access_log_format: Option<AccessLogFormat>,
+    access_log_rotation: Option<AccessLogRotation>,
+    auth_log: Option<PathBuf>,
+    log: Option<LogConfig>,
+    webhooks: Option<Vec<WebhookConfig>>,
+    matrix: Option<MatrixConfig>,
+    irc: Option<IrcConfig>,
+    hooks: Option<Vec<HookConfig>>,
+    max_hooks: Option<usize>,
+    hook_timeout_secs: Option<u64>,
+    log_lookup_ips: Option<bool>,
+    max_lookups: Option<usize>,
+    lookup_retention_secs: Option<u64>,
+    max_status_history: Option<usize>,
+    max_glob_results: Option<usize>,
+    idle_timeout_secs: Option<u64>,
+    worker_interval_secs: Option<u64>,
+    avatar_dir: Option<PathBuf>,
+    max_avatar_bytes: Option<usize>,
+    feed_size: Option<usize>,
+    finger_forwarding: Option<bool>,
+    max_finger_hops: Option<u32>,
+    peers: Option<Vec<PeerConfig>>,
+    peer_sync_secs: Option<u64>,
+    system_users: Option<SystemUsers>,
+    utmp: Option<PathBuf>,
+    wtmp: Option<PathBuf>,
+    mail_spool: Option<PathBuf>,
+    mailboxes: Option<HashMap<String, PathBuf>>,
+    motd: Option<String>,
+    messages: Option<Messages>,
+    language: Option<String>,
+    translations: Option<HashMap<String, Messages>>,
+    pages: Option<PagesConfig>,
+    push_notifications: Option<bool>,
+    ntfy_server: Option<String>,
+}
+
+impl InitialConfig {
+    async fn load(p: &Path, overrides: &[(String, String)]) -> Result<Self> {
+        let mut table = match tokio::fs::read(p).await {
+            Ok(mut buffer) => {
+                let table = toml::from_slice::<toml::Table>(&buffer);
+                // it may have secrets in it
+                buffer.zeroize();
+                table?
+            }
+            // a container can be configured with overrides alone
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !overrides.is_empty() => {
+                info!("there is no {}, using the overrides alone", p.display());
+                toml::Table::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for (key, value) in overrides {
+            table.insert(key.to_owned(), parse_value(value));
+        }
+
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    /// The hosts and ports the http api listens on, from `listen` or else `address` and `port`.
+    fn listen(&self) -> Result<Vec<(String, u16)>> {
+        match (&self.listen, &self.address, self.port) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err(anyhow!(
+                "listen takes the place of address and port, set one or the other"
+            )),
+            (Some(listen), None, None) if listen.is_empty() => {
+                Err(anyhow!("listen needs at least one address"))
+            }
+            (Some(listen), None, None) => listen
+                .iter()
+                .map(|addr| {
+                    split_addr(addr).ok_or_else(|| {
+                        anyhow!(
+                            "'{}' in listen isn't host:port, like 0.0.0.0:2323 or [::]:2323",
+                            addr
+                        )
+                    })
+                })
+                .collect(),
+            (None, Some(address), Some(port)) => Ok(vec![(bracket(address), port)]),
+            (None, _, _) => Err(anyhow!("address and port are needed, or else listen")),
+        }
+    }
+
+    /// Checks what serde can't: the version, the ports and the paths.
+    fn validate(&self) -> Result<()> {
+        match self.version {
+            None => warn!(
+                "the config has no version, add `version = {}` to it",
+                CONFIG_VERSION
+            ),
+            Some(0) => return Err(anyhow!("version starts at 1")),
+            Some(version) if version > CONFIG_VERSION => {
+                return Err(anyhow!(
+                    "version {} is newer than this server, which reads version {}",
+                    version,
+                    CONFIG_VERSION
+                ));
+            }
+            Some(_) => {}
+        }
+
+        if let Some(base_path) = &self.base_path
+            && !base_path.is_empty()
+            && (!base_path.starts_with('/')
+                || !base_path
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-._~/".contains(c)))
+        {
+            return Err(anyhow!(
+                "base_path must start with / and be letters, digits and -._~/, like /fngr"
+            ));
+        }
+
+        if self.run_as_group.is_some() && self.run_as_user.is_none() {
+            return Err(anyhow!("run_as_group needs run_as_user"));
+        }
+
+        if self
+            .hooks
+            .iter()
+            .flatten()
+            .any(|hook| hook.command.is_empty())
+        {
+            return Err(anyhow!("hooks need a command"));
+        }
+
+        // the http api may listen on one port on several hosts
+        let mut ports = vec![];
+        for (_, port) in self.listen()? {
+            if !ports.contains(&("port", port)) {
+                ports.push(("port", port));
+            }
+        }
+        ports.extend(
+            [
+                ("finger_port", self.finger_port),
+                ("tls_port", self.tls_port),
+            ]
+            .into_iter()
+            .filter_map(|(name, port)| Some((name, port?))),
+        );
+        for (i, (name, port)) in ports.iter().enumerate() {
+            if *port == 0 {
+                return Err(anyhow!("{} must be a port from 1 to 65535", name));
+            }
+            if let Some((other, _)) = ports[..i].iter().find(|(_, other)| other == port) {
+                return Err(anyhow!("{} and {} are both {}", other, name, port));
+            }
+        }
+
+        if let Some(log) = &self.log {
+            logging::targets(log.level.as_deref())?;
+            if log.file.is_none() && log.rotation.is_some_and(|r| r != LogRotation::Never) {
+                return Err(anyhow!("log.rotation needs a log.file to rotate"));
+            }
+        }
+
+        if let Some(server) = &self.ntfy_server
+            && !push::is_url(server)
+        {
+            return Err(anyhow!("ntfy_server must be an http or https url"));
+        }
+        if let Some(matrix) = &self.matrix
+            && !push::is_url(&matrix.homeserver)
+        {
+            return Err(anyhow!("matrix.homeserver must be an http or https url"));
+        }
+        if let Some(irc) = &self.irc {
+            if split_addr(&irc.server).is_none() {
+                return Err(anyhow!(
+                    "irc.server must be host:port, like irc.libera.chat:6697"
+                ));
+            }
+            // they end up in commands sent to the server
+            let words = [
+                ("irc.nick", irc.nick.as_deref().unwrap_or("fngr")),
+                ("irc.channel", &irc.channel),
+            ];
+            if let Some((name, _)) = words
+                .iter()
+                .find(|(_, word)| word.is_empty() || word.contains([' ', '\r', '\n', ',']))
+            {
+                return Err(anyhow!("{} can't be empty or have spaces or commas", name));
+            }
+        }
+
+        let paths = [
+            ("users_list", Some(Path::new(&self.users_list))),
+            ("status_file", self.status_file.as_deref()),
+            ("database", self.database.as_deref()),
+            ("invites_file", self.invites_file.as_deref()),
+            ("groups_file", self.groups_file.as_deref()),
+            ("lock", self.lock.as_deref()),
+            ("auth_key_file", self.auth_key_file.as_deref()),
+            ("admin_key_file", self.admin_key_file.as_deref()),
+            ("tls_cert", self.tls_cert.as_deref()),
+            ("tls_key", self.tls_key.as_deref()),
+            ("unix_socket", self.unix_socket.as_deref()),
+            ("access_log", self.access_log.as_deref()),
+            ("auth_log", self.auth_log.as_deref()),
+            (
+                "log.file",
+                self.log.as_ref().and_then(|log| log.file.as_deref()),
+            ),
+            ("avatar_dir", self.avatar_dir.as_deref()),
+            (
+                "pages.template",
+                self.pages
+                    .as_ref()
+                    .and_then(|pages| pages.template.as_deref()),
+            ),
+            (
+                "pages.dir",
+                self.pages.as_ref().and_then(|pages| pages.dir.as_deref()),
+            ),
+            ("utmp", self.utmp.as_deref()),
+            ("wtmp", self.wtmp.as_deref()),
+            ("mail_spool", self.mail_spool.as_deref()),
+        ];
+        let mailboxes = self.mailboxes.iter().flatten().map(|(_, path)| path);
+        for (name, path) in paths
+            .into_iter()
+            .filter_map(|(name, path)| Some((name, path?)))
+            .chain(mailboxes.map(|path| ("mailboxes", path.as_path())))
+        {
+            is_relative(name, path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The `FNGR_*` variables of the environment, as config keys and values.
+fn env_overrides() -> impl Iterator<Item = (String, String)> {
+    std::env::vars_os().filter_map(|(name, value)| {
+        let key = name.to_str()?.strip_prefix(ENV_PREFIX)?.to_lowercase();
+        Some((key, value.into_string().ok()?))
+    })
+}
+
+// a toml value like `38273` or `["root"]`, anything else is taken as a string
+fn parse_value(value: &str) -> toml::Value {
+    value
+        .parse()
+        .unwrap_or_else(|_| toml::Value::String(value.to_owned()))
+}
+
+/// The host and port of `host:port`, where an ipv6 host must be in brackets.
+fn split_addr(addr: &str) -> Option<(String, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    let bracketed = host.starts_with('[') && host.ends_with(']');
+    if host.is_empty() || (host.contains(':') && !bracketed) {
+        return None;
+    }
+
+    Some((host.to_owned(), port.parse().ok()?))
+}
+
+// an ipv6 address goes in brackets before a port
+fn bracket(address: &str) -> String {
+    if address.contains(':') && !address.starts_with('[') {
+        format!("[{}]", address)
+    } else {
+        address.to_owned()
+    }
+}
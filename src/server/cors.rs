@@ -0,0 +1,77 @@
+//! Cross origin requests, so a page on another site can call the api from a browser.
+//!
+//! Only origins listed in the `[cors]` section of the config get the headers that let a browser
+//! hand the response to the page. Everyone else gets the same responses as before, which a
+//! browser keeps from the page.
+
+use crate::networking::{Request, Response, ResponseStatus};
+
+use crate::server::config::CorsConfig;
+
+const DEFAULT_METHODS: &str = "GET, HEAD, POST";
+const DEFAULT_HEADERS: &str = "Authorization, Content-Type, X-Fngr-Version";
+const DEFAULT_MAX_AGE: u64 = 600;
+// response headers a page may read on top of the ones every browser allows
+const EXPOSED_HEADERS: &str = "Retry-After, X-Online-Count, X-Fngr-Version";
+
+/// The `Access-Control-Allow-Origin` for a request from `origin`, `None` if it isn't allowed.
+fn allow_origin<'a>(config: &'a CorsConfig, origin: &'a str) -> Option<&'a str> {
+    if config.origins.iter().any(|allowed| allowed == "*") {
+        Some("*")
+    } else {
+        config
+            .origins
+            .iter()
+            .find(|allowed| allowed.eq_ignore_ascii_case(origin))
+            .map(|_| origin)
+    }
+}
+
+/// Answers an `OPTIONS` request, a preflight if it comes from an allowed origin.
+pub fn preflight(config: Option<&CorsConfig>, request: &Request) -> Response {
+    let response = Response::from_bytes(ResponseStatus::NoContent, vec![])
+        .header("Allow", "GET, HEAD, POST, OPTIONS");
+    let Some((config, origin)) = config.zip(request.headers.get("origin")) else {
+        return response;
+    };
+    let Some(allowed) = allow_origin(config, origin) else {
+        return response;
+    };
+
+    let join = |values: &Option<Vec<String>>, default: &str| {
+        values
+            .as_ref()
+            .map(|values| values.join(", "))
+            .unwrap_or(default.to_owned())
+    };
+    response
+        .header("Access-Control-Allow-Origin", allowed)
+        .header(
+            "Access-Control-Allow-Methods",
+            join(&config.methods, DEFAULT_METHODS),
+        )
+        .header(
+            "Access-Control-Allow-Headers",
+            join(&config.headers, DEFAULT_HEADERS),
+        )
+        .header(
+            "Access-Control-Max-Age",
+            config.max_age_secs.unwrap_or(DEFAULT_MAX_AGE),
+        )
+        .header("Vary", "Origin")
+}
+
+/// Lets the page at `origin` read `response`, if it is allowed to.
+pub fn allow(config: Option<&CorsConfig>, origin: Option<&str>, response: Response) -> Response {
+    let Some(allowed) = config
+        .zip(origin)
+        .and_then(|(config, origin)| allow_origin(config, origin))
+    else {
+        return response;
+    };
+
+    response
+        .header("Access-Control-Allow-Origin", allowed)
+        .header("Access-Control-Expose-Headers", EXPOSED_HEADERS)
+        .header("Vary", "Origin")
+}
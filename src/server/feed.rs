@@ -0,0 +1,147 @@
+//! Atom (RFC 4287) feeds of recent presence and status changes.
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use uuid::Uuid;
+
+use crate::networking::{Change, Presence};
+
+/// The most recent changes of every user, oldest first.
+pub struct Feed {
+    entries: VecDeque<Entry>,
+    max: usize,
+    /// Last status text seen of every user, to tell a new one apart from a repeated one.
+    texts: HashMap<String, Option<String>>,
+}
+
+struct Entry {
+    id: Uuid,
+    username: String,
+    title: String,
+    text: Option<String>,
+    at: DateTime<Utc>,
+}
+
+impl Feed {
+    pub fn new(max: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            max,
+            texts: HashMap::new(),
+        }
+    }
+
+    /// Changes how many entries are kept, dropping the oldest beyond it.
+    pub fn set_max(&mut self, max: usize) {
+        self.max = max;
+        self.truncate();
+    }
+
+    pub fn record(&mut self, presence: &Presence) {
+        let text = presence.status.text.to_owned();
+        let previous = self
+            .texts
+            .insert(presence.username.to_owned(), text.clone());
+        let new_text = previous.as_ref() != Some(&text);
+
+        let title = match (presence.change, &text) {
+            (Change::Login, Some(text)) if new_text => format!("{}: {}", presence.username, text),
+            (Change::Login, _) => format!("{} is online", presence.username),
+            (Change::Logoff, _) => format!("{} logged off", presence.username),
+            (Change::Offline, _) => format!("{} went offline", presence.username),
+            (Change::Away, _) => format!("{} is away", presence.username),
+            (Change::Presence, _) => {
+                format!("{} is now {}", presence.username, presence.status.presence)
+            }
+        };
+
+        self.entries.push_back(Entry {
+            id: Uuid::from_bytes(rand::random()),
+            username: presence.username.to_owned(),
+            title,
+            text,
+            at: Utc::now(),
+        });
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.entries.len() > self.max {
+            self.entries.pop_front();
+        }
+    }
+
+    /// The feed as an atom document, only the entries of `user` if given and of users `listed`
+    /// says anyone may see.
+    pub fn atom(
+        &self,
+        public_url: &str,
+        user: Option<&str>,
+        listed: impl Fn(&str) -> bool,
+    ) -> String {
+        let (title, self_url) = match user {
+            Some(user) => (
+                format!("fngr: {}", user),
+                format!("{}/feed?user={}", public_url, user),
+            ),
+            None => ("fngr".to_owned(), format!("{}/feed", public_url)),
+        };
+
+        let entries: Vec<&Entry> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| user.is_none_or(|user| entry.username == user))
+            .filter(|entry| listed(&entry.username))
+            .collect();
+        let updated = entries
+            .first()
+            .map(|entry| entry.at)
+            .unwrap_or_else(Utc::now);
+
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        output.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        output.push_str(&format!("  <title>{}</title>\n", escape(&title)));
+        output.push_str(&format!("  <id>{}</id>\n", escape(&self_url)));
+        output.push_str(&format!(
+            "  <link rel=\"self\" href=\"{}\"/>\n",
+            escape(&self_url)
+        ));
+        output.push_str(&format!("  <updated>{}</updated>\n", timestamp(updated)));
+
+        for entry in entries {
+            let finger = format!("{}/finger?user={}", public_url, entry.username);
+            output.push_str("  <entry>\n");
+            output.push_str(&format!("    <title>{}</title>\n", escape(&entry.title)));
+            output.push_str(&format!("    <id>urn:uuid:{}</id>\n", entry.id));
+            output.push_str(&format!("    <updated>{}</updated>\n", timestamp(entry.at)));
+            output.push_str(&format!(
+                "    <author><name>{}</name></author>\n",
+                escape(&entry.username)
+            ));
+            output.push_str(&format!("    <link href=\"{}\"/>\n", escape(&finger)));
+            if let Some(text) = &entry.text {
+                output.push_str(&format!(
+                    "    <content type=\"text\">{}</content>\n",
+                    escape(text)
+                ));
+            }
+            output.push_str("  </entry>\n");
+        }
+
+        output.push_str("</feed>\n");
+        output
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
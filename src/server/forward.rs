@@ -0,0 +1,210 @@
+//! Fingering users on other servers, like the `user@host1@host2` chains of classic finger.
+//!
+//! The last host of a query is asked for the rest of it, over http if it runs fngr and with the
+//! finger protocol otherwise, so every hop strips one host off the chain.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use crate::{
+    Client,
+    networking::{JSONResponse, Request, Response, ResponseStatus},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, lookup_host},
+    time::timeout,
+};
+
+use crate::server::prelude::*;
+
+/// How many servers a query has already been forwarded through.
+pub const HOPS_HEADER: &str = "X-Fngr-Hops";
+
+// how long a remote server gets to answer
+const TIMEOUT: Duration = Duration::from_secs(10);
+// most of a finger protocol answer we relay
+const MAX_ANSWER: u64 = 64 * 1024;
+
+/// Splits `alice@host1@host2` into `alice@host1` and `host2`, `None` for a local user.
+pub fn split(query: &str) -> Option<(&str, &str)> {
+    query.rsplit_once('@')
+}
+
+/// Whether `host` names this server, whose public url is `public_url`.
+pub fn is_local(host: &str, public_url: &str) -> bool {
+    let authority = public_url
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(public_url);
+    let authority = authority.split('/').next().unwrap_or(authority);
+
+    host.eq_ignore_ascii_case(authority)
+        || host.eq_ignore_ascii_case(crate::server::webfinger::host(public_url))
+}
+
+/// The addresses of `host`, port 80 unless it has one.
+pub async fn resolve(host: &str) -> Result<Vec<SocketAddr>> {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if let Ok(port) = port.parse() => (name, port),
+        _ => (host, 80),
+    };
+
+    let addresses: Vec<SocketAddr> =
+        timeout(TIMEOUT, lookup_host((name.trim_matches(['[', ']']), port)))
+            .await
+            .map_err(|_| anyhow!("{} did not resolve in time", name))??
+            .collect();
+    if addresses.is_empty() {
+        return Err(anyhow!("{} has no address", name));
+    }
+    Ok(addresses)
+}
+
+/// The address to forward to out of `addresses`, `None` unless all of them are public, so queries
+/// can't reach into the network the server runs in.
+pub fn public(addresses: &[SocketAddr]) -> Option<SocketAddr> {
+    if !addresses.iter().all(|address| is_public(address.ip())) {
+        return None;
+    }
+    addresses.first().copied()
+}
+
+/// Whether `ip` is reachable from the internet, not loopback, private, link-local or unspecified.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Asks `address`, which `public` chose for `host`, about `query`, a fngr server on its http port
+/// or else a finger server on port 79.
+pub async fn finger(
+    query: &str,
+    host: &str,
+    address: SocketAddr,
+    hops: u32,
+) -> Result<JSONResponse> {
+    // connecting to the address that was checked, not whatever the name resolves to by then
+    let client = Client::new(address.to_string()).header(HOPS_HEADER, hops);
+    match timeout(TIMEOUT, client.finger(query, None)).await {
+        Ok(Ok(response)) => return Ok(response),
+        Ok(Err(e)) => debug!(?host, "not a fngr server, trying finger: {}", e),
+        Err(_) => debug!(?host, "no answer over http, trying finger"),
+    }
+
+    let lines = timeout(
+        TIMEOUT,
+        finger_protocol(query, SocketAddr::new(address.ip(), 79)),
+    )
+    .await
+    .map_err(|_| anyhow!("{} did not answer in time", host))??;
+    Ok(JSONResponse::Log(lines))
+}
+
+async fn finger_protocol(query: &str, address: SocketAddr) -> Result<Vec<String>> {
+    let mut stream = TcpStream::connect(address).await?;
+    stream
+        .write_all(format!("{}\r\n", query).as_bytes())
+        .await?;
+
+    let mut answer = vec![];
+    stream.take(MAX_ANSWER).read_to_end(&mut answer).await?;
+
+    // the answer is relayed as text, so keep out anything that could mess with a terminal
+    Ok(String::from_utf8_lossy(&answer)
+        .lines()
+        .map(|line| line.chars().filter(|c| !c.is_control()).collect())
+        .collect())
+}
+
+/// Relays a finger of `query` to `host`, unless it went through too many servers already.
+pub async fn relay(
+    req: &Request,
+    query: &str,
+    host: &str,
+    forwarding: bool,
+    max_hops: u32,
+) -> Response {
+    if !forwarding {
+        return Response::from(
+            ResponseStatus::Bad,
+            JSONResponse::Error("forwarding is disabled on this server".to_owned()),
+        );
+    }
+
+    let hops = req
+        .headers
+        .get(&HOPS_HEADER.to_ascii_lowercase())
+        .and_then(|hops| hops.trim().parse::<u32>().ok())
+        .unwrap_or(0)
+        + 1;
+    // every host left in the query is another hop to come
+    let chain = hops + query.matches('@').count() as u32;
+    if chain > max_hops {
+        return Response::from(
+            ResponseStatus::LoopDetected,
+            JSONResponse::Error(format!("forwarded through more than {} servers", max_hops)),
+        );
+    }
+
+    let address = match resolve(host).await.map(|addresses| public(&addresses)) {
+        Ok(Some(address)) => address,
+        Ok(None) => {
+            warn!(?host, "not forwarding to a private address");
+            return Response::from(
+                ResponseStatus::Forbidden,
+                JSONResponse::Error(format!("{} is not a public address", host)),
+            );
+        }
+        Err(e) => {
+            return Response::from(
+                ResponseStatus::BadGateway,
+                JSONResponse::Error(format!("could not reach {}: {}", host, e)),
+            );
+        }
+    };
+
+    info!(?query, ?host, hops, "forwarding finger");
+    match finger(query, host, address, hops).await {
+        Ok(JSONResponse::User {
+            status, profile, ..
+        }) => Response::from(
+            ResponseStatus::Ok,
+            JSONResponse::User {
+                username: format!("{}@{}", query, host),
+                status,
+                profile,
+            },
+        ),
+        Ok(JSONResponse::Error(e)) => Response::from(
+            ResponseStatus::NotFound,
+            JSONResponse::Error(format!("{}: {}", host, e)),
+        ),
+        Ok(response) => Response::from(ResponseStatus::Ok, response),
+        Err(e) => {
+            warn!(?host, "forwarding failed: {}", e);
+            Response::from(
+                ResponseStatus::BadGateway,
+                JSONResponse::Error(format!("could not reach {}: {}", host, e)),
+            )
+        }
+    }
+}
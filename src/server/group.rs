@@ -0,0 +1,109 @@
+//! Named groups of users like a team, so they can be fingered together, kept in a json file.
+//!
+//! Groups made with `admin/group` are only changed by admins. Any user can start a group of
+//! their own by joining one that doesn't exist yet, and anyone can join or leave those.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::server::{prelude::*, store::write_atomic};
+
+// longest group name, in characters
+pub const MAX_NAME: usize = 32;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Group {
+    /// Made by an admin, so users can't join or leave it.
+    #[serde(default)]
+    pub admin: bool,
+    pub members: Vec<String>,
+}
+
+pub struct Groups {
+    path: PathBuf,
+    groups: BTreeMap<String, Group>,
+}
+
+impl Groups {
+    /// Reads the groups at `path`, none if there is no file yet.
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        is_relative("groups file", &path)?;
+
+        let groups = match tokio::fs::read(&path).await {
+            Ok(buffer) => serde_json::from_slice(&buffer)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(anyhow!("can't read {}: {}", path.display(), e)),
+        };
+
+        Ok(Self { path, groups })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Group> {
+        self.groups.get(name)
+    }
+
+    /// Adds `username` to the group `name`, starting it if there is none, on disk first.
+    pub async fn join(&mut self, name: &str, username: &str) -> Result<()> {
+        let mut groups = self.groups.clone();
+        let group = groups.entry(name.to_owned()).or_default();
+        if !group.members.iter().any(|member| member == username) {
+            group.members.push(username.to_owned());
+        }
+        self.save(&groups).await?;
+        self.groups = groups;
+
+        Ok(())
+    }
+
+    /// Takes `username` out of the group `name`, which goes away with its last member, on disk
+    /// first.
+    pub async fn leave(&mut self, name: &str, username: &str) -> Result<()> {
+        let mut groups = self.groups.clone();
+        if let Some(group) = groups.get_mut(name) {
+            group.members.retain(|member| member != username);
+            if group.members.is_empty() {
+                groups.remove(name);
+            }
+        }
+        self.save(&groups).await?;
+        self.groups = groups;
+
+        Ok(())
+    }
+
+    /// Makes `members` the group `name`, an admin's from now on, or removes it if there are none,
+    /// on disk first.
+    pub async fn set(&mut self, name: &str, members: Vec<String>) -> Result<()> {
+        let mut groups = self.groups.clone();
+        if members.is_empty() {
+            groups.remove(name);
+        } else {
+            groups.insert(
+                name.to_owned(),
+                Group {
+                    admin: true,
+                    members,
+                },
+            );
+        }
+        self.save(&groups).await?;
+        self.groups = groups;
+
+        Ok(())
+    }
+
+    async fn save(&self, groups: &BTreeMap<String, Group>) -> Result<()> {
+        write_atomic(&self.path, serde_json::to_string_pretty(groups)?.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+/// Whether `name` can name a group: letters, digits, `-` and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= MAX_NAME
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
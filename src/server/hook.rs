@@ -0,0 +1,158 @@
+//! Runs the commands in the config's `[[hooks]]` on events, with the event as json on stdin.
+
+use std::{
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::networking::{Change, JSONStatus};
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, process::Command, sync::Semaphore, time::timeout};
+
+use crate::server::{
+    config::{Config, HookConfig},
+    prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Login,
+    Logoff,
+    /// Set offline by the server after going too long without a bump.
+    AutoOffline,
+    /// Set away by the server after going too long without a bump.
+    AutoAway,
+    /// Changed their presence with `setpresence`.
+    Presence,
+    Register,
+    Finger,
+}
+
+impl From<Change> for Event {
+    fn from(change: Change) -> Self {
+        match change {
+            Change::Login => Self::Login,
+            Change::Logoff => Self::Logoff,
+            Change::Offline => Self::AutoOffline,
+            Change::Away => Self::AutoAway,
+            Change::Presence => Self::Presence,
+        }
+    }
+}
+
+/// What a hook gets on stdin.
+#[derive(Debug, Serialize)]
+pub struct Payload<'a> {
+    pub event: Event,
+    pub username: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'a JSONStatus>,
+    /// Who fingered `username`, with `finger`. Left out for anonymous fingers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by: Option<&'a str>,
+    /// Unix timestamp of the event.
+    pub time: u64,
+}
+
+impl<'a> Payload<'a> {
+    pub fn new(event: Event, username: &'a str) -> Self {
+        Self {
+            event,
+            username,
+            status: None,
+            by: None,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+pub struct Hooks {
+    // one permit for each hook running, `max_hooks` in all
+    permits: Arc<Semaphore>,
+}
+
+impl Hooks {
+    pub fn new(max: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max)),
+        }
+    }
+
+    /// Starts the hooks in `config` that want `payload`'s event. Hooks over `max_hooks` wait for
+    /// a running one to finish.
+    pub fn fire(&self, config: &Config, payload: &Payload) {
+        let hooks: Vec<_> = config
+            .hooks
+            .iter()
+            .filter(|hook| hook.events.contains(&payload.event))
+            .cloned()
+            .collect();
+        if hooks.is_empty() {
+            return;
+        }
+
+        let input = match serde_json::to_vec(payload) {
+            Ok(input) => input,
+            Err(e) => {
+                error!("can't serialize hook payload: {}", e);
+                return;
+            }
+        };
+        let limit = Duration::from_secs(config.hook_timeout_secs);
+        for hook in hooks {
+            tokio::spawn(run(self.permits.clone(), hook, input.clone(), limit));
+        }
+    }
+}
+
+/// Runs `hook` with `input` on stdin, killing it if it takes longer than `limit`.
+async fn run(permits: Arc<Semaphore>, hook: HookConfig, input: Vec<u8>, limit: Duration) {
+    let Ok(_permit) = permits.acquire_owned().await else {
+        return;
+    };
+    // the config doesn't load with an empty command
+    let Some((program, args)) = hook.command.split_first() else {
+        return;
+    };
+
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            error!(program, "can't run hook: {}", e);
+            return;
+        }
+    };
+
+    let stdin = child.stdin.take();
+    let finished = async move {
+        if let Some(mut stdin) = stdin {
+            // a hook that doesn't read its input still gets to run
+            let _ = stdin.write_all(&input).await;
+        }
+        child.wait_with_output().await
+    };
+
+    match timeout(limit, finished).await {
+        Ok(Ok(output)) if output.status.success() => debug!(program, "hook finished"),
+        Ok(Ok(output)) => warn!(
+            program,
+            "hook {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Ok(Err(e)) => error!(program, "hook failed: {}", e),
+        Err(_) => warn!(program, "hook killed after {} seconds", limit.as_secs()),
+    }
+}
@@ -0,0 +1,79 @@
+//! Messages in the reader's language for text and html responses, picked by the
+//! `Accept-Language` header. Json is for programs and keeps the english messages.
+//!
+//! German and French come with the server. `[translations.<language>]` tables in the config add
+//! to them or add other languages, in the same form as `[messages]`, which is the english one.
+
+use std::collections::HashMap;
+
+use crate::server::{messages::Messages, prelude::*};
+
+pub const ENGLISH: &str = "en";
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("de", include_str!("i18n/de.toml")),
+    ("fr", include_str!("i18n/fr.toml")),
+];
+
+/// The messages of every language besides english.
+#[derive(Debug, Default, Clone)]
+pub struct Translations(HashMap<String, Messages>);
+
+impl Translations {
+    /// The built in languages, with `extra` on top.
+    pub fn load(extra: HashMap<String, Messages>) -> Result<Self> {
+        let mut languages = HashMap::new();
+        for (language, catalog) in BUILT_IN {
+            let messages: Messages = toml::from_str(catalog)
+                .map_err(|e| anyhow!("built in {} messages: {}", language, e))?;
+            languages.insert(language.to_string(), messages);
+        }
+
+        for (language, messages) in extra {
+            languages
+                .entry(language.to_ascii_lowercase())
+                .or_insert_with(Messages::default)
+                .extend(messages);
+        }
+
+        Ok(Self(languages))
+    }
+
+    pub fn has(&self, language: &str) -> bool {
+        language == ENGLISH || self.0.contains_key(language)
+    }
+
+    pub fn get(&self, language: &str) -> Option<&Messages> {
+        self.0.get(language)
+    }
+
+    /// The language `accept` prefers that we have, or `fallback`. A tag like `de-AT` also
+    /// matches `de`.
+    pub fn negotiate(&self, accept: Option<&str>, fallback: &str) -> String {
+        let mut best: Option<(String, f32)> = None;
+        for range in accept.unwrap_or("").split(',') {
+            let mut parts = range.split(';').map(str::trim);
+            let tag = parts.next().unwrap_or("").to_ascii_lowercase();
+            let language = if self.has(&tag) {
+                tag
+            } else {
+                match tag.split_once('-') {
+                    Some((primary, _)) if self.has(primary) => primary.to_owned(),
+                    _ => continue,
+                }
+            };
+            let q = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            // on a tie the first one listed wins
+            if q > 0.0 && best.as_ref().is_none_or(|(_, best_q)| q > *best_q) {
+                best = Some((language, q));
+            }
+        }
+
+        best.map(|(language, _)| language)
+            .unwrap_or(fallback.to_owned())
+    }
+}
@@ -0,0 +1,62 @@
+//! Single use invite codes for `registration_mode = "invite"`, kept in a json file.
+//!
+//! Only the hashes of codes are written down, like keys, so the file is no use to whoever reads it.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use uuid::Uuid;
+
+use crate::server::{prelude::*, store::write_atomic, userlist::hash_key};
+
+pub struct Invites {
+    path: PathBuf,
+    hashes: HashSet<String>,
+}
+
+impl Invites {
+    /// Reads the invites at `path`, none if there is no file yet.
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        is_relative("invites file", &path)?;
+
+        let hashes = match tokio::fs::read(&path).await {
+            Ok(buffer) => serde_json::from_slice(&buffer)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(anyhow!("can't read {}: {}", path.display(), e)),
+        };
+
+        Ok(Self { path, hashes })
+    }
+
+    /// Makes a new code, on disk first.
+    pub async fn create(&mut self) -> Result<Uuid> {
+        let code = Uuid::from_bytes(rand::random());
+        let mut hashes = self.hashes.clone();
+        hashes.insert(hash_key(code));
+        self.save(&hashes).await?;
+        self.hashes = hashes;
+
+        Ok(code)
+    }
+
+    pub fn is_valid(&self, code: &str) -> bool {
+        code.trim()
+            .parse()
+            .is_ok_and(|code| self.hashes.contains(&hash_key(code)))
+    }
+
+    /// Uses up `code`, on disk first.
+    pub async fn redeem(&mut self, code: &str) -> Result<()> {
+        let code = code.trim().parse()?;
+        let mut hashes = self.hashes.clone();
+        hashes.remove(&hash_key(code));
+        self.save(&hashes).await?;
+        self.hashes = hashes;
+
+        Ok(())
+    }
+
+    async fn save(&self, hashes: &HashSet<String>) -> Result<()> {
+        write_atomic(&self.path, serde_json::to_string_pretty(hashes)?.as_bytes()).await?;
+        Ok(())
+    }
+}
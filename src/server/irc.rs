@@ -0,0 +1,232 @@
+//! A bot in an irc channel that announces presence changes there and answers `!finger alice` like
+//! the finger protocol does.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::{
+        Mutex,
+        broadcast::{Receiver, error::RecvError},
+    },
+    time::sleep,
+};
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{ClientConfig, RootCertStore, pki_types::ServerName},
+};
+
+use crate::networking::Presence;
+use crate::server::{Fingr, config::IrcConfig, prelude::*, rfc1288};
+
+// between tries to reach the server after it failed
+const RETRY: Duration = Duration::from_secs(30);
+// most lines of a finger answer, so a list doesn't flood the channel
+const MAX_LINES: usize = 10;
+// longest text of a line, leaving room for the rest of the 512 bytes irc allows
+const MAX_TEXT: usize = 400;
+
+/// A line from the server, like `:alice!a@host PRIVMSG #fngr :!finger bob`.
+struct Message<'a> {
+    /// The nick of who sent it.
+    nick: Option<&'a str>,
+    command: &'a str,
+    params: Vec<&'a str>,
+}
+
+impl<'a> Message<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut nick = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (prefix, after) = prefixed.split_once(' ')?;
+            nick = prefix.split('!').next();
+            rest = after;
+        }
+
+        let (middle, trailing) = match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = middle.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?;
+        let mut params: Vec<_> = words.collect();
+        params.extend(trailing);
+        Some(Self {
+            nick,
+            command,
+            params,
+        })
+    }
+}
+
+/// Runs the bot if the config has an `[irc]` channel, reconnecting when the server drops it.
+pub async fn bridge(state: Arc<Mutex<Fingr>>) {
+    let Some(config) = state.lock().await.config.irc.clone() else {
+        return;
+    };
+    let mut updates = state.lock().await.presence.subscribe();
+
+    loop {
+        let result = match TcpStream::connect(&config.server).await {
+            Ok(stream) if config.tls.unwrap_or(true) => match tls(&config.server, stream).await {
+                Ok(stream) => session(&state, &config, stream, &mut updates).await,
+                Err(e) => Err(e),
+            },
+            Ok(stream) => session(&state, &config, stream, &mut updates).await,
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Ok(()) => warn!(server = config.server, "irc server closed the connection"),
+            Err(e) => error!(server = config.server, "irc failed: {}", e),
+        }
+        sleep(RETRY).await;
+    }
+}
+
+async fn tls(
+    server: &str,
+    stream: TcpStream,
+) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let host = server.rsplit_once(':').map_or(server, |(host, _)| host);
+    let name = ServerName::try_from(host.trim_matches(['[', ']']).to_owned())?;
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await?)
+}
+
+/// Registers, joins the channel and serves it until the connection ends.
+async fn session<S: AsyncRead + AsyncWrite>(
+    state: &Arc<Mutex<Fingr>>,
+    config: &IrcConfig,
+    stream: S,
+    updates: &mut Receiver<Presence>,
+) -> Result<()> {
+    let (reader, writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    let mut writer = Writer(writer);
+
+    let mut nick = config.nick.clone().unwrap_or("fngr".to_owned());
+    if let Some(password) = &config.password {
+        writer.send(&format!("PASS {}", password)).await?;
+    }
+    writer.send(&format!("NICK {}", nick)).await?;
+    writer.send(&format!("USER {} 0 * :fngr", nick)).await?;
+    let mut joined = false;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                let Some(message) = Message::parse(&line) else {
+                    continue;
+                };
+
+                match (message.command, message.params.as_slice()) {
+                    ("PING", params) => {
+                        writer.send(&format!("PONG :{}", params.first().unwrap_or(&""))).await?;
+                    }
+                    // welcome, registered with the server
+                    ("001", _) => {
+                        writer.send(&format!("JOIN {}", config.channel)).await?;
+                    }
+                    // nick in use
+                    ("433", _) if !joined => {
+                        nick.push('_');
+                        writer.send(&format!("NICK {}", nick)).await?;
+                    }
+                    ("JOIN", [channel, ..]) if message.nick == Some(&nick) => {
+                        info!(channel, nick, "joined the irc channel");
+                        joined = true;
+                    }
+                    ("PRIVMSG", [target, text]) => {
+                        let Some(query) = finger_query(text) else {
+                            continue;
+                        };
+                        // private messages are answered privately
+                        let to = if target.eq_ignore_ascii_case(&nick) {
+                            message.nick.unwrap_or(target)
+                        } else {
+                            target
+                        };
+                        info!(from = message.nick, query, "finger from irc");
+                        let output = rfc1288::query(state, query, None).await;
+                        let lines: Vec<_> = output
+                            .lines()
+                            .map(str::trim_end)
+                            .filter(|line| !line.is_empty())
+                            .collect();
+                        for line in lines.iter().take(MAX_LINES) {
+                            writer.privmsg(to, line).await?;
+                        }
+                        if lines.len() > MAX_LINES {
+                            writer.privmsg(to, &format!("... and {} more lines", lines.len() - MAX_LINES)).await?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            presence = updates.recv() => {
+                let presence = match presence {
+                    Ok(presence) => presence,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("irc missed {} presence changes", missed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                };
+                if !joined || (!config.events.is_empty() && !config.events.contains(&presence.change)) {
+                    continue;
+                }
+                let listed = state
+                    .lock()
+                    .await
+                    .users
+                    .get(&presence.username)
+                    .is_some_and(|user| user.listed_for(None));
+                if listed {
+                    writer.privmsg(&config.channel, &presence.to_string()).await?;
+                }
+            }
+        }
+    }
+}
+
+/// What `!finger` was asked for in `text`, `None` if it isn't one.
+fn finger_query(text: &str) -> Option<&str> {
+    let query = text.strip_prefix("!finger")?;
+    (query.is_empty() || query.starts_with(' ')).then(|| query.trim())
+}
+
+struct Writer<W>(W);
+
+impl<W: AsyncWrite + Unpin> Writer<W> {
+    async fn send(&mut self, line: &str) -> Result<()> {
+        self.0.write_all(line.as_bytes()).await?;
+        self.0.write_all(b"\r\n").await?;
+        Ok(())
+    }
+
+    /// Says `text` to `to`, cut to one line that fits.
+    async fn privmsg(&mut self, to: &str, text: &str) -> Result<()> {
+        // statuses are the users' own text, which must not start a command of its own
+        let mut text = text.replace(['\r', '\n'], " ");
+        if text.len() > MAX_TEXT {
+            let mut end = MAX_TEXT;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        self.send(&format!("PRIVMSG {} :{}", to, text)).await
+    }
+}
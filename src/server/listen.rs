@@ -0,0 +1,48 @@
+//! Binding the tcp listeners, on as many addresses as the config lists.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::{TcpListener, lookup_host};
+
+use crate::server::prelude::*;
+
+// connections waiting to be accepted, as tokio's own bind has it
+const BACKLOG: i32 = 1024;
+
+/// Binds every address in `addrs`, for serving `what`.
+pub async fn bind_all(addrs: &[String], what: &str) -> Result<Vec<TcpListener>> {
+    let mut listeners = vec![];
+    for addr in addrs {
+        listeners.push(bind(addr).await?);
+        info!("listening for {} on '{}'", what, addr);
+    }
+
+    Ok(listeners)
+}
+
+/// Binds `addr`, a `host:port`. An ipv6 socket only takes ipv6 connections, so `[::]` can be
+/// listened on next to `0.0.0.0` with the same port.
+pub async fn bind(addr: &str) -> Result<TcpListener> {
+    let resolved = lookup_host(addr)
+        .await
+        .map_err(|e| anyhow!("can't resolve {}: {}", addr, e))?
+        .next()
+        .ok_or_else(|| anyhow!("{} doesn't resolve to an address", addr))?;
+
+    let socket = Socket::new(
+        Domain::for_address(resolved),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if resolved.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket
+        .bind(&resolved.into())
+        .and_then(|()| socket.listen(BACKLOG))
+        .map_err(|e| anyhow!("can't listen on {}: {}", addr, e))?;
+
+    Ok(TcpListener::from_std(socket.into())?)
+}
@@ -0,0 +1,61 @@
+//! The lock file that keeps two servers from running on the same users list.
+
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{Read, Seek, Write},
+    path::Path,
+};
+
+use crate::server::prelude::*;
+
+/// Takes an exclusive advisory lock on `path` and writes our pid into it.
+///
+/// The lock goes away with the process that held it, so a lock file left behind by a crashed
+/// server is reclaimed, while one held by a running server is refused.
+pub fn acquire(path: &Path) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    let mut previous = String::new();
+    file.read_to_string(&mut previous)?;
+    let previous = previous.trim();
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            return Err(anyhow!(
+                "another server (pid {}) holds the lock at {}",
+                if previous.is_empty() {
+                    "unknown"
+                } else {
+                    previous
+                },
+                path.display()
+            ));
+        }
+        Err(TryLockError::Error(e)) => return Err(e.into()),
+    }
+
+    if !previous.is_empty() {
+        warn!("reclaiming stale lock left by pid {}", previous);
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    writeln!(file, "{}", std::process::id())?;
+    file.sync_all()?;
+
+    Ok(file)
+}
+
+/// Unlocks and removes the lock file.
+pub fn release(file: File, path: &Path) -> Result<()> {
+    // remove first so a server starting right now can't lock the file we are about to delete
+    std::fs::remove_file(path)?;
+    file.unlock()?;
+    Ok(())
+}
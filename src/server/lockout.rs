@@ -0,0 +1,95 @@
+use std::{collections::HashMap, hash::Hash, net::IpAddr, time::Duration};
+
+use tokio::time::Instant;
+
+/// Counts failed key checks per username and per client address.
+///
+/// Once either reaches `threshold` failures further attempts are refused until `cooldown` has
+/// passed since the last failure.
+pub struct Lockout {
+    users: HashMap<String, Failures>,
+    peers: HashMap<IpAddr, Failures>,
+    threshold: u32,
+    cooldown: Duration,
+}
+
+struct Failures {
+    count: u32,
+    last: Instant,
+}
+
+impl Lockout {
+    /// A `threshold` of 0 turns the lockout off.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            users: HashMap::new(),
+            peers: HashMap::new(),
+            threshold,
+            cooldown,
+        }
+    }
+
+    pub fn set_limits(&mut self, threshold: u32, cooldown: Duration) {
+        self.threshold = threshold;
+        self.cooldown = cooldown;
+    }
+
+    /// Seconds until `username` or `peer` may try again, `None` if neither is locked.
+    pub fn locked(&self, username: &str, peer: Option<IpAddr>) -> Option<u64> {
+        let user = self.remaining(self.users.get(username));
+        let peer = peer.and_then(|peer| self.remaining(self.peers.get(&peer)));
+        user.max(peer)
+    }
+
+    pub fn fail(&mut self, username: &str, peer: Option<IpAddr>) {
+        let cooldown = self.cooldown;
+        record(&mut self.users, username.to_owned(), cooldown);
+        self.fail_peer(peer);
+    }
+
+    /// Counts a failure against `peer` alone, for usernames that don't exist and so can't be
+    /// locked.
+    pub fn fail_peer(&mut self, peer: Option<IpAddr>) {
+        if let Some(peer) = peer {
+            record(&mut self.peers, peer, self.cooldown);
+        }
+    }
+
+    /// Forgets the failures of `username` after it got its key right.
+    pub fn succeed(&mut self, username: &str) {
+        self.users.remove(username);
+    }
+
+    /// Forgets failures older than the cooldown.
+    pub fn prune(&mut self) {
+        let cooldown = self.cooldown;
+        self.users.retain(|_, f| f.last.elapsed() < cooldown);
+        self.peers.retain(|_, f| f.last.elapsed() < cooldown);
+    }
+
+    fn remaining(&self, failures: Option<&Failures>) -> Option<u64> {
+        let failures = failures?;
+        if self.threshold == 0 || failures.count < self.threshold {
+            return None;
+        }
+
+        let left = self.cooldown.saturating_sub(failures.last.elapsed());
+        (!left.is_zero()).then(|| left.as_secs().max(1))
+    }
+}
+
+fn record<K: Eq + Hash>(map: &mut HashMap<K, Failures>, key: K, cooldown: Duration) {
+    let now = Instant::now();
+    let failures = map.entry(key).or_insert(Failures {
+        count: 0,
+        last: now,
+    });
+
+    // failures from before the last cooldown don't count anymore
+    if now.duration_since(failures.last) >= cooldown {
+        failures.count = 0;
+    }
+
+    failures.count += 1;
+    failures.last = now;
+}
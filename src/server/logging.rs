@@ -0,0 +1,184 @@
+//! The server's own log, as set up in the `[log]` section of the config. Separate from the access
+//! log, which only has the requests.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{self, writer::BoxMakeWriter},
+    prelude::*,
+};
+
+use crate::server::{config::LogConfig, prelude::*};
+
+const DEFAULT_LEVEL: &str = "info";
+const DEFAULT_MAX_SIZE_MB: u64 = 10;
+const DEFAULT_MAX_FILES: usize = 5;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Lines for people to read, in color on a terminal.
+    #[default]
+    Pretty,
+    /// One json object per line.
+    Json,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+    /// Once the file reaches `max_size_mb`.
+    Size,
+}
+
+/// Keeps the log going, and flushes what is still buffered when dropped.
+pub struct Guard {
+    _writer: Option<WorkerGuard>,
+    #[cfg(feature = "otel")]
+    _telemetry: Option<crate::server::otel::Telemetry>,
+}
+
+/// The filter for `level`, like `info` or `info,fngr::server::peer=debug`.
+pub fn targets(level: Option<&str>) -> Result<Targets> {
+    let level = level.unwrap_or(DEFAULT_LEVEL);
+    level
+        .parse()
+        .map_err(|e| anyhow!("log level '{}': {}", level, e))
+}
+
+/// Starts logging to stdout, or the configured file.
+pub fn init(config: &LogConfig) -> Result<Guard> {
+    let targets = targets(config.level.as_deref())?;
+
+    let (writer, guard) = match &config.file {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(open(path, config)?);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(io::stdout), None),
+    };
+    let layer = match config.format.unwrap_or_default() {
+        LogFormat::Pretty => fmt::layer()
+            .with_writer(writer)
+            .with_ansi(config.file.is_none())
+            .boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+    };
+
+    #[cfg(feature = "otel")]
+    let telemetry = crate::server::otel::Telemetry::init()?;
+    let registry = tracing_subscriber::registry();
+    #[cfg(feature = "otel")]
+    let registry = registry.with(telemetry.as_ref().map(|telemetry| telemetry.layer()));
+    registry.with(layer).with(targets).init();
+
+    Ok(Guard {
+        _writer: guard,
+        #[cfg(feature = "otel")]
+        _telemetry: telemetry,
+    })
+}
+
+/// The file at `path`, rotated as configured.
+fn open(path: &Path, config: &LogConfig) -> Result<Box<dyn Write + Send>> {
+    let rotation = match config.rotation.unwrap_or_default() {
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Size => {
+            let max_size = config.max_size_mb.unwrap_or(DEFAULT_MAX_SIZE_MB) * 1024 * 1024;
+            let max_files = config.max_files.unwrap_or(DEFAULT_MAX_FILES);
+            return Ok(Box::new(SizeRotating::open(path, max_size, max_files)?));
+        }
+    };
+
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or(anyhow!("'{}' is not a file", path.display()))?;
+    let mut appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(file_name.to_string_lossy());
+    if let Some(max_files) = config.max_files {
+        appender = appender.max_log_files(max_files);
+    }
+
+    Ok(Box::new(appender.build(directory)?))
+}
+
+/// A file that is moved to `<path>.1` once it would grow past `max_size`, `<path>.1` to
+/// `<path>.2` and so on, keeping `max_files` of the old ones.
+struct SizeRotating {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl SizeRotating {
+    fn open(path: &Path, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_owned(),
+            size: file.metadata()?.len(),
+            file,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                let old = self.numbered(n);
+                if old.exists() {
+                    fs::rename(old, self.numbered(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.numbered(1))?;
+        }
+
+        *self = Self::open(&self.path, self.max_size, self.max_files)?;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // a line longer than `max_size` still gets a file of its own
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,83 @@
+//! "No mail." and "New mail received ..." lines like traditional finger, from mbox files or Maildirs.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+// how long an answer is reused before the mailbox is looked at again
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Mail status of mailboxes, cached so fingers don't stat them every time.
+#[derive(Default)]
+pub struct MailCheck {
+    cache: HashMap<PathBuf, (Instant, Option<String>)>,
+}
+
+impl MailCheck {
+    /// A line about the mail in `path`, `None` if there is no mailbox there.
+    pub fn check(&mut self, path: &Path) -> Option<String> {
+        if let Some((checked, line)) = self.cache.get(path)
+            && checked.elapsed() < CACHE_TTL
+        {
+            return line.clone();
+        }
+
+        self.cache
+            .retain(|_, (checked, _)| checked.elapsed() < CACHE_TTL);
+        let line = inspect(path);
+        self.cache
+            .insert(path.to_owned(), (Instant::now(), line.clone()));
+        line
+    }
+}
+
+fn inspect(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.is_dir() {
+        return maildir(path);
+    }
+
+    if metadata.len() == 0 {
+        return Some("No mail.".to_owned());
+    }
+
+    let modified = metadata.modified().ok()?;
+    let accessed = metadata.accessed().ok()?;
+    Some(if modified > accessed {
+        format!(
+            "New mail received {}, unread since {}",
+            time(modified),
+            time(accessed)
+        )
+    } else {
+        format!("Mail last read {}", time(accessed))
+    })
+}
+
+/// Unread mail sits in `new`, read mail in `cur`.
+fn maildir(path: &Path) -> Option<String> {
+    let newest = fs::read_dir(path.join("new"))
+        .ok()?
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .max();
+    if let Some(newest) = newest {
+        return Some(format!("New mail received {}", time(newest)));
+    }
+
+    let read = fs::read_dir(path.join("cur"))
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    Some(if read { "No unread mail." } else { "No mail." }.to_owned())
+}
+
+fn time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a %b %e %H:%M %Y (UTC)")
+        .to_string()
+}
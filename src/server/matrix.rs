@@ -0,0 +1,285 @@
+//! A bot in a matrix room that announces presence changes there and answers `!finger alice` like
+//! the finger protocol does, through the homeserver's client-server api.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::{
+    sync::{
+        Mutex,
+        broadcast::{Receiver, error::RecvError},
+    },
+    time::sleep,
+};
+
+use crate::networking::Presence;
+use crate::server::{Fingr, config::MatrixConfig, feed, prelude::*, rfc1288};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+// how long the homeserver may hold a sync open waiting for new events
+const SYNC_WAIT: Duration = Duration::from_secs(30);
+// between tries to reach the homeserver after it failed
+const RETRY: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct Sync {
+    next_batch: String,
+    #[serde(default)]
+    rooms: Rooms,
+}
+
+#[derive(Default, Deserialize)]
+struct Rooms {
+    #[serde(default)]
+    join: HashMap<String, JoinedRoom>,
+}
+
+#[derive(Deserialize)]
+struct JoinedRoom {
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Default, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    events: Vec<RoomEvent>,
+}
+
+#[derive(Deserialize)]
+struct RoomEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    sender: String,
+    #[serde(default)]
+    content: Value,
+}
+
+/// The bot's account on the homeserver, joined to the room.
+struct Bot {
+    client: reqwest::Client,
+    homeserver: String,
+    token: String,
+    user_id: String,
+    room_id: String,
+}
+
+impl Bot {
+    async fn connect(config: &MatrixConfig) -> Result<Self> {
+        let mut bot = Self {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build()?,
+            homeserver: config.homeserver.trim_end_matches('/').to_owned(),
+            token: config.token.clone(),
+            user_id: String::new(),
+            room_id: String::new(),
+        };
+
+        let whoami = bot.call(Method::GET, "account/whoami", None).await?;
+        bot.user_id = string(&whoami, "user_id")?;
+        let joined = bot
+            .call(
+                Method::POST,
+                &format!("join/{}", encode(&config.room)),
+                Some(json!({})),
+            )
+            .await?;
+        bot.room_id = string(&joined, "room_id")?;
+        Ok(bot)
+    }
+
+    /// Calls `path` of the client-server api, answering with the json it answered.
+    async fn call(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let url = format!("{}/_matrix/client/v3/{}", self.homeserver, path);
+        let mut request = self.client.request(method, url).bearer_auth(&self.token);
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+        // a sync is held open for up to `SYNC_WAIT`
+        if path.starts_with("sync") {
+            request = request.timeout(TIMEOUT + SYNC_WAIT);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!("homeserver answered {}: {}", status, text.trim()));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Posts `text` to the room, and `html` for clients that show it.
+    async fn send(&self, text: &str, html: Option<String>) -> Result<()> {
+        let mut content = json!({ "msgtype": "m.notice", "body": text });
+        if let Some(html) = html {
+            content["format"] = json!("org.matrix.custom.html");
+            content["formatted_body"] = json!(html);
+        }
+        let txn = format!("{:016x}", rand::random::<u64>());
+        let path = format!(
+            "rooms/{}/send/m.room.message/{}",
+            encode(&self.room_id),
+            txn
+        );
+        self.call(Method::PUT, &path, Some(content)).await?;
+        Ok(())
+    }
+
+    /// New events since `since`, the ones so far without it.
+    async fn sync(&self, since: Option<&str>) -> Result<Sync> {
+        let filter = json!({
+            "room": {
+                "rooms": [self.room_id],
+                "timeline": { "types": ["m.room.message"] },
+                "state": { "types": [] },
+                "ephemeral": { "types": [] },
+                "account_data": { "types": [] },
+            },
+            "presence": { "types": [] },
+            "account_data": { "types": [] },
+        });
+        let mut path = format!("sync?filter={}", encode(&filter.to_string()));
+        if let Some(since) = since {
+            path += &format!("&since={}&timeout={}", encode(since), SYNC_WAIT.as_millis());
+        }
+        Ok(serde_json::from_value(
+            self.call(Method::GET, &path, None).await?,
+        )?)
+    }
+}
+
+/// Runs the bot if the config has a `[matrix]` room, reconnecting when the homeserver fails.
+pub async fn bridge(state: Arc<Mutex<Fingr>>) {
+    let Some(config) = state.lock().await.config.matrix.clone() else {
+        return;
+    };
+    // subscribed before connecting so changes meanwhile are announced
+    let updates = state.lock().await.presence.subscribe();
+
+    let bot = loop {
+        match Bot::connect(&config).await {
+            Ok(bot) => break Arc::new(bot),
+            Err(e) => {
+                error!("can't join the matrix room: {}", e);
+                sleep(RETRY).await;
+            }
+        }
+    };
+    info!(
+        room = bot.room_id,
+        user = bot.user_id,
+        "joined the matrix room"
+    );
+
+    tokio::join!(
+        announce(state.clone(), bot.clone(), &config, updates),
+        answer(state, bot),
+    );
+}
+
+/// Posts the presence changes of users anyone may see.
+async fn announce(
+    state: Arc<Mutex<Fingr>>,
+    bot: Arc<Bot>,
+    config: &MatrixConfig,
+    mut updates: Receiver<Presence>,
+) {
+    loop {
+        let presence = match updates.recv().await {
+            Ok(presence) => presence,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("matrix missed {} presence changes", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if !config.events.is_empty() && !config.events.contains(&presence.change) {
+            continue;
+        }
+        let listed = state
+            .lock()
+            .await
+            .users
+            .get(&presence.username)
+            .is_some_and(|user| user.listed_for(None));
+        if !listed {
+            continue;
+        }
+
+        if let Err(e) = bot.send(&presence.to_string(), None).await {
+            warn!("can't post to the matrix room: {}", e);
+        }
+    }
+}
+
+/// Answers `!finger` in the room, skipping what was said before the bot got there.
+async fn answer(state: Arc<Mutex<Fingr>>, bot: Arc<Bot>) {
+    let mut since = None;
+    loop {
+        let sync = match bot.sync(since.as_deref()).await {
+            Ok(sync) => sync,
+            Err(e) => {
+                warn!("matrix sync failed: {}", e);
+                sleep(RETRY).await;
+                continue;
+            }
+        };
+        let first = since.is_none();
+        since = Some(sync.next_batch);
+        if first {
+            continue;
+        }
+
+        let events = sync
+            .rooms
+            .join
+            .into_iter()
+            .filter(|(room, _)| *room == bot.room_id)
+            .flat_map(|(_, room)| room.timeline.events);
+        for event in events {
+            if event.kind != "m.room.message" || event.sender == bot.user_id {
+                continue;
+            }
+            let Some(body) = event.content["body"].as_str() else {
+                continue;
+            };
+            let Some(query) = body.strip_prefix("!finger") else {
+                continue;
+            };
+            if !query.is_empty() && !query.starts_with(' ') {
+                continue;
+            }
+
+            info!(
+                sender = event.sender,
+                query = query.trim(),
+                "finger from matrix"
+            );
+            let output = rfc1288::query(&state, query, None)
+                .await
+                .replace("\r\n", "\n");
+            let output = output.trim_end();
+            let html = format!("<pre><code>{}</code></pre>", feed::escape(output));
+            if let Err(e) = bot.send(output, Some(html)).await {
+                warn!("can't answer in the matrix room: {}", e);
+            }
+        }
+    }
+}
+
+/// The string at `key` of `value`.
+fn string(value: &Value, key: &str) -> Result<String> {
+    value[key]
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("homeserver answered without {}", key))
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
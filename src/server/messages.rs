@@ -0,0 +1,97 @@
+//! The server's own wording for the messages in its responses, from the `[messages]` table of the
+//! config, to translate them or give the server some personality.
+//!
+//! A message is looked up by its usual text, with the names in it written as placeholders:
+//!
+//! ```toml
+//! [messages]
+//! "you are now logged on" = "welcome back, {username}!"
+//! "user not found" = "nobody here goes by {user}"
+//! "only friends of {user} can finger them" = "{user} only talks to friends"
+//! ```
+//!
+//! `{username}` is who sent the request, `{user}` who it is about, `{status}` the status text it
+//! set and `{message}` the usual text.
+
+use std::collections::HashMap;
+
+use crate::networking::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Messages(HashMap<String, String>);
+
+/// What the placeholders of a request are filled with.
+#[derive(Debug, Default)]
+pub struct Placeholders {
+    username: Option<String>,
+    user: Option<String>,
+    status: Option<String>,
+}
+
+impl Placeholders {
+    pub fn from_request(request: &Request) -> Self {
+        Self {
+            username: request.username.clone(),
+            user: request.finger_user.clone(),
+            status: request.status.clone(),
+        }
+    }
+
+    /// `message` with the names in it put back as placeholders, `user` first as it is usually
+    /// the one a message is about.
+    fn key(&self, message: &str) -> String {
+        let mut key = message.to_owned();
+        for (name, placeholder) in [(&self.user, "{user}"), (&self.username, "{username}")] {
+            if let Some(name) = name.as_deref().filter(|name| !name.is_empty()) {
+                key = key.replace(name, placeholder);
+            }
+        }
+        key
+    }
+
+    fn fill(&self, template: &str, message: &str) -> String {
+        let value = |value: &Option<String>| value.clone().unwrap_or_default();
+        template
+            .replace("{username}", &value(&self.username))
+            .replace("{user}", &value(&self.user))
+            .replace("{status}", &value(&self.status))
+            .replace("{message}", message)
+    }
+}
+
+impl Messages {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds `other`'s messages, replacing the ones both have.
+    pub fn extend(&mut self, other: Messages) {
+        self.0.extend(other.0);
+    }
+
+    /// The template for `message`, by its text or with its names as placeholders.
+    fn template(&self, message: &str, placeholders: &Placeholders) -> Option<&str> {
+        self.0
+            .get(message)
+            .or_else(|| self.0.get(&placeholders.key(message)))
+            .map(String::as_str)
+    }
+
+    /// `response` with its message in the server's wording, if it has one for it.
+    pub fn apply(&self, placeholders: &Placeholders, response: Response) -> Response {
+        if self.is_empty() {
+            return response;
+        }
+
+        response.map_json(|json| {
+            let Some(message) = json.message_mut() else {
+                return;
+            };
+            if let Some(template) = self.template(message, placeholders) {
+                *message = placeholders.fill(template, message);
+            }
+        })
+    }
+}
@@ -0,0 +1,3699 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+mod accesslog;
+mod auth;
+mod authlog;
+mod avatar;
+mod ban;
+mod builder;
+mod cache;
+pub mod cli;
+pub mod config;
+mod cors;
+mod feed;
+mod forward;
+mod group;
+mod hook;
+mod i18n;
+mod invite;
+#[cfg(feature = "irc")]
+mod irc;
+mod listen;
+mod lockfile;
+mod lockout;
+mod logging;
+mod mail;
+#[cfg(feature = "matrix")]
+mod matrix;
+mod messages;
+mod moderation;
+mod openapi;
+#[cfg(feature = "otel")]
+mod otel;
+mod pages;
+mod peer;
+pub(crate) mod prelude;
+#[cfg(unix)]
+mod privilege;
+mod proxy;
+mod push;
+mod ratelimit;
+mod rfc1288;
+mod session;
+mod signature;
+mod stdio;
+pub mod store;
+mod systemd;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "tls")]
+mod tls;
+mod totp;
+#[cfg(unix)]
+mod unix;
+pub mod userlist;
+mod utmp;
+mod webfinger;
+#[cfg(feature = "webhooks")]
+mod webhook;
+#[cfg(feature = "websocket")]
+mod websocket;
+
+use crate::{
+    Fngr, Plugins,
+    networking::{
+        self, Action, Availability, Change, Format, JSONInfo, JSONProfile, JSONResponse, JSONStats,
+        JSONStatus, Method, Presence, Request, Response,
+    },
+};
+use accesslog::{AccessLog, Entry};
+use anyhow::Error;
+use auth::{Auth, AuthProvider};
+use authlog::AuthLog;
+pub use builder::{Server, ServerBuilder};
+use config::{Config, ConfigSource, RegistrationMode};
+use feed::Feed;
+use group::Groups;
+use hook::{Event, Hooks, Payload};
+use invite::Invites;
+use lockout::Lockout;
+use mail::MailCheck;
+use moderation::{Filter, Moderation};
+use peer::Remote;
+use prelude::*;
+use push::{PushEvent, Pusher};
+use ratelimit::RateLimiter;
+use session::Sessions;
+use signature::Nonces;
+use store::{Ban, Profile, Settings, Store};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, BufStream},
+    net::TcpListener,
+    sync::{Mutex, OwnedSemaphorePermit, Semaphore, broadcast, mpsc::Sender},
+    task::AbortHandle,
+    time::{Instant, sleep, timeout},
+};
+use tracing::{Instrument, Span, field, info_span};
+use userlist::UserList;
+use utmp::SystemUsers;
+use uuid::Uuid;
+use webfinger::Jrd;
+
+use crate::server::userlist::{ListQuery, Page, Status, User};
+
+// struct holds the state of the server
+struct Fingr {
+    config: Config,
+    // where the config was loaded from, for reloading it, none when it was handed to a builder
+    config_source: Option<ConfigSource>,
+    lock: Option<File>,
+    users: UserList,
+    // checks keys instead of the users' own hashes
+    auth: Option<Auth>,
+    invites: Invites,
+    groups: Groups,
+    // checks status texts and profile fields before they are saved
+    moderation: Moderation,
+    // usernames freed by deregister, with when they were freed and the owner's grace token
+    freed_names: HashMap<String, (Instant, String)>,
+    // serialized `list` body and the user list and api versions it was built for
+    list_cache: Option<(u64, u32, String)>,
+    // how often the `list` body was serialized, to tell the cache was used
+    #[cfg(test)]
+    list_serializations: std::sync::atomic::AtomicUsize,
+    sessions: Sessions,
+    rate_limiter: RateLimiter,
+    lockout: Lockout,
+    // one permit for each connection we are serving, `max_connections` in all
+    connections: Arc<Semaphore>,
+    // nonces of recent signed requests
+    nonces: Nonces,
+    access_log: Option<AccessLog>,
+    // failed key checks and refused registrations, for fail2ban
+    auth_log: Option<AuthLog>,
+    presence: broadcast::Sender<Presence>,
+    // commands run on events
+    hooks: Hooks,
+    pusher: Pusher,
+    plugins: Plugins,
+    feed: Feed,
+    // users of peer servers
+    remote: Remote,
+    mail: MailCheck,
+    started: Instant,
+}
+
+// tokens a request costs, registering and failing to authenticate cost extra
+const REQUEST_COST: f64 = 1.0;
+const REGISTER_COST: f64 = 10.0;
+const UNAUTH_COST: f64 = 5.0;
+
+// longest profile fields, in characters
+const MAX_BIO: usize = 500;
+const MAX_LINK: usize = 200;
+const MAX_SHORT: usize = 100;
+// longest friends and blocked lists
+const MAX_NAMES: usize = 500;
+// most users a batch finger can ask for
+const MAX_BATCH: usize = 100;
+
+// carries the server's `admin_key` on `/admin/*` requests
+const ADMIN_KEY_HEADER: &str = "X-Fngr-Admin-Key";
+
+// the web ui served at `/`
+const INDEX: &str = include_str!("index.html");
+
+/// The sockets a server accepts connections on.
+#[derive(Default)]
+struct Listeners {
+    http: Vec<TcpListener>,
+    finger: Vec<TcpListener>,
+    #[cfg(feature = "tls")]
+    tls: Vec<(TcpListener, tokio_rustls::TlsAcceptor)>,
+    #[cfg(unix)]
+    unix: Option<tokio::net::UnixListener>,
+}
+
+/// The server's workers and listeners, aborted when dropped so that an embedded server stops
+/// with its future.
+#[derive(Default)]
+struct Tasks(Vec<AbortHandle>);
+
+impl Tasks {
+    fn spawn<F>(&mut self, task: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.0.push(tokio::spawn(task).abort_handle());
+    }
+}
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+impl Fingr {
+    pub async fn init(config_source: ConfigSource, plugins: Plugins) -> Result<Self> {
+        let config = Config::load(&config_source).await?;
+        let store = Store::open(&config)?;
+        Self::new(config, Some(config_source), store, plugins).await
+    }
+
+    async fn new(
+        config: Config,
+        config_source: Option<ConfigSource>,
+        store: Store,
+        plugins: Plugins,
+    ) -> Result<Self> {
+        let lock = None;
+        let users = UserList::load(store, config.idle_timeout()).await?;
+        let auth = Auth::open(&config)?;
+        let invites = Invites::load(config.invites_file.clone()).await?;
+        let groups = Groups::load(config.groups_file.clone()).await?;
+        let moderation = Moderation::new(&config.moderation);
+        let sessions = Sessions::new(Duration::from_secs(config.session_ttl));
+        let rate_limiter = RateLimiter::new(config.rate_limit_burst, config.rate_limit_refill);
+        let feed_size = config.feed_size;
+        let hooks = Hooks::new(config.max_hooks);
+        let connections = Arc::new(Semaphore::new(config.max_connections));
+        let lockout = Lockout::new(
+            config.lockout_threshold,
+            Duration::from_secs(config.lockout_secs),
+        );
+
+        let access_log = match &config.access_log {
+            Some(path) => Some(AccessLog::open(
+                path,
+                config.access_log_format,
+                config.access_log_rotation,
+            )?),
+            None => None,
+        };
+        let auth_log = match &config.auth_log {
+            Some(path) => Some(AuthLog::open(path)?),
+            None => None,
+        };
+
+        Ok(Self {
+            config,
+            config_source,
+            lock,
+            users,
+            auth,
+            invites,
+            groups,
+            moderation,
+            freed_names: HashMap::new(),
+            list_cache: None,
+            #[cfg(test)]
+            list_serializations: Default::default(),
+            sessions,
+            rate_limiter,
+            lockout,
+            connections,
+            nonces: Nonces::default(),
+            access_log,
+            auth_log,
+            presence: broadcast::channel(64).0,
+            hooks,
+            pusher: Pusher::new(),
+            plugins,
+            feed: Feed::new(feed_size),
+            remote: Remote::default(),
+            mail: MailCheck::default(),
+            started: Instant::now(),
+        })
+    }
+
+    async fn offline_worker(state: Arc<Mutex<Self>>, _tx: Sender<Vec<Error>>) -> ! {
+        info!("starting offline worker");
+        loop {
+            let interval = state.lock().await.config.worker_interval_secs;
+            sleep(Duration::from_secs(interval)).await;
+            info!("checking for dead users");
+            let mut lock = state.lock().await;
+            let idle_timeout = lock.config.idle_timeout();
+            for username in lock.users.check_statuses(idle_timeout) {
+                let change = match lock.users.get(&username).map(|user| user.online()) {
+                    Some(true) => Change::Away,
+                    _ => Change::Offline,
+                };
+                info!(?username, ?change, "user automatically set away or offline");
+                lock.announce(&username, change);
+            }
+            lock.sessions.prune();
+            lock.lockout.prune();
+            lock.nonces.prune();
+            let retention = Duration::from_secs(lock.config.lookup_retention_secs);
+            lock.users.expire_logs(retention);
+            lock.save_statuses().await;
+        }
+    }
+
+    async fn cooldown_worker(state: Arc<Mutex<Self>>) -> ! {
+        loop {
+            sleep(Duration::from_secs(60)).await;
+            state.lock().await.rate_limiter.prune();
+        }
+    }
+
+    /// Re-reads the config and the users without dropping connections or statuses.
+    async fn reload(state: &Arc<Mutex<Self>>) -> Result<()> {
+        let mut lock = state.lock().await;
+        let Some(source) = &lock.config_source else {
+            return Err(anyhow!("the config wasn't loaded from a file"));
+        };
+        let config = Config::load(source).await?;
+
+        for setting in lock.config.needs_restart(&config) {
+            warn!("'{}' changed, it takes effect after a restart", setting);
+        }
+
+        lock.rate_limiter
+            .set_limits(config.rate_limit_burst, config.rate_limit_refill);
+        lock.lockout.set_limits(
+            config.lockout_threshold,
+            Duration::from_secs(config.lockout_secs),
+        );
+        lock.sessions
+            .set_ttl(Duration::from_secs(config.session_ttl));
+        lock.feed.set_max(config.feed_size);
+        lock.auth = Auth::open(&config)?;
+        lock.moderation = Moderation::new(&config.moderation);
+
+        // keep the settings that need a restart as they were
+        let old = std::mem::replace(&mut lock.config, config);
+        lock.config.socket_paths = old.socket_paths;
+        lock.config.finger_socket_paths = old.finger_socket_paths;
+        lock.config.tls = old.tls;
+        lock.config.unix_socket = old.unix_socket;
+        lock.config.users_list = old.users_list;
+        lock.config.status_file = old.status_file;
+        lock.config.store = old.store;
+        lock.config.database = old.database;
+        lock.config.invites_file = old.invites_file;
+        lock.config.groups_file = old.groups_file;
+        lock.config.lock = old.lock;
+        lock.config.max_connections = old.max_connections;
+        lock.config.access_log = old.access_log;
+        lock.config.access_log_format = old.access_log_format;
+        lock.config.access_log_rotation = old.access_log_rotation;
+        lock.config.auth_log = old.auth_log;
+        lock.config.log = old.log;
+        lock.config.matrix = old.matrix;
+        lock.config.irc = old.irc;
+
+        for username in lock.users.reload().await? {
+            lock.sessions.end(&username);
+        }
+        lock.list_cache = None;
+
+        info!("reloaded");
+        Ok(())
+    }
+
+    /// Reloads on every `SIGHUP`.
+    #[cfg(unix)]
+    async fn reload_worker(state: Arc<Mutex<Self>>) {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hup = match signal(SignalKind::hangup()) {
+            Ok(hup) => hup,
+            Err(e) => {
+                error!("can't listen for SIGHUP: {}", e);
+                return;
+            }
+        };
+
+        while hup.recv().await.is_some() {
+            info!("SIGHUP received, reloading...");
+            systemd::notify("RELOADING=1");
+            if let Err(e) = Self::reload(&state).await {
+                error!("reload failed: {}", e);
+            }
+            systemd::notify("READY=1");
+        }
+    }
+
+    pub async fn run(mut self) -> Result<()> {
+        info!("starting finger server...");
+        self.lock = Some(self.lock()?);
+        // sockets from systemd take the place of the configured addresses
+        let mut activated = systemd::Activated::take();
+        let listeners = self.bind(&mut activated).await?;
+        activated.finish();
+
+        // everything that needs root is open by now
+        #[cfg(unix)]
+        if let Some(user) = &self.config.run_as_user {
+            privilege::drop_to(user, self.config.run_as_group.as_deref())?;
+        }
+
+        #[cfg(not(unix))]
+        if self.config.run_as_user.is_some() {
+            warn!("run_as_user is not supported on this platform");
+        }
+
+        let (state, mut tasks) = self.start(listeners);
+        #[cfg(unix)]
+        tasks.spawn(Self::reload_worker(state.clone()));
+        tasks.spawn(systemd::watchdog(state.clone()));
+
+        info!("server started.");
+        systemd::notify("READY=1\nSTATUS=serving");
+        shutdown_signal().await;
+
+        info!("shutting down...");
+        systemd::notify("STOPPING=1");
+        Self::stop(&state).await
+    }
+
+    /// Binds the addresses in the config that `activated` has no sockets for.
+    async fn bind(&self, activated: &mut systemd::Activated) -> Result<Listeners> {
+        let mut http = activated.tcp("http")?;
+        if http.is_empty() {
+            http = listen::bind_all(&self.config.socket_paths, "http").await?;
+        }
+
+        let mut finger = activated.tcp("finger")?;
+        if finger.is_empty() {
+            finger = listen::bind_all(&self.config.finger_socket_paths, "finger").await?;
+        }
+
+        #[cfg(feature = "tls")]
+        let tls = match &self.config.tls {
+            Some(tls) => {
+                let acceptor = tls::acceptor(&tls.cert, &tls.key).await?;
+                let mut listeners = activated.tcp("tls")?;
+                if listeners.is_empty() {
+                    listeners = listen::bind_all(&tls.socket_paths, "tls").await?;
+                }
+                listeners
+                    .into_iter()
+                    .map(|listener| (listener, acceptor.clone()))
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        #[cfg(unix)]
+        let unix = match (activated.unix()?, &self.config.unix_socket) {
+            (Some(listener), _) => Some(listener),
+            (None, Some(path)) => {
+                let listener = unix::bind(path).await?;
+                info!("listening on '{}'", path.display());
+                Some(listener)
+            }
+            (None, None) => None,
+        };
+
+        Ok(Listeners {
+            http,
+            finger,
+            #[cfg(feature = "tls")]
+            tls,
+            #[cfg(unix)]
+            unix,
+        })
+    }
+
+    /// Starts the workers and serves `listeners`, until the tasks are dropped.
+    fn start(self, listeners: Listeners) -> (Arc<Mutex<Self>>, Tasks) {
+        #[cfg(not(unix))]
+        if self.config.unix_socket.is_some() {
+            warn!("unix sockets are not supported on this platform");
+        }
+
+        #[cfg(not(feature = "tls"))]
+        if self.config.tls.is_some() {
+            warn!("tls is configured but this server was built without the tls feature");
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        if self.config.system_users != SystemUsers::Off {
+            warn!(
+                "system users are read in the utmp layout of linux, which this platform may not use"
+            );
+        }
+
+        #[cfg(not(feature = "webhooks"))]
+        if !self.config.webhooks.is_empty() {
+            warn!("webhooks are configured but this server was built without the webhooks feature");
+        }
+
+        #[cfg(not(feature = "matrix"))]
+        if self.config.matrix.is_some() {
+            warn!("matrix is configured but this server was built without the matrix feature");
+        }
+
+        #[cfg(not(feature = "irc"))]
+        if self.config.irc.is_some() {
+            warn!("irc is configured but this server was built without the irc feature");
+        }
+
+        // make state of the server thread safe.
+        let state = Arc::new(Mutex::new(self));
+        #[cfg(feature = "otel")]
+        otel::observe(&state);
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+
+        let mut tasks = Tasks::default();
+        let ow_state = state.clone();
+        tasks.spawn(Self::offline_worker(ow_state, tx));
+        tasks.spawn(Self::cooldown_worker(state.clone()));
+        #[cfg(feature = "webhooks")]
+        tasks.spawn(webhook::worker(state.clone()));
+        tasks.spawn(push::worker(state.clone()));
+        #[cfg(feature = "matrix")]
+        tasks.spawn(matrix::bridge(state.clone()));
+        #[cfg(feature = "irc")]
+        tasks.spawn(irc::bridge(state.clone()));
+        tasks.spawn(peer::push_worker(state.clone()));
+        tasks.spawn(peer::sync_worker(state.clone()));
+
+        for listener in listeners.http {
+            tasks.spawn(Self::serve(state.clone(), listener));
+        }
+
+        for listener in listeners.finger {
+            tasks.spawn(rfc1288::serve(state.clone(), listener));
+        }
+
+        #[cfg(feature = "tls")]
+        for (listener, acceptor) in listeners.tls {
+            tasks.spawn(tls::serve(state.clone(), listener, acceptor));
+        }
+
+        #[cfg(unix)]
+        if let Some(listener) = listeners.unix {
+            tasks.spawn(unix::serve(state.clone(), listener));
+        }
+
+        (state, tasks)
+    }
+
+    /// Saves the statuses and lets go of the lock file.
+    async fn stop(state: &Arc<Mutex<Self>>) -> Result<()> {
+        let mut lock = state.lock().await;
+        lock.save_statuses().await;
+        if let Some(file) = lock.lock.take() {
+            lockfile::release(file, &lock.config.lock)?;
+        }
+        Ok(())
+    }
+
+    /// Accepts http connections on `listener`, one of possibly several.
+    async fn serve(state: Arc<Mutex<Self>>, listener: TcpListener) {
+        let connections = state.lock().await.connections.clone();
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    let Ok(permit) = connections.clone().try_acquire_owned() else {
+                        warn!(?addr, "too many connections, dropping it");
+                        continue;
+                    };
+                    info!(?addr, "connection...");
+                    tokio::spawn(Self::serve_connection(
+                        state.clone(),
+                        stream,
+                        Some(addr.ip()),
+                        permit,
+                    ));
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    continue;
+                }
+            }
+        }
+    }
+
+    async fn save_statuses(&mut self) {
+        if let Err(e) = self.users.save_statuses().await {
+            error!("failed to save statuses: {}", e);
+        }
+    }
+
+    /// Answers requests on one connection until the client closes it or asks us to, or is too
+    /// slow. The connection's `permit` is given back when it closes.
+    async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(
+        state: Arc<Mutex<Self>>,
+        stream: S,
+        peer: Option<IpAddr>,
+        permit: OwnedSemaphorePermit,
+    ) {
+        let _permit = permit;
+        let mut stream = BufStream::new(stream);
+        let lock = state.lock().await;
+        let (read_timeout, write_timeout, limits, base_path, trusted_proxies, cors) = (
+            lock.config.read_timeout(),
+            lock.config.write_timeout(),
+            lock.config.limits,
+            lock.config.base_path.clone(),
+            lock.config.trusted_proxies.clone(),
+            lock.config.cors.clone(),
+        );
+        let (messages, language, translations, pages, public_url) = (
+            lock.config.messages.clone(),
+            lock.config.language.clone(),
+            lock.config.translations.clone(),
+            lock.config.pages.clone(),
+            lock.config.public_url.clone(),
+        );
+        drop(lock);
+
+        loop {
+            let mut id = request_id();
+            let span = info_span!(
+                "request",
+                // recorded once it is known whether a proxy sent one
+                id = field::Empty,
+                ?peer,
+                client = field::Empty,
+                action = field::Empty,
+                status = field::Empty,
+                latency_ms = field::Empty
+            );
+            let next = async {
+                let parsed = timeout(
+                    read_timeout,
+                    Request::parse(&mut stream, &limits, &base_path),
+                )
+                .await;
+                let mut request = match parsed {
+                    Ok(Ok(Some(request))) => request,
+                    Ok(Ok(None)) => return Next::Close,
+                    Ok(Err(e)) => {
+                        Span::current().record("id", &id);
+                        error!("{}", e);
+                        let response = Response::error(&e);
+                        Self::refuse(&state, &mut stream, peer, write_timeout, &id, response).await;
+                        return Next::Close;
+                    }
+                    Err(_) => {
+                        Span::current().record("id", &id);
+                        info!("timed out waiting for a request");
+                        let response = Response::from(
+                            networking::ResponseStatus::RequestTimeout,
+                            JSONResponse::Error("took too long to send a request".to_owned()),
+                        );
+                        Self::refuse(&state, &mut stream, peer, write_timeout, &id, response).await;
+                        return Next::Close;
+                    }
+                };
+
+                let started = Instant::now();
+                // the client, which is not the peer behind a trusted proxy
+                let client = peer.map(|ip| proxy::client(ip, &request.headers, &trusted_proxies));
+                request.peer = client;
+                // a trusted proxy's id ties our logs to its own
+                if client != peer
+                    && let Some(theirs) = request.headers.get("x-request-id")
+                    && is_request_id(theirs)
+                {
+                    id = theirs.to_owned();
+                }
+                let span = Span::current();
+                span.record("id", &id);
+                span.record("client", field::debug(client));
+                span.record("action", field::display(&request.action));
+                let keep_alive = request.keep_alive;
+                let head = request.method == Method::Head;
+                let format = Format::negotiate(&request);
+                let origin = request.headers.get("origin").cloned();
+                let conditional = cache::Conditional::from_request(&request);
+                let api_version = request.api_version;
+                let placeholders = messages::Placeholders::from_request(&request);
+                // json is for programs, which get english
+                let language = (format != Format::Json).then(|| {
+                    translations.negotiate(
+                        request.headers.get("accept-language").map(String::as_str),
+                        &language,
+                    )
+                });
+                let mut entry = Entry {
+                    peer: client,
+                    method: Some(request.method.to_string()),
+                    action: Some(request.action.to_string()),
+                    username: request.username.clone(),
+                    id: id.clone(),
+                    status: 0,
+                    bytes: 0,
+                    latency: Duration::ZERO,
+                };
+
+                let cost = match request.action {
+                    Action::Register => REGISTER_COST,
+                    _ => REQUEST_COST,
+                };
+                let limited = match client {
+                    Some(ip) => state.lock().await.rate_limiter.take(ip, cost).err(),
+                    None => None,
+                };
+
+                let mut response = match limited {
+                    Some(wait) => {
+                        warn!(?client, "rate limited");
+                        Response::from(
+                            networking::ResponseStatus::TooManyRequests,
+                            JSONResponse::Error("too many requests, slow down".to_owned()),
+                        )
+                        .header("Retry-After", wait)
+                    }
+                    #[cfg(feature = "websocket")]
+                    None if websocket::is_upgrade(&request) => {
+                        match websocket::accept(&state, &request).await {
+                            Ok(Ok(username)) => {
+                                entry.status = 101;
+                                entry.latency = started.elapsed();
+                                Self::log_access(&state, entry).await;
+                                return Next::Upgrade(Box::new(request), username);
+                            }
+                            Ok(Err(response)) => response,
+                            Err(e) => {
+                                error!("{}", e);
+                                Response::from(
+                                    networking::ResponseStatus::ServerError,
+                                    JSONResponse::Error(e.to_string()),
+                                )
+                            }
+                        }
+                    }
+                    None if request.method == Method::Options => {
+                        cors::preflight(cors.as_ref(), &request)
+                    }
+                    None => match Self::run_request(state.clone(), request).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            error!("{}", e);
+                            Response::from(e.status(), JSONResponse::Error(e.to_string()))
+                        }
+                    },
+                };
+
+                if response.status() == networking::ResponseStatus::Unauth
+                    && let Some(ip) = client
+                {
+                    state.lock().await.rate_limiter.charge(ip, UNAUTH_COST);
+                }
+
+                response = match language.as_deref() {
+                    Some(language) => match translations.get(language) {
+                        Some(translated) => translated.apply(&placeholders, response),
+                        None => messages.apply(&placeholders, response),
+                    }
+                    .header("Content-Language", language),
+                    None => messages.apply(&placeholders, response),
+                };
+                response = conditional.apply(response);
+                response = cors::allow(cors.as_ref(), origin.as_deref(), response);
+                response = response
+                    .version(api_version)
+                    .negotiate(format)
+                    .header(networking::VERSION_HEADER, api_version)
+                    .header("X-Request-Id", &id);
+                if format == Format::Html {
+                    response = pages::render(&pages, &public_url, response).await;
+                }
+                if !keep_alive {
+                    response = response.header("Connection", "close");
+                }
+
+                entry.status = response.status().code();
+                entry.bytes = if head { 0 } else { response.body_len() };
+                entry.latency = started.elapsed();
+                span.record("status", entry.status);
+                span.record(
+                    "latency_ms",
+                    field::display(format!("{:.3}", entry.latency.as_secs_f64() * 1000.0)),
+                );
+                debug!("answered");
+                Self::log_access(&state, entry).await;
+
+                let r = if head {
+                    timeout(write_timeout, response.write_head(&mut stream)).await
+                } else {
+                    timeout(write_timeout, response.write(&mut stream)).await
+                };
+
+                let r = r.unwrap_or_else(|_| {
+                    Err(FngrError::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "timed out writing a response",
+                    )))
+                });
+                if let Err(e) = r {
+                    error!("{}", e);
+                    return Next::Close;
+                }
+
+                if keep_alive {
+                    Next::Continue
+                } else {
+                    Next::Close
+                }
+            }
+            .instrument(span.clone())
+            .await;
+
+            match next {
+                Next::Continue => {}
+                Next::Close => break,
+                #[cfg(feature = "websocket")]
+                Next::Upgrade(request, username) => {
+                    let served = websocket::serve(state, stream, *request, username)
+                        .instrument(span)
+                        .await;
+                    if let Err(e) = served {
+                        error!("websocket: {}", e);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Answers a request that couldn't be read with `response` and has the connection closed.
+    async fn refuse<S: AsyncWrite + Unpin>(
+        state: &Arc<Mutex<Self>>,
+        stream: &mut S,
+        peer: Option<IpAddr>,
+        write_timeout: Duration,
+        id: &str,
+        response: Response,
+    ) {
+        let response = response
+            .header("Connection", "close")
+            .header(networking::VERSION_HEADER, networking::OLDEST_API_VERSION)
+            .header("X-Request-Id", id);
+
+        Self::log_access(
+            state,
+            Entry {
+                peer,
+                method: None,
+                action: None,
+                username: None,
+                id: id.to_owned(),
+                status: response.status().code(),
+                bytes: response.body_len(),
+                latency: Duration::ZERO,
+            },
+        )
+        .await;
+
+        match timeout(write_timeout, response.write(stream)).await {
+            Ok(Err(e)) => error!("{}", e),
+            Err(_) => error!("timed out writing a response"),
+            Ok(Ok(())) => (),
+        }
+    }
+
+    /// Tells presence subscribers and the feed about the current status of `username`.
+    fn announce(&mut self, username: &str, change: Change) {
+        if let Some(user) = self.users.get(username) {
+            let presence = Presence {
+                change,
+                username: username.to_owned(),
+                status: user.status().clone().into(),
+            };
+            self.feed.record(&presence);
+            self.hooks.fire(
+                &self.config,
+                &Payload {
+                    status: Some(&presence.status),
+                    ..Payload::new(change.into(), username)
+                },
+            );
+            self.plugins.on_presence_change(&presence);
+            if change == Change::Login {
+                self.push_followers(username);
+            }
+            // nobody listening is fine
+            let _ = self.presence.send(presence);
+        }
+    }
+
+    /// Pushes to the users following `username` that they logged on, if `username` lets them
+    /// see it.
+    fn push_followers(&self, username: &str) {
+        if !self.config.push_notifications {
+            return;
+        }
+        let Some(user) = self.users.get(username) else {
+            return;
+        };
+
+        let message = match &user.status().text {
+            Some(text) => format!("{} is online: {}", username, text),
+            None => format!("{} is online", username),
+        };
+        for follower in self.users.values() {
+            let settings = follower.settings();
+            if settings.following.iter().any(|name| name == username)
+                && user.listed_for(Some(follower.username()))
+            {
+                self.pusher.push(
+                    settings,
+                    &self.config.ntfy_server,
+                    PushEvent::Online,
+                    &message,
+                );
+            }
+        }
+    }
+
+    /// Counts a finger of `username` by `by`, `None` for anonymous, and tells the hooks and the
+    /// user about it.
+    fn fingered(&mut self, username: &str, by: Option<&str>) {
+        self.users.count_finger(username);
+        self.hooks.fire(
+            &self.config,
+            &Payload {
+                by,
+                ..Payload::new(Event::Finger, username)
+            },
+        );
+
+        if self.config.push_notifications
+            && let Some(user) = self.users.get(username)
+        {
+            let message = format!("{} fingered you", by.unwrap_or("someone"));
+            self.pusher.push(
+                user.settings(),
+                &self.config.ntfy_server,
+                PushEvent::Finger,
+                &message,
+            );
+        }
+    }
+
+    /// Applies the `ntfy`, `gotify` and `push` of `req` to `settings`. Setting a target without
+    /// `push` pushes when someone the user follows logs on.
+    fn push_settings(
+        config: &Config,
+        req: &Request,
+        settings: &mut Settings,
+    ) -> std::result::Result<(), String> {
+        let ntfy = req.ntfy.as_deref().map(str::trim);
+        let gotify = req.gotify.as_deref().map(str::trim);
+        let targets = ntfy.is_some_and(|t| !t.is_empty()) || gotify.is_some_and(|t| !t.is_empty());
+        let events = req.push.as_deref().is_some_and(|p| !p.trim().is_empty());
+        // clearing them always works, for when the server stops sending them
+        if (targets || events) && !(cfg!(feature = "webhooks") && config.push_notifications) {
+            return Err("this server doesn't send push notifications".to_owned());
+        }
+
+        if let Some(topic) = ntfy {
+            if !topic.is_empty() && (!push::is_topic(topic) || topic.len() > MAX_LINK) {
+                return Err(
+                    "ntfy must be a topic of letters, digits, - and _, or an http or https url"
+                        .to_owned(),
+                );
+            }
+            settings.ntfy = (!topic.is_empty()).then(|| topic.to_owned());
+        }
+
+        if let Some(url) = gotify {
+            if !url.is_empty() && (!push::is_url(url) || url.len() > MAX_LINK) {
+                return Err("gotify must be an http or https url".to_owned());
+            }
+            settings.gotify = (!url.is_empty()).then(|| url.to_owned());
+        }
+
+        match &req.push {
+            Some(events) => {
+                let mut push = vec![];
+                for event in events.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                    let event = event.parse::<PushEvent>()?;
+                    if !push.contains(&event) {
+                        push.push(event);
+                    }
+                }
+                settings.push = push;
+            }
+            None if targets && settings.push.is_empty() => settings.push = vec![PushEvent::Online],
+            None => {}
+        }
+        Ok(())
+    }
+
+    async fn log_access(state: &Arc<Mutex<Self>>, entry: Entry) {
+        #[cfg(feature = "otel")]
+        otel::record(&entry);
+        let lock = state.lock().await;
+        if let Some(log) = &lock.access_log {
+            log.record(&entry);
+        }
+        if let Some(log) = &lock.auth_log {
+            log.record(&entry);
+        }
+    }
+
+    async fn change_online_status(
+        state: Arc<Mutex<Self>>,
+        req: Request,
+        username: String,
+        presence: Availability,
+    ) -> Result<Response, FngrError> {
+        if let Some(text) = req.status.as_deref()
+            && let Some(res) = Self::moderate(&state, &username, "status", text).await?
+        {
+            return Ok(res);
+        }
+
+        let mut lock = state.lock().await;
+
+        let status = presence.is_online();
+        let idle_timeout = lock.config.idle_timeout();
+        let max_history = lock.config.max_status_history;
+        let already_online = if let Some(user) = lock.users.get_mut(&username) {
+            let already_online = user.online();
+            let now = Instant::now();
+            user.set_status(
+                Status {
+                    presence,
+                    text: req.status.or(user.status().text.to_owned()),
+                    since: now,
+                    expires: status.then(|| now + user.idle_timeout(idle_timeout)),
+                    last_seen: Some(SystemTime::now()),
+                },
+                max_history,
+            );
+            lock.users.touch();
+            already_online
+        } else {
+            return Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                networking::JSONResponse::Error("user not found".to_owned()),
+            ));
+        };
+
+        if let Err(e) = lock.users.save_status(&username).await {
+            error!("failed to save status of '{}': {}", username, e);
+        }
+        lock.announce(
+            &username,
+            if status {
+                Change::Login
+            } else {
+                Change::Logoff
+            },
+        );
+
+        let online = lock.users.online_count();
+        if status {
+            let session = lock.sessions.create(&username);
+            Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                networking::JSONResponse::Login {
+                    message: "you are now logged on".to_owned(),
+                    already_online,
+                    session,
+                },
+            )
+            .header("X-Online-Count", online))
+        } else {
+            lock.sessions.end(&username);
+            Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                networking::JSONResponse::OK("you are now logged off".to_owned()),
+            )
+            .header("X-Online-Count", online))
+        }
+    }
+
+    /// Identifies the user from an `Authorization: Bearer` session token, or their username and key.
+    async fn authenticate(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+    ) -> Result<std::result::Result<String, Response>, FngrError> {
+        let token = req
+            .headers
+            .get("authorization")
+            .and_then(|auth| auth.strip_prefix("Bearer "));
+
+        if let Some(token) = token {
+            let lock = state.lock().await;
+            return match lock.sessions.get(token.trim()) {
+                Some(username) => match lock.refuse_banned(Some(username), req.peer) {
+                    Some(res) => Ok(Err(lock.log_refusal(authlog::Kind::Banned, req, res))),
+                    None => Ok(Ok(username.to_owned())),
+                },
+                None => Ok(Err(lock.log_refusal(
+                    authlog::Kind::Failure,
+                    req,
+                    Response::from(
+                        networking::ResponseStatus::Unauth,
+                        JSONResponse::Error("invalid or expired session".to_owned()),
+                    ),
+                ))),
+            };
+        }
+
+        Self::check_key(state, req).await
+    }
+
+    /// What a request gets when its user is gone by the time it is answered, like when they
+    /// deregistered while it was on its way.
+    fn unknown_user(username: &str) -> Response {
+        Response::error(&FngrError::UserNotFound(username.to_owned()))
+    }
+
+    async fn check_key(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+    ) -> Result<std::result::Result<String, Response>, FngrError> {
+        if req
+            .headers
+            .contains_key(&signature::SIGNATURE_HEADER.to_ascii_lowercase())
+        {
+            return Self::check_signature(state, req).await;
+        }
+
+        if let Some(username) = &req.username {
+            if let Some(key) = &req.key {
+                let mut lock = state.lock().await;
+                if let Some(res) = lock.refuse_banned(None, req.peer) {
+                    return Ok(Err(lock.log_refusal(authlog::Kind::Banned, req, res)));
+                }
+                if let Some(wait) = lock.lockout.locked(username, req.peer) {
+                    return Ok(Err(lock.log_refusal(
+                        authlog::Kind::Locked,
+                        req,
+                        Response::from(
+                            networking::ResponseStatus::Locked,
+                            JSONResponse::Error(
+                                "too many failed attempts, try again later".to_owned(),
+                            ),
+                        )
+                        .header("Retry-After", wait),
+                    )));
+                }
+
+                let verified = match lock.auth.clone() {
+                    Some(auth) => {
+                        // the directory may take a while, don't hold up everyone else
+                        drop(lock);
+                        let verified = auth.verify(username, key).await;
+                        lock = state.lock().await;
+                        match verified {
+                            Ok(verified) => verified,
+                            Err(e) => {
+                                error!(?username, "can't check key with the directory: {}", e);
+                                return Ok(Err(Response::from(
+                                    networking::ResponseStatus::BadGateway,
+                                    JSONResponse::Error(
+                                        "can't check the key right now, try again later".to_owned(),
+                                    ),
+                                )));
+                            }
+                        }
+                    }
+                    None => match lock.users.get(username) {
+                        Some(user) => key.parse().is_ok_and(|key| user.compare_key(key)),
+                        // answered like a wrong key, so usernames can't be probed for
+                        None => {
+                            warn!(?username, peer = ?req.peer, "key check for an unknown user");
+                            lock.lockout.fail_peer(req.peer);
+                            return Ok(Err(lock.log_refusal(
+                                authlog::Kind::Failure,
+                                req,
+                                Response::error(&FngrError::AuthFailed),
+                            )));
+                        }
+                    },
+                };
+
+                if verified {
+                    lock.lockout.succeed(username);
+                    // users of a directory are added the first time they show up
+                    if !lock.users.contains_key(username) {
+                        if ban::is_banned_name(&lock.config.banned_names, username) {
+                            return Ok(Err(Response::from(
+                                networking::ResponseStatus::Forbidden,
+                                JSONResponse::Error("that username is not allowed".to_owned()),
+                            )));
+                        }
+                        lock.users.register(username.to_owned(), false).await?;
+                        info!(?username, "added user from the directory");
+                    }
+                    match lock.refuse_inactive(username, req) {
+                        Some(res) => Ok(Err(res)),
+                        None => Ok(Ok(username.to_owned())),
+                    }
+                } else {
+                    warn!(?username, peer = ?req.peer, "failed key check");
+                    lock.lockout.fail(username, req.peer);
+                    Ok(Err(lock.log_refusal(
+                        authlog::Kind::Failure,
+                        req,
+                        Response::error(&FngrError::AuthFailed),
+                    )))
+                }
+            } else {
+                Ok(Err(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("missing key".to_owned()),
+                )))
+            }
+        } else {
+            Ok(Err(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("missing username".to_owned()),
+            )))
+        }
+    }
+
+    /// Changes the profile field `name` of the authenticated user to the `value` from the request,
+    /// an empty value removes it.
+    async fn set_profile_field(
+        state: Arc<Mutex<Self>>,
+        req: &Request,
+        name: &str,
+        value: Option<&str>,
+        max_len: usize,
+        field: fn(&mut Profile) -> &mut Option<String>,
+    ) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let Some(value) = value.map(str::trim) else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!("a {} is required", name)),
+            ));
+        };
+
+        if value.chars().count() > max_len {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!("{} must be at most {} characters", name, max_len)),
+            ));
+        }
+
+        // it ends up in plain text finger output
+        if value.chars().any(char::is_control) {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!("{} must be a single line", name)),
+            ));
+        }
+
+        if let Some(res) = Self::moderate(&state, &username, name, value).await? {
+            return Ok(res);
+        }
+
+        let mut lock = state.lock().await;
+        let Some(user) = lock.users.get(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+        let mut profile = user.profile().clone();
+        *field(&mut profile) = (!value.is_empty()).then(|| value.to_owned());
+        lock.users.set_profile(&username, profile).await?;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("your {} is saved", name)),
+        ))
+    }
+
+    /// Refuses `text` as `username`'s `field` if moderation says so. Clearing a field is always
+    /// fine.
+    async fn moderate(
+        state: &Arc<Mutex<Self>>,
+        username: &str,
+        field: &str,
+        text: &str,
+    ) -> Result<Option<Response>, FngrError> {
+        if text.is_empty() {
+            return Ok(None);
+        }
+
+        // the command can take a while, so it doesn't run under the lock
+        let moderation = state.lock().await.moderation.clone();
+        Ok(moderation
+            .check(username, field, text)
+            .await
+            .map_err(server_error)?
+            .map(|reason| {
+                info!(?username, ?field, ?reason, "refused by moderation");
+                Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(format!("your {} was refused: {}", field, reason)),
+                )
+            }))
+    }
+
+    /// Adds the users in `user`, comma separated, to the ones the user follows, or removes them.
+    async fn change_following(
+        state: Arc<Mutex<Self>>,
+        req: Request,
+        follow: bool,
+    ) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let names: Vec<&str> = req
+            .finger_user
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        if names.is_empty() {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a user is required".to_owned()),
+            ));
+        }
+
+        let mut lock = state.lock().await;
+        let Some(user) = lock.users.get(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+        let mut settings = user.settings().clone();
+        if follow {
+            for name in &names {
+                if *name == username {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::Bad,
+                        JSONResponse::Error("you can't follow yourself".to_owned()),
+                    ));
+                }
+                // blocked users are told the user doesn't exist, like when they finger them
+                if lock
+                    .users
+                    .get(*name)
+                    .is_none_or(|user| user.blocks(Some(&username)))
+                {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::NotFound,
+                        JSONResponse::Error(format!("user not found: {}", name)),
+                    ));
+                }
+                if !settings.following.iter().any(|followed| followed == name) {
+                    settings.following.push(name.to_string());
+                }
+            }
+            if settings.following.len() > MAX_NAMES {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(format!("you can follow at most {} users", MAX_NAMES)),
+                ));
+            }
+        } else {
+            settings
+                .following
+                .retain(|followed| !names.contains(&followed.as_str()));
+        }
+        lock.users.set_settings(&username, settings).await?;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(if follow {
+                format!("you now follow {}", names.join(", "))
+            } else {
+                format!("you no longer follow {}", names.join(", "))
+            }),
+        ))
+    }
+
+    /// Answers `finger?group=...` with the members of `group` the viewer may see.
+    async fn finger_group(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+        group: &str,
+    ) -> Result<Response, FngrError> {
+        let viewer = match Self::authenticate(state, req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+        let viewer = viewer.as_deref();
+
+        let lock = state.lock().await;
+        let Some(found) = lock.groups.get(group) else {
+            return Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("group not found".to_owned()),
+            ));
+        };
+
+        let members: Vec<&User> = found
+            .members
+            .iter()
+            .filter_map(|member| lock.users.get(member))
+            .filter(|user| user.listed_for(viewer))
+            .collect();
+        let online = members.iter().filter(|user| user.online()).count();
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::Group {
+                name: group.to_owned(),
+                online,
+                members: members.into_iter().map(Into::into).collect(),
+            },
+        ))
+    }
+
+    /// The group named by `req`, or what is wrong with it.
+    fn group_name(req: &Request) -> std::result::Result<String, String> {
+        match req.group.as_deref().map(str::trim) {
+            Some(group) if group::is_valid_name(group) => Ok(group.to_owned()),
+            Some(_) => Err(format!(
+                "group names are up to {} letters, digits, - and _",
+                group::MAX_NAME
+            )),
+            None => Err("a group is required".to_owned()),
+        }
+    }
+
+    /// Fingers `user` for `finger`, on its own or as part of a batch.
+    async fn finger_one(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+        user: &str,
+        viewer: Option<&str>,
+    ) -> Result<Response, FngrError> {
+        let mut user = user;
+        if let Some((query, host)) = forward::split(user) {
+            let lock = state.lock().await;
+            if forward::is_local(host, &lock.config.public_url) {
+                user = query;
+            } else {
+                let forwarding = lock.config.finger_forwarding;
+                let max_hops = lock.config.max_finger_hops;
+                drop(lock);
+                return Ok(forward::relay(req, query, host, forwarding, max_hops).await);
+            }
+        }
+
+        if let Some(response) = Self::finger_system_user(state, user).await? {
+            return Ok(response);
+        }
+
+        let mut lock = state.lock().await;
+        // `None` for users who keep their lookups private
+        let from_user = match viewer {
+            Some(viewer) => {
+                let private = lock
+                    .users
+                    .get(viewer)
+                    .is_some_and(|user| user.settings().private);
+                (!private).then(|| viewer.to_owned())
+            }
+            None => Some("anonymous".to_owned()),
+        };
+        let ip = req.peer.filter(|_| lock.config.log_lookup_ips);
+        let max = lock.config.max_lookups;
+        // whether the user fingered the viewer back lately
+        let fingered_you = viewer
+            .filter(|viewer| *viewer != user)
+            .and_then(|viewer| lock.users.get(viewer))
+            .and_then(|viewer| viewer.fingered_by(user));
+        if let Some(found) = lock.users.get_mut(user) {
+            // blocked users are told the user doesn't exist
+            if found.blocks(viewer) {
+                return Ok(Response::from(
+                    networking::ResponseStatus::NotFound,
+                    JSONResponse::Error("user not found".to_owned()),
+                ));
+            }
+            if !found.allows(viewer) {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Forbidden,
+                    JSONResponse::Error(format!("only friends of {} can finger them", user)),
+                ));
+            }
+            if let Some(from_user) = from_user {
+                found.add_log(&from_user, ip, max);
+            }
+            let history =
+                matches!(req.history.as_deref(), Some("1" | "true")).then(|| found.history());
+            let follows_you = viewer.is_some_and(|viewer| {
+                found
+                    .settings()
+                    .following
+                    .iter()
+                    .any(|followed| followed == viewer)
+            });
+            let mut found: JSONResponse = found.into();
+            // mail and whom the user fingered come and go without the version changing
+            let mut cacheable = viewer.is_none();
+            if let JSONResponse::User {
+                profile: Some(profile),
+                ..
+            } = &mut found
+            {
+                profile.mail = lock.mail(user);
+                cacheable &= profile.mail.is_none();
+                profile.history = history;
+                profile.follows_you = follows_you;
+                profile.fingered_you = fingered_you;
+            }
+            if viewer != Some(user) {
+                lock.fingered(user, viewer);
+            }
+            let response = Response::from(networking::ResponseStatus::Ok, found);
+            Ok(if cacheable {
+                lock.validate(response, req, None)
+            } else {
+                response
+            })
+        } else {
+            Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("user not found".to_owned()),
+            ))
+        }
+    }
+
+    /// Answers a finger of a system user, `None` if it is one for the registered users.
+    async fn finger_system_user(
+        state: &Arc<Mutex<Self>>,
+        user: &str,
+    ) -> Result<Option<Response>, FngrError> {
+        let lock = state.lock().await;
+        let (utmp, wtmp) = match lock.config.system_users {
+            SystemUsers::Off => return Ok(None),
+            SystemUsers::Merge if lock.users.contains_key(user) => return Ok(None),
+            _ => (lock.config.utmp.clone(), lock.config.wtmp.clone()),
+        };
+        drop(lock);
+
+        Ok(Some(
+            match utmp::user(&utmp, &wtmp, user).await.map_err(server_error)? {
+                Some(JSONResponse::User {
+                    username, status, ..
+                }) => {
+                    let mail = state.lock().await.mail(&username);
+                    let profile = mail.map(|mail| {
+                        Box::new(JSONProfile {
+                            mail: Some(mail),
+                            ..Default::default()
+                        })
+                    });
+                    Response::from(
+                        networking::ResponseStatus::Ok,
+                        JSONResponse::User {
+                            username,
+                            status,
+                            profile,
+                        },
+                    )
+                }
+                Some(found) => Response::from(networking::ResponseStatus::Ok, found),
+                None => Response::from(
+                    networking::ResponseStatus::NotFound,
+                    JSONResponse::Error("user not found".to_owned()),
+                ),
+            },
+        ))
+    }
+
+    /// Identifies the user from an ed25519 signature over the request, made with their `pubkey`.
+    async fn check_signature(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+    ) -> Result<std::result::Result<String, Response>, FngrError> {
+        let header = |name: &str| {
+            req.headers
+                .get(&name.to_ascii_lowercase())
+                .map(|value| value.trim())
+                .unwrap_or_default()
+        };
+        let unauth = |lock: &Self, message: &str| {
+            lock.log_refusal(
+                authlog::Kind::Failure,
+                req,
+                Response::from(
+                    networking::ResponseStatus::Unauth,
+                    JSONResponse::Error(message.to_owned()),
+                ),
+            )
+        };
+
+        let mut lock = state.lock().await;
+        let Some(username) = &req.username else {
+            return Ok(Err(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("missing username".to_owned()),
+            )));
+        };
+        if let Some(res) = lock.refuse_banned(None, req.peer) {
+            return Ok(Err(lock.log_refusal(authlog::Kind::Banned, req, res)));
+        }
+        if let Some(wait) = lock.lockout.locked(username, req.peer) {
+            return Ok(Err(lock.log_refusal(
+                authlog::Kind::Locked,
+                req,
+                Response::from(
+                    networking::ResponseStatus::Locked,
+                    JSONResponse::Error("too many failed attempts, try again later".to_owned()),
+                )
+                .header("Retry-After", wait),
+            )));
+        }
+        let Some(pubkey) = lock
+            .users
+            .get(username)
+            .and_then(|user| user.pubkey())
+            .map(str::to_owned)
+        else {
+            return Ok(Err(unauth(&lock, "no public key is set for this user")));
+        };
+
+        let (timestamp, nonce) = (
+            header(signature::TIMESTAMP_HEADER),
+            header(signature::NONCE_HEADER),
+        );
+        let message = signature::message(&req.action.to_string(), username, timestamp, nonce);
+        if !signature::verify(&pubkey, &message, header(signature::SIGNATURE_HEADER)) {
+            warn!(?username, peer = ?req.peer, "failed signature check");
+            lock.lockout.fail(username, req.peer);
+            return Ok(Err(unauth(&lock, "invalid signature")));
+        }
+        if !signature::is_fresh(timestamp) {
+            return Ok(Err(unauth(
+                &lock,
+                "the signature's timestamp is too far off",
+            )));
+        }
+        if !lock.nonces.insert(username, nonce) {
+            warn!(?username, peer = ?req.peer, "replayed signature");
+            return Ok(Err(unauth(&lock, "that nonce was used already")));
+        }
+
+        lock.lockout.succeed(username);
+        Ok(match lock.refuse_inactive(username, req) {
+            Some(res) => Err(res),
+            None => Ok(username.to_owned()),
+        })
+    }
+
+    /// Refuses users who are banned or not approved yet, once they proved who they are.
+    fn refuse_inactive(&self, username: &str, req: &Request) -> Option<Response> {
+        if let Some(res) = self.refuse_banned(Some(username), req.peer) {
+            return Some(self.log_refusal(authlog::Kind::Banned, req, res));
+        }
+        if self.users.get(username).is_some_and(|user| user.pending()) {
+            return Some(Response::from(
+                networking::ResponseStatus::Forbidden,
+                JSONResponse::Error("your registration is waiting for approval".to_owned()),
+            ));
+        }
+        None
+    }
+
+    /// Refuses users with a second factor unless the request has a fresh `code` of it.
+    fn refuse_without_code(&mut self, username: &str, req: &Request) -> Option<Response> {
+        let user = self.users.get_mut(username)?;
+        if !user.has_totp() {
+            return None;
+        }
+
+        let Some(code) = &req.code else {
+            return Some(Response::from(
+                networking::ResponseStatus::Unauth,
+                JSONResponse::Error("a code from your authenticator is required".to_owned()),
+            ));
+        };
+        if user.check_totp(code) {
+            return None;
+        }
+
+        warn!(?username, peer = ?req.peer, "failed totp check");
+        self.lockout.fail(username, req.peer);
+        Some(self.log_refusal(
+            authlog::Kind::Failure,
+            req,
+            Response::from(
+                networking::ResponseStatus::Unauth,
+                JSONResponse::Error("invalid code".to_owned()),
+            ),
+        ))
+    }
+
+    /// Writes `req`, refused with `res`, to the auth log if there is one and answers with `res`.
+    fn log_refusal(&self, kind: authlog::Kind, req: &Request, res: Response) -> Response {
+        if let Some(log) = &self.auth_log {
+            log.refused(kind, req, res.status().code());
+        }
+        res
+    }
+
+    /// Refuses to change keys that come from a directory.
+    fn refuse_external_keys(&self) -> Option<Response> {
+        self.auth.as_ref().map(|_| {
+            Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("keys come from a directory on this server".to_owned()),
+            )
+        })
+    }
+
+    /// The response refusing a banned address or user, `None` if neither is banned.
+    fn refuse_banned(&self, username: Option<&str>, peer: Option<IpAddr>) -> Option<Response> {
+        if ban::is_banned_ip(&self.config.banned_ips, peer) {
+            return Some(Response::from(
+                networking::ResponseStatus::Forbidden,
+                JSONResponse::Error("this address is banned from this server".to_owned()),
+            ));
+        }
+
+        let ban = self.users.get(username?)?.ban()?;
+        let mut message = match ban
+            .until
+            .and_then(|until| chrono::DateTime::from_timestamp(until as i64, 0))
+        {
+            Some(until) => format!(
+                "you are suspended until {}",
+                until.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            ),
+            None => "you are banned from this server".to_owned(),
+        };
+        if let Some(reason) = &ban.reason {
+            message.push_str(&format!(": {}", reason));
+        }
+
+        Some(Response::from(
+            networking::ResponseStatus::Forbidden,
+            JSONResponse::Error(message),
+        ))
+    }
+
+    /// Sets `username` offline if they are online and ends their sessions.
+    async fn force_logoff(&mut self, username: &str) {
+        if let Some(user) = self.users.get_mut(username)
+            && user.online()
+        {
+            // the text stays the same, so nothing goes into the history
+            user.set_status(
+                Status {
+                    presence: Availability::Offline,
+                    text: user.status().text.to_owned(),
+                    since: Instant::now(),
+                    expires: None,
+                    last_seen: Some(SystemTime::now()),
+                },
+                0,
+            );
+            self.users.touch();
+
+            if let Err(e) = self.users.save_status(username).await {
+                error!("failed to save status of '{}': {}", username, e);
+            }
+            self.announce(username, Change::Logoff);
+        }
+        self.sessions.end(username);
+    }
+
+    /// The user an `/admin/*` request is about, if it carries the server's `admin_key`.
+    async fn admin_user(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+    ) -> Result<std::result::Result<String, Response>, FngrError> {
+        if let Err(res) = Self::check_admin(state, req).await? {
+            return Ok(Err(res));
+        }
+
+        let lock = state.lock().await;
+        Ok(match &req.finger_user {
+            Some(username) if lock.users.contains_key(username) => Ok(username.to_owned()),
+            Some(_) => Err(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("user not found".to_owned()),
+            )),
+            None => Err(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a user is required".to_owned()),
+            )),
+        })
+    }
+
+    async fn check_admin(
+        state: &Arc<Mutex<Self>>,
+        req: &Request,
+    ) -> Result<std::result::Result<(), Response>, FngrError> {
+        let key = req
+            .headers
+            .get(&ADMIN_KEY_HEADER.to_ascii_lowercase())
+            .map(|key| key.trim());
+
+        let lock = state.lock().await;
+        Ok(match &lock.config.admin_key {
+            Some(admin_key) if key == Some(admin_key.as_str()) => Ok(()),
+            Some(_) => {
+                warn!(peer = ?req.peer, "failed admin key check");
+                Err(lock.log_refusal(
+                    authlog::Kind::Failure,
+                    req,
+                    Response::from(
+                        networking::ResponseStatus::Unauth,
+                        JSONResponse::Error("invalid admin key".to_owned()),
+                    ),
+                ))
+            }
+            None => Err(Response::from(
+                networking::ResponseStatus::Unauth,
+                JSONResponse::Error("the admin api is disabled on this server".to_owned()),
+            )),
+        })
+    }
+
+    /// Tags `response` with the versions of the users list and peers it was built from.
+    fn validate(&self, response: Response, req: &Request, viewer: Option<&str>) -> Response {
+        cache::validate(
+            response,
+            (self.users.version(), self.remote.version()),
+            self.users.modified().max(self.remote.modified()),
+            Format::negotiate(req),
+            req.api_version,
+            viewer,
+        )
+    }
+
+    /// The mail line of `username`, from their mailbox in the config or the spool for system users.
+    fn mail(&mut self, username: &str) -> Option<String> {
+        // the name ends up in a path
+        if username.contains('/') || username.starts_with('.') {
+            return None;
+        }
+
+        let path = match self.config.mailboxes.get(username) {
+            Some(path) => path.to_owned(),
+            None if self.config.system_users != SystemUsers::Off => {
+                self.config.mail_spool.join(username)
+            }
+            None => return None,
+        };
+        self.mail.check(&path)
+    }
+
+    fn lock(&self) -> Result<File> {
+        is_relative("lock", &self.config.lock)?;
+        info!("creating lock at {}", self.config.lock.display());
+        lockfile::acquire(&self.config.lock)
+    }
+}
+
+impl Fngr for Fingr {
+    type SelfLock = Arc<Mutex<Self>>;
+    type Error = FngrError;
+
+    async fn plugins(state: &Arc<Mutex<Self>>) -> Plugins {
+        state.lock().await.plugins.clone()
+    }
+
+    async fn login(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        if let Some(res) = state.lock().await.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        let presence = match req.presence.as_deref().map(str::parse::<Availability>) {
+            None => Availability::Online,
+            Some(Ok(presence)) if presence.is_online() => presence,
+            Some(_) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("presence must be online, away or dnd".to_owned()),
+                ));
+            }
+        };
+
+        Self::change_online_status(state, req, username, presence).await
+    }
+
+    async fn logoff(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        Self::change_online_status(state, req, username, Availability::Offline).await
+    }
+
+    async fn finger(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if req.finger_user.is_none()
+            && let Some(group) = &req.group
+        {
+            return Self::finger_group(&state, &req, group).await;
+        }
+
+        let Some(users) = req.finger_user.to_owned() else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a user is required".to_owned()),
+            ));
+        };
+
+        let viewer = match Self::authenticate(&state, &req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+
+        if userlist::is_glob(&users) && !users.contains([',', '@']) {
+            let lock = state.lock().await;
+            let found = lock.users.glob(&users, viewer.as_deref());
+            let total = found.len();
+            let found: Vec<JSONResponse> = found
+                .into_iter()
+                .take(lock.config.max_glob_results)
+                .map(Into::into)
+                .collect();
+            return Ok(
+                Response::from(networking::ResponseStatus::Ok, JSONResponse::List(found))
+                    .header("X-Total-Count", total),
+            );
+        }
+
+        if !users.contains(',') {
+            return Self::finger_one(&state, &req, &users, viewer.as_deref()).await;
+        }
+
+        let names: Vec<&str> = users
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect();
+        if names.len() > MAX_BATCH {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!(
+                    "at most {} users can be fingered at once",
+                    MAX_BATCH
+                )),
+            ));
+        }
+
+        let mut found = vec![];
+        for name in names {
+            let response = Self::finger_one(&state, &req, name, viewer.as_deref()).await?;
+            found.push(match response.json() {
+                _ if response.status() == networking::ResponseStatus::NotFound => {
+                    JSONResponse::NotFound(name.to_owned())
+                }
+                Some(json) => json.to_owned(),
+                None => JSONResponse::Error(format!("{} can't be fingered in a batch", name)),
+            });
+        }
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::List(found),
+        ))
+    }
+
+    async fn history(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let Some(usern) = req.finger_user.to_owned() else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a user is required".to_owned()),
+            ));
+        };
+
+        let viewer = match Self::authenticate(&state, &req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+
+        let lock = state.lock().await;
+        match lock.users.get(&usern) {
+            Some(user) if !user.blocks(viewer.as_deref()) => {
+                if !user.allows(viewer.as_deref()) {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::Forbidden,
+                        JSONResponse::Error(format!("only friends of {} can finger them", usern)),
+                    ));
+                }
+
+                Ok(Response::from(
+                    networking::ResponseStatus::Ok,
+                    JSONResponse::History(user.history()),
+                ))
+            }
+            // blocked users are told the user doesn't exist
+            _ => Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("user not found".to_owned()),
+            )),
+        }
+    }
+
+    async fn stats(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let viewer = match Self::authenticate(&state, &req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+
+        let lock = state.lock().await;
+        let Some(usern) = req.finger_user.to_owned() else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                JSONResponse::Leaderboard(lock.users.leaderboard().map(User::stats).collect()),
+            ));
+        };
+
+        match lock.users.get(&usern) {
+            Some(user) if !user.blocks(viewer.as_deref()) => {
+                if !user.allows(viewer.as_deref()) {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::Forbidden,
+                        JSONResponse::Error(format!("only friends of {} can finger them", usern)),
+                    ));
+                }
+
+                Ok(Response::from(
+                    networking::ResponseStatus::Ok,
+                    JSONResponse::UserStats(user.stats()),
+                ))
+            }
+            // blocked users are told the user doesn't exist
+            _ => Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("user not found".to_owned()),
+            )),
+        }
+    }
+
+    async fn info(state: Arc<Mutex<Self>>, _req: Request) -> Result<Response, FngrError> {
+        let lock = state.lock().await;
+        let registration =
+            lock.config
+                .registration
+                .then_some(match lock.config.registration_mode {
+                    RegistrationMode::Open => "open",
+                    RegistrationMode::Invite => "invite",
+                    RegistrationMode::Approval => "approval",
+                });
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::Info(JSONInfo {
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+                uptime_secs: lock.started.elapsed().as_secs(),
+                users: lock.users.len(),
+                online: lock.users.online_count(),
+                registration: registration.map(str::to_owned),
+                motd: lock.config.motd.to_owned(),
+            }),
+        )
+        .header("X-Online-Count", lock.users.online_count()))
+    }
+
+    async fn check(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let page = match Page::from_request(&req) {
+            Ok(page) => page,
+            Err(e) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(e),
+                ));
+            }
+        };
+
+        let mut lock = state.lock().await;
+        let Some(user) = lock.users.get_mut(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+        let (mut log, total) = user.log(&page);
+        for lookup in &mut log {
+            lookup.you_fingered = lock
+                .users
+                .get(&lookup.username)
+                .and_then(|user| user.fingered_by(&username));
+        }
+
+        Ok(
+            Response::from(networking::ResponseStatus::Ok, JSONResponse::Lookups(log))
+                .header("X-Total-Count", total),
+        )
+    }
+
+    async fn bump(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        let idle_timeout = lock.config.idle_timeout();
+        let Some(user) = lock.users.get_mut(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+
+        if user.bump(idle_timeout) {
+            lock.users.touch();
+            Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                JSONResponse::OK("you are bumped".to_owned()),
+            ))
+        } else {
+            Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("you must be logged on to bump".to_owned()),
+            ))
+        }
+    }
+
+    async fn snooze(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let secs: u64 = match req.duration.as_deref().map(str::parse) {
+            Some(Ok(secs)) => secs,
+            Some(Err(_)) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("duration must be a number of seconds".to_owned()),
+                ));
+            }
+            None => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("a duration is required".to_owned()),
+                ));
+            }
+        };
+
+        let mut lock = state.lock().await;
+        let secs = secs.min(lock.config.max_snooze);
+        let Some(user) = lock.users.get_mut(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+
+        if user.snooze(Duration::from_secs(secs)) {
+            lock.users.touch();
+            Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                JSONResponse::OK(format!("you are snoozed for {} seconds", secs)),
+            ))
+        } else {
+            Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("you must be logged on to snooze".to_owned()),
+            ))
+        }
+    }
+
+    async fn list(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let viewer = match Self::authenticate(&state, &req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+        let viewer = viewer.as_deref();
+        let mut query = match ListQuery::from_request(&req) {
+            Ok(query) => query,
+            Err(e) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(e),
+                ));
+            }
+        };
+
+        let mut lock = state.lock().await;
+        let version = lock.users.version();
+
+        if query.following() {
+            let Some(viewer) = viewer else {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Unauth,
+                    JSONResponse::Error("log in to see who you follow".to_owned()),
+                ));
+            };
+            let following = lock
+                .users
+                .get(viewer)
+                .map(|user| user.settings().following.clone())
+                .unwrap_or_default();
+            query.only(following);
+        }
+
+        // system users come and go without the version changing, so they are never cached
+        let system_users = lock.config.system_users;
+        if system_users != SystemUsers::Off {
+            let mut output: Vec<JSONResponse> = match system_users {
+                SystemUsers::Only => vec![],
+                _ => lock.users.search(viewer, &query).map(Into::into).collect(),
+            };
+            let mut online = match system_users {
+                SystemUsers::Only => 0,
+                _ => lock.users.online_count(),
+            };
+            for user in utmp::users(&lock.config.utmp).await.map_err(server_error)? {
+                if let JSONResponse::User { username, .. } = &user
+                    && system_users == SystemUsers::Merge
+                    && lock.users.contains_key(username)
+                {
+                    continue;
+                }
+                online += 1;
+                if query.matches_response(&user) {
+                    output.push(user);
+                }
+            }
+            output.extend(
+                lock.remote
+                    .responses()
+                    .into_iter()
+                    .filter(|user| query.matches_response(user)),
+            );
+            let (body, total) = query.finish(output);
+            return Ok(Response::from(networking::ResponseStatus::Ok, body)
+                .header("X-Online-Count", online)
+                .header("X-Total-Count", total));
+        }
+
+        // only the anonymous, unfiltered json body is worth caching, the rest is rendered from
+        // scratch
+        if Format::negotiate(&req) != Format::Json || viewer.is_some() || !query.is_empty() {
+            let mut output: Vec<JSONResponse> =
+                lock.users.search(viewer, &query).map(Into::into).collect();
+            output.extend(
+                lock.remote
+                    .responses()
+                    .into_iter()
+                    .filter(|user| query.matches_response(user)),
+            );
+            let (body, total) = query.finish(output);
+            let response = Response::from(networking::ResponseStatus::Ok, body)
+                .header("X-Online-Count", lock.users.online_count())
+                .header("X-Total-Count", total);
+            return Ok(lock.validate(response, &req, viewer));
+        }
+
+        let body = match &lock.list_cache {
+            Some((v, api, body)) if *v == version && *api == req.api_version => body.to_owned(),
+            _ => {
+                #[cfg(test)]
+                lock.list_serializations
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let mut output: Vec<JSONResponse> = lock
+                    .users
+                    .values()
+                    .filter(|user| user.listed_for(None))
+                    .map(Into::into)
+                    .collect();
+                output.extend(lock.remote.responses());
+                let body = JSONResponse::List(output).render(req.api_version);
+                lock.list_cache = Some((version, req.api_version, body.to_owned()));
+                body
+            }
+        };
+
+        let response = Response::from_bytes(networking::ResponseStatus::Ok, body.into_bytes())
+            .header("Content-Type", Format::Json.content_type())
+            .header("X-Online-Count", lock.users.online_count());
+        Ok(lock.validate(response, &req, None))
+    }
+
+    async fn register(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let mut lock = state.lock().await;
+
+        if !lock.config.registration {
+            return Ok(Response::from(
+                networking::ResponseStatus::Unauth,
+                JSONResponse::Error("registration is not allowed on this server".to_owned()),
+            ));
+        }
+
+        if lock.auth.is_some() {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(
+                    "accounts come from a directory here, log in with your directory password"
+                        .to_owned(),
+                ),
+            ));
+        }
+
+        if let Some(res) = lock.refuse_banned(None, req.peer) {
+            return Ok(res);
+        }
+        if let Some(username) = &req.username
+            && ban::is_banned_name(&lock.config.banned_names, username)
+        {
+            return Ok(Response::from(
+                networking::ResponseStatus::Forbidden,
+                JSONResponse::Error("that username is not allowed".to_owned()),
+            ));
+        }
+
+        if let Some(username) = req.username {
+            if let Some(reason) = userlist::invalid_username(&username) {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(reason.to_owned()),
+                ));
+            }
+
+            if let Some(auth_key) = &lock.config.auth_key
+                && req.regkey.as_deref() != Some(auth_key.as_str())
+            {
+                warn!(?username, peer = ?req.peer, "incorrect registration key");
+                return Ok(Response::from(
+                    networking::ResponseStatus::Unauth,
+                    JSONResponse::Error("incorrect registration key".to_owned()),
+                ));
+            }
+
+            let mode = lock.config.registration_mode;
+            if mode == RegistrationMode::Invite
+                && !req
+                    .invite
+                    .as_deref()
+                    .is_some_and(|code| lock.invites.is_valid(code))
+            {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Forbidden,
+                    JSONResponse::Error("a valid invite code is required to register".to_owned()),
+                ));
+            }
+
+            let cooldown = Duration::from_secs(lock.config.name_cooldown_secs);
+            lock.freed_names
+                .retain(|_, (freed, _)| freed.elapsed() < cooldown);
+
+            if let Some((freed, grace)) = lock.freed_names.get(&username)
+                && req.grace.as_ref() != Some(grace)
+            {
+                let retry = cooldown.saturating_sub(freed.elapsed()).as_secs() + 1;
+                return Ok(Response::from(
+                    networking::ResponseStatus::Conflict,
+                    JSONResponse::Error(format!(
+                        "'{}' was recently freed, try again in {} seconds",
+                        username, retry
+                    )),
+                )
+                .header("Retry-After", retry));
+            }
+
+            lock.freed_names.remove(&username);
+            let pending = mode == RegistrationMode::Approval;
+            let uuid = lock.users.register(username.clone(), pending).await?;
+            if mode == RegistrationMode::Invite
+                && let Some(code) = &req.invite
+            {
+                lock.invites.redeem(code).await.map_err(server_error)?;
+            }
+            if pending {
+                info!(?username, "registration waiting for approval");
+            }
+            lock.hooks
+                .fire(&lock.config, &Payload::new(Event::Register, &username));
+            let uid = uuid.to_string();
+            Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                JSONResponse::OK(uid),
+            ))
+        } else {
+            Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a username is required to register".to_owned()),
+            ))
+        }
+    }
+
+    async fn deregister(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        if let Some(res) = state.lock().await.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        let mut lock = state.lock().await;
+        lock.users.remove(username.to_owned()).await?;
+        lock.sessions.end(&username);
+        if let Err(e) = avatar::remove(&lock.config.avatar_dir, &username).await {
+            error!("failed to remove the avatar of '{}': {}", username, e);
+        }
+
+        let grace_token = if lock.config.name_cooldown_secs > 0 {
+            let grace = Uuid::from_bytes(rand::random()).to_string();
+            lock.freed_names
+                .insert(username, (Instant::now(), grace.to_owned()));
+            Some(grace)
+        } else {
+            None
+        };
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::Deregistered {
+                message: "your account has been removed".to_owned(),
+                grace_token,
+            },
+        ))
+    }
+
+    async fn set_presence(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let presence = match req.presence.as_deref().map(str::parse::<Availability>) {
+            Some(Ok(presence)) if presence.is_online() => presence,
+            Some(Ok(_)) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("use logoff to go offline".to_owned()),
+                ));
+            }
+            _ => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("presence must be online, away or dnd".to_owned()),
+                ));
+            }
+        };
+
+        let mut lock = state.lock().await;
+        let idle_timeout = lock.config.idle_timeout();
+        let Some(user) = lock.users.get_mut(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+        if !user.set_presence(presence, idle_timeout) {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("you must be logged on to set your presence".to_owned()),
+            ));
+        }
+        lock.users.touch();
+
+        if let Err(e) = lock.users.save_status(&username).await {
+            error!("failed to save status of '{}': {}", username, e);
+        }
+        lock.announce(&username, Change::Presence);
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("you are now {}", presence)),
+        ))
+    }
+
+    async fn follow(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        Self::change_following(state, req, true).await
+    }
+
+    async fn unfollow(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        Self::change_following(state, req, false).await
+    }
+
+    async fn join_group(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+        let group = match Self::group_name(&req) {
+            Ok(group) => group,
+            Err(e) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(e),
+                ));
+            }
+        };
+
+        let mut lock = state.lock().await;
+        match lock.groups.get(&group) {
+            Some(found) if found.admin => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Forbidden,
+                    JSONResponse::Error(format!("only admins can change {}", group)),
+                ));
+            }
+            Some(found) if found.members.len() >= MAX_NAMES => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(format!("{} is full", group)),
+                ));
+            }
+            _ => {}
+        }
+        lock.groups
+            .join(&group, &username)
+            .await
+            .map_err(server_error)?;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("you are now in {}", group)),
+        ))
+    }
+
+    async fn leave_group(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+        let group = match Self::group_name(&req) {
+            Ok(group) => group,
+            Err(e) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(e),
+                ));
+            }
+        };
+
+        let mut lock = state.lock().await;
+        match lock.groups.get(&group) {
+            Some(found) if found.admin => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Forbidden,
+                    JSONResponse::Error(format!("only admins can change {}", group)),
+                ));
+            }
+            Some(found) if found.members.contains(&username) => {}
+            _ => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(format!("you are not in {}", group)),
+                ));
+            }
+        }
+        lock.groups
+            .leave(&group, &username)
+            .await
+            .map_err(server_error)?;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("you left {}", group)),
+        ))
+    }
+
+    async fn enroll_2fa(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        // enrolling again replaces the secret, which needs a code of the old one
+        let mut lock = state.lock().await;
+        if let Some(res) = lock.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        let secret = totp::generate_secret();
+        let uri = totp::uri(webfinger::host(&lock.config.public_url), &username, &secret);
+        lock.users.set_totp(&username, Some(secret)).await?;
+
+        info!(?username, "enrolled a second factor");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(uri),
+        ))
+    }
+
+    async fn disable_2fa(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        if !lock
+            .users
+            .get(&username)
+            .is_some_and(|user| user.has_totp())
+        {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("you have no second factor".to_owned()),
+            ));
+        }
+        if let Some(res) = lock.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        lock.users.set_totp(&username, None).await?;
+
+        info!(?username, "disabled their second factor");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK("your second factor has been removed".to_owned()),
+        ))
+    }
+
+    async fn set_pubkey(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        if let Some(res) = lock.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        let pubkey = match req.pubkey.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(pubkey) => match signature::parse_key(pubkey) {
+                Some(_) => Some(pubkey.to_ascii_lowercase()),
+                None => {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::Bad,
+                        JSONResponse::Error("pubkey must be a hex ed25519 public key".to_owned()),
+                    ));
+                }
+            },
+        };
+        let message = match pubkey {
+            Some(_) => "your public key is saved",
+            None => "your public key has been removed",
+        };
+        lock.users.set_pubkey(&username, pubkey).await?;
+
+        info!(?username, "public key changed");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(message.to_owned()),
+        ))
+    }
+
+    async fn passwd(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        if let Some(res) = state.lock().await.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        let mut lock = state.lock().await;
+        if let Some(res) = lock.refuse_external_keys() {
+            return Ok(res);
+        }
+        let Some(new_key) = req.new_key.and_then(|key| key.trim().parse::<Uuid>().ok()) else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("new_key must be a uuid".to_owned()),
+            ));
+        };
+
+        lock.users.set_key(&username, new_key).await?;
+        lock.sessions.end(&username);
+
+        info!(?username, "key changed");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK("your key has been changed".to_owned()),
+        ))
+    }
+
+    async fn rotate_key(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::check_key(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        if let Some(res) = state.lock().await.refuse_without_code(&username, &req) {
+            return Ok(res);
+        }
+
+        let mut lock = state.lock().await;
+        if let Some(res) = lock.refuse_external_keys() {
+            return Ok(res);
+        }
+
+        let uuid = lock.users.reset_key(&username).await?;
+        lock.sessions.end(&username);
+
+        info!(?username, "key rotated");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(uuid.to_string()),
+        ))
+    }
+
+    async fn webfinger(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let Some(resource) = req.resource.to_owned() else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a resource is required".to_owned()),
+            ));
+        };
+
+        let viewer = match Self::authenticate(&state, &req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+        let viewer = viewer.as_deref();
+
+        let lock = state.lock().await;
+        let host = webfinger::host(&lock.config.public_url);
+
+        match webfinger::parse_acct(&resource) {
+            // hidden users aren't looked up by name either
+            Some((username, rhost))
+                if let Some(user) = lock.users.get(username)
+                    && rhost == host
+                    && user.listed_for(viewer) =>
+            {
+                let website = user.profile().website.as_deref();
+                let jrd = Jrd::user(username, host, &lock.config.public_url, website);
+                Ok(
+                    Response::from_bytes(networking::ResponseStatus::Ok, serde_json::to_vec(&jrd)?)
+                        .header("Content-Type", "application/jrd+json"),
+                )
+            }
+            Some((username, rhost))
+                if let Some(user) = lock.users.get(username)
+                    && rhost == host
+                    && !user.blocks(viewer)
+                    && !user.allows(viewer) =>
+            {
+                Ok(Response::from(
+                    networking::ResponseStatus::Forbidden,
+                    JSONResponse::Error(format!("only friends of {} can finger them", username)),
+                ))
+            }
+            // blocked users are told the user doesn't exist
+            _ => Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("resource not found".to_owned()),
+            )),
+        }
+    }
+
+    async fn set_bio(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        Self::set_profile_field(state, &req, "bio", req.bio.as_deref(), MAX_BIO, |p| {
+            &mut p.bio
+        })
+        .await
+    }
+
+    async fn set_website(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Some(website) = req.website.as_deref().map(str::trim)
+            && !website.is_empty()
+            && !website.starts_with("https://")
+            && !website.starts_with("http://")
+        {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("website must be an http or https url".to_owned()),
+            ));
+        }
+
+        Self::set_profile_field(
+            state,
+            &req,
+            "website",
+            req.website.as_deref(),
+            MAX_LINK,
+            |p| &mut p.website,
+        )
+        .await
+    }
+
+    async fn set_social(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        Self::set_profile_field(
+            state,
+            &req,
+            "social",
+            req.social.as_deref(),
+            MAX_LINK,
+            |p| &mut p.social,
+        )
+        .await
+    }
+
+    async fn set_pronouns(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        Self::set_profile_field(
+            state,
+            &req,
+            "pronouns",
+            req.pronouns.as_deref(),
+            MAX_SHORT,
+            |p| &mut p.pronouns,
+        )
+        .await
+    }
+
+    async fn set_location(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        Self::set_profile_field(
+            state,
+            &req,
+            "location",
+            req.location.as_deref(),
+            MAX_SHORT,
+            |p| &mut p.location,
+        )
+        .await
+    }
+
+    async fn set_timezone(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Some(timezone) = req.timezone.as_deref().map(str::trim)
+            && !timezone.is_empty()
+            && timezone.parse::<chrono_tz::Tz>().is_err()
+        {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!("unknown timezone '{}'", timezone)),
+            ));
+        }
+
+        Self::set_profile_field(
+            state,
+            &req,
+            "timezone",
+            req.timezone.as_deref(),
+            MAX_SHORT,
+            |p| &mut p.timezone,
+        )
+        .await
+    }
+
+    async fn set_avatar(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        let Some(user) = lock.users.get(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+        let mut profile = user.profile().clone();
+        let dir = lock.config.avatar_dir.clone();
+
+        if let Some(image) = &req.body {
+            if image.len() > lock.config.max_avatar_bytes {
+                return Ok(Response::from(
+                    networking::ResponseStatus::PayloadTooLarge,
+                    JSONResponse::Error(format!(
+                        "avatars can be at most {} bytes",
+                        lock.config.max_avatar_bytes
+                    )),
+                ));
+            }
+
+            // the content type the client sent is only a claim
+            let Some(content_type) = avatar::sniff(image) else {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("avatars must be png, jpeg, gif or webp".to_owned()),
+                ));
+            };
+
+            avatar::save(&dir, &username, image)
+                .await
+                .map_err(server_error)?;
+            profile.avatar = None;
+            profile.avatar_type = Some(content_type.to_owned());
+        } else if let Some(url) = req.avatar.as_deref().map(str::trim) {
+            if !url.is_empty() && !url.starts_with("https://") && !url.starts_with("http://") {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("avatar must be an http or https url".to_owned()),
+                ));
+            }
+
+            if url.chars().count() > MAX_LINK || url.chars().any(char::is_control) {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(format!(
+                        "avatar must be a single line of at most {} characters",
+                        MAX_LINK
+                    )),
+                ));
+            }
+
+            avatar::remove(&dir, &username)
+                .await
+                .map_err(server_error)?;
+            profile.avatar = (!url.is_empty()).then(|| url.to_owned());
+            profile.avatar_type = None;
+        } else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("an avatar url or image is required".to_owned()),
+            ));
+        }
+
+        lock.users.set_profile(&username, profile).await?;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK("your avatar is saved".to_owned()),
+        ))
+    }
+
+    async fn avatar(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let Some(username) = req.finger_user.to_owned() else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a user is required".to_owned()),
+            ));
+        };
+
+        let viewer = match Self::authenticate(&state, &req).await {
+            Ok(Ok(viewer)) => Some(viewer),
+            _ => None,
+        };
+        let viewer = viewer.as_deref();
+
+        let lock = state.lock().await;
+        let profile = match lock.users.get(&username) {
+            Some(user) if !user.blocks(viewer) => {
+                if !user.allows(viewer) {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::Forbidden,
+                        JSONResponse::Error(format!(
+                            "only friends of {} can finger them",
+                            username
+                        )),
+                    ));
+                }
+                Some(user.profile())
+            }
+            // blocked users are told the user doesn't exist
+            _ => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::NotFound,
+                    JSONResponse::Error("user not found".to_owned()),
+                ));
+            }
+        };
+
+        match profile {
+            Some(Profile {
+                avatar: Some(url), ..
+            }) => Ok(
+                Response::from_bytes(networking::ResponseStatus::Found, vec![])
+                    .header("Location", url),
+            ),
+            Some(Profile {
+                avatar_type: Some(content_type),
+                ..
+            }) => {
+                let image = avatar::read(&lock.config.avatar_dir, &username)
+                    .await
+                    .map_err(server_error)?;
+                Ok(Response::from_bytes(networking::ResponseStatus::Ok, image)
+                    .header("Content-Type", content_type)
+                    .header("Cache-Control", "max-age=3600"))
+            }
+            _ => Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error("no avatar".to_owned()),
+            )),
+        }
+    }
+
+    async fn index(state: Arc<Mutex<Self>>, _: Request) -> Result<Response, FngrError> {
+        // the page calls the api under the same prefix it is served from
+        let index = INDEX.replace("{{base_path}}", &state.lock().await.config.base_path);
+        Ok(
+            Response::from_bytes(networking::ResponseStatus::Ok, index.into_bytes())
+                .header("Content-Type", "text/html; charset=utf-8"),
+        )
+    }
+
+    async fn openapi(state: Arc<Mutex<Self>>, _: Request) -> Result<Response, FngrError> {
+        let document = openapi::document(&state.lock().await.config.public_url);
+        Ok(Response::from_bytes(
+            networking::ResponseStatus::Ok,
+            serde_json::to_vec_pretty(&document)?,
+        )
+        .header("Content-Type", "application/json"))
+    }
+
+    async fn feed(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let lock = state.lock().await;
+        let user = req.finger_user.as_deref();
+        // feeds are public, so they leave out who anonymous viewers can't list
+        match user.map(|user| (user, lock.users.get(user))) {
+            Some((_, Some(found))) if found.listed_for(None) => {}
+            Some((user, Some(found))) if !found.allows(None) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Forbidden,
+                    JSONResponse::Error(format!("only friends of {} can finger them", user)),
+                ));
+            }
+            Some(_) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::NotFound,
+                    JSONResponse::Error("user not found".to_owned()),
+                ));
+            }
+            None => {}
+        }
+
+        let atom = lock.feed.atom(&lock.config.public_url, user, |username| {
+            lock.users
+                .get(username)
+                .is_some_and(|user| user.listed_for(None))
+        });
+        Ok(
+            Response::from_bytes(networking::ResponseStatus::Ok, atom.into_bytes())
+                .header("Content-Type", "application/atom+xml"),
+        )
+    }
+
+    async fn peer(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let mut lock = state.lock().await;
+        let key = req.headers.get(&peer::KEY_HEADER.to_ascii_lowercase());
+        let Some(host) = key
+            .and_then(|key| peer::find(&lock.config.peers, key.trim()))
+            .map(|peer| peer.host.to_owned())
+        else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Unauth,
+                JSONResponse::Error("not a peer of this server".to_owned()),
+            ));
+        };
+
+        let (Some(username), Some(online), Some(since)) = (
+            req.finger_user.filter(|username| !username.contains('@')),
+            req.online.and_then(|online| online.parse::<bool>().ok()),
+            req.since.and_then(|since| since.parse::<u64>().ok()),
+        ) else {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error("a local user, online and since are required".to_owned()),
+            ));
+        };
+
+        lock.remote.update(
+            &host,
+            &username,
+            JSONStatus {
+                online,
+                // `Remote` fills it in from `online` for older peers
+                presence: req
+                    .presence
+                    .and_then(|presence| presence.parse().ok())
+                    .unwrap_or_default(),
+                text: req.status,
+                since,
+                expires_in: None,
+                last_seen: None,
+            },
+        );
+        lock.list_cache = None;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK("update received".to_owned()),
+        ))
+    }
+
+    async fn admin_logoff(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::admin_user(&state, &req).await {
+            Ok(Ok(username)) => username,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        state.lock().await.force_logoff(&username).await;
+
+        info!(?username, "logged off by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("{} is now logged off", username)),
+        ))
+    }
+
+    async fn admin_delete(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::admin_user(&state, &req).await {
+            Ok(Ok(username)) => username,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        lock.users.remove(username.to_owned()).await?;
+        lock.sessions.end(&username);
+        if let Err(e) = avatar::remove(&lock.config.avatar_dir, &username).await {
+            error!("failed to remove the avatar of '{}': {}", username, e);
+        }
+
+        info!(?username, "deleted by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("{} has been removed", username)),
+        ))
+    }
+
+    async fn admin_reset_key(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::admin_user(&state, &req).await {
+            Ok(Ok(username)) => username,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        if let Some(res) = lock.refuse_external_keys() {
+            return Ok(res);
+        }
+
+        let uuid = lock.users.reset_key(&username).await?;
+        lock.sessions.end(&username);
+
+        info!(?username, "key reset by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(uuid.to_string()),
+        ))
+    }
+
+    async fn admin_stats(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Err(res) = Self::check_admin(&state, &req).await? {
+            return Ok(res);
+        }
+
+        let lock = state.lock().await;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::Stats(JSONStats {
+                users: lock.users.len(),
+                online: lock.users.online_count(),
+                remote_users: lock.remote.len(),
+                sessions: lock.sessions.len(),
+                uptime_secs: lock.started.elapsed().as_secs(),
+            }),
+        ))
+    }
+
+    async fn admin_reload(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Err(res) = Self::check_admin(&state, &req).await? {
+            return Ok(res);
+        }
+
+        info!("reload requested by an admin");
+        match Self::reload(&state).await {
+            Ok(()) => Ok(Response::from(
+                networking::ResponseStatus::Ok,
+                JSONResponse::OK("reloaded".to_owned()),
+            )),
+            Err(e) => {
+                error!("reload failed: {}", e);
+                Ok(Response::from(
+                    networking::ResponseStatus::ServerError,
+                    JSONResponse::Error(format!("reload failed: {}", e)),
+                ))
+            }
+        }
+    }
+
+    async fn admin_ban(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::admin_user(&state, &req).await {
+            Ok(Ok(username)) => username,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let until = match req.duration.as_deref().map(str::parse::<u64>) {
+            None => None,
+            Some(Ok(secs)) if secs > 0 => Some(
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+                    .saturating_add(secs),
+            ),
+            Some(_) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("duration must be a positive number of seconds".to_owned()),
+                ));
+            }
+        };
+        let ban = Ban {
+            reason: req
+                .reason
+                .map(|reason| reason.trim().to_owned())
+                .filter(|reason| !reason.is_empty()),
+            until,
+        };
+
+        let mut lock = state.lock().await;
+        lock.users.set_ban(&username, Some(ban)).await?;
+        lock.force_logoff(&username).await;
+
+        info!(?username, ?until, "banned by an admin");
+        let message = match req.duration {
+            Some(secs) if until.is_some() => format!("{} is suspended for {}s", username, secs),
+            _ => format!("{} is banned", username),
+        };
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(message),
+        ))
+    }
+
+    async fn admin_unban(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::admin_user(&state, &req).await {
+            Ok(Ok(username)) => username,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        state.lock().await.users.set_ban(&username, None).await?;
+
+        info!(?username, "unbanned by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("{} is no longer banned", username)),
+        ))
+    }
+
+    async fn admin_invite(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Err(res) = Self::check_admin(&state, &req).await? {
+            return Ok(res);
+        }
+
+        let code = state
+            .lock()
+            .await
+            .invites
+            .create()
+            .await
+            .map_err(server_error)?;
+
+        info!("invite code created by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(code.to_string()),
+        ))
+    }
+
+    async fn admin_pending(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Err(res) = Self::check_admin(&state, &req).await? {
+            return Ok(res);
+        }
+
+        let lock = state.lock().await;
+        let output = lock
+            .users
+            .values()
+            .filter(|user| user.pending())
+            .map(Into::into)
+            .collect();
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::List(output),
+        ))
+    }
+
+    async fn admin_approve(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::admin_user(&state, &req).await {
+            Ok(Ok(username)) => username,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        if !lock.users.get(&username).is_some_and(|user| user.pending()) {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!("{} is not waiting for approval", username)),
+            ));
+        }
+        lock.users.approve(&username).await?;
+
+        info!(?username, "approved by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(format!("{} can now log in", username)),
+        ))
+    }
+
+    async fn admin_group(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        if let Err(res) = Self::check_admin(&state, &req).await? {
+            return Ok(res);
+        }
+        let group = match Self::group_name(&req) {
+            Ok(group) => group,
+            Err(e) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(e),
+                ));
+            }
+        };
+
+        let mut members: Vec<String> = vec![];
+        for member in req.members.as_deref().unwrap_or_default().split(',') {
+            let member = member.trim();
+            if !member.is_empty() && !members.iter().any(|known| known == member) {
+                members.push(member.to_owned());
+            }
+        }
+        if members.len() > MAX_NAMES {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(format!("a group can have at most {} members", MAX_NAMES)),
+            ));
+        }
+
+        let mut lock = state.lock().await;
+        if let Some(unknown) = members
+            .iter()
+            .find(|member| !lock.users.contains_key(*member))
+        {
+            return Ok(Response::from(
+                networking::ResponseStatus::NotFound,
+                JSONResponse::Error(format!("user not found: {}", unknown)),
+            ));
+        }
+        let removed = members.is_empty();
+        lock.groups
+            .set(&group, members)
+            .await
+            .map_err(server_error)?;
+
+        info!(?group, "group changed by an admin");
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK(if removed {
+                format!("{} is removed", group)
+            } else {
+                format!("{} is saved", group)
+            }),
+        ))
+    }
+
+    async fn settings(state: Arc<Mutex<Self>>, req: Request) -> Result<Response, FngrError> {
+        let username = match Self::authenticate(&state, &req).await {
+            Ok(Ok(content)) => content,
+            Ok(Err(res)) => return Ok(res),
+            Err(e) => return Err(e),
+        };
+
+        let mut lock = state.lock().await;
+        let Some(user) = lock.users.get(&username) else {
+            return Ok(Self::unknown_user(&username));
+        };
+        let mut settings = user.settings().clone();
+
+        match req.private.as_deref().map(str::parse) {
+            Some(Ok(private)) => settings.private = private,
+            Some(Err(_)) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("private must be true or false".to_owned()),
+                ));
+            }
+            None => {}
+        }
+
+        // like a snooze, a user can't stay online without bumping for longer than `max_snooze`
+        match req.idle_timeout.as_deref().map(str::parse::<u64>) {
+            Some(Ok(0)) => settings.idle_timeout_secs = None,
+            Some(Ok(secs)) => {
+                settings.idle_timeout_secs = Some(secs.clamp(60, lock.config.max_snooze.max(60)))
+            }
+            Some(Err(_)) => {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error("idle_timeout must be a number of seconds".to_owned()),
+                ));
+            }
+            None => {}
+        }
+
+        for (name, value, setting) in [
+            ("hidden", &req.hidden, &mut settings.hidden),
+            (
+                "friends_only",
+                &req.friends_only,
+                &mut settings.friends_only,
+            ),
+        ] {
+            match value.as_deref().map(str::parse) {
+                Some(Ok(value)) => *setting = value,
+                Some(Err(_)) => {
+                    return Ok(Response::from(
+                        networking::ResponseStatus::Bad,
+                        JSONResponse::Error(format!("{} must be true or false", name)),
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        // both lists are replaced as a whole, an empty value clears them
+        for (name, value, setting) in [
+            ("friends", &req.friends, &mut settings.friends),
+            ("blocked", &req.blocked, &mut settings.blocked),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            let mut names: Vec<String> = vec![];
+            for n in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                if !names.iter().any(|name| name == n) {
+                    names.push(n.to_owned());
+                }
+            }
+            if names.len() > MAX_NAMES {
+                return Ok(Response::from(
+                    networking::ResponseStatus::Bad,
+                    JSONResponse::Error(format!("{} can have at most {} names", name, MAX_NAMES)),
+                ));
+            }
+            *setting = names;
+        }
+
+        if let Err(e) = Self::push_settings(&lock.config, &req, &mut settings) {
+            return Ok(Response::from(
+                networking::ResponseStatus::Bad,
+                JSONResponse::Error(e),
+            ));
+        }
+
+        lock.users.set_settings(&username, settings).await?;
+        lock.list_cache = None;
+
+        Ok(Response::from(
+            networking::ResponseStatus::Ok,
+            JSONResponse::OK("your settings are saved".to_owned()),
+        ))
+    }
+}
+
+/// What a connection does after a request was answered.
+enum Next {
+    Continue,
+    Close,
+    /// The connection is a websocket from now on.
+    #[cfg(feature = "websocket")]
+    Upgrade(Box<Request>, Option<String>),
+}
+
+/// A new id for a request, sent back in `X-Request-Id` and on every log line about it.
+fn request_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// Whether a proxy's `X-Request-Id` is short and plain enough to log.
+fn is_request_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+/// Resolves on ctrl-c, or `SIGTERM` on unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(e) => {
+                error!("{}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::server::testing::{self, TempDir};
+
+    /// Runs the offline worker's sweep once, answering with who it changed.
+    async fn sweep(state: &Arc<Mutex<Fingr>>) -> Vec<String> {
+        let mut lock = state.lock().await;
+        let idle_timeout = lock.config.idle_timeout();
+        let changed = lock.users.check_statuses(idle_timeout);
+        lock.save_statuses().await;
+        changed
+    }
+
+    async fn writes(state: &Arc<Mutex<Fingr>>) -> usize {
+        state.lock().await.users.store().status_writes()
+    }
+
+    async fn presence(state: &Arc<Mutex<Fingr>>, username: &str) -> Availability {
+        state.lock().await.users[username].status().presence
+    }
+
+    #[tokio::test]
+    async fn list_is_serialized_once_for_each_version() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[]).await;
+        let key = testing::register(&state, "alice").await;
+        testing::register(&state, "bob").await;
+        let serializations = || async {
+            state
+                .lock()
+                .await
+                .list_serializations
+                .load(Ordering::Relaxed)
+        };
+
+        for _ in 0..3 {
+            testing::request(&state, "/list").await;
+        }
+        assert_eq!(serializations().await, 1);
+
+        testing::request(&state, &format!("/login?username=alice&key={}", key)).await;
+        testing::request(&state, "/list").await;
+        testing::request(&state, "/list").await;
+        assert_eq!(serializations().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn snoozed_users_outlast_the_idle_timeout() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[("idle_timeout_secs", "60")]).await;
+        let key = testing::register(&state, "alice").await;
+        testing::request(&state, &format!("/login?username=alice&key={}", key)).await;
+        let snoozed = testing::request(
+            &state,
+            &format!("/snooze?username=alice&key={}&duration=300", key),
+        )
+        .await;
+        assert_eq!(snoozed.status(), networking::ResponseStatus::Ok);
+
+        // well past the idle timeout, but not the snooze
+        tokio::time::advance(Duration::from_secs(120)).await;
+        assert!(sweep(&state).await.is_empty());
+        assert_eq!(presence(&state, "alice").await, Availability::Online);
+
+        tokio::time::advance(Duration::from_secs(181)).await;
+        assert_eq!(sweep(&state).await, ["alice"]);
+        assert_eq!(presence(&state, "alice").await, Availability::Away);
+    }
+
+    #[tokio::test]
+    async fn second_login_is_already_online() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[]).await;
+        let key = testing::register(&state, "alice").await;
+        let login = format!("/login?username=alice&key={}", key);
+
+        let already_online = |response: Response| match response.json() {
+            Some(JSONResponse::Login { already_online, .. }) => *already_online,
+            other => panic!("login answered {:?}", other),
+        };
+        assert!(!already_online(testing::request(&state, &login).await));
+        assert!(already_online(testing::request(&state, &login).await));
+    }
+
+    #[tokio::test]
+    async fn list_counts_online_users() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[]).await;
+        let alice = testing::register(&state, "alice").await;
+        let bob = testing::register(&state, "bob").await;
+        testing::register(&state, "carol").await;
+        let online_count = |target: &'static str| {
+            let state = &state;
+            async move {
+                let response = testing::request(state, target).await;
+                response.header_value("X-Online-Count").map(str::to_owned)
+            }
+        };
+
+        assert_eq!(online_count("/list").await.as_deref(), Some("0"));
+        assert_eq!(online_count("/info").await.as_deref(), Some("0"));
+        let login = testing::request(&state, &format!("/login?username=alice&key={}", alice)).await;
+        assert_eq!(login.header_value("X-Online-Count"), Some("1"));
+        testing::request(&state, &format!("/login?username=bob&key={}", bob)).await;
+        assert_eq!(online_count("/list").await.as_deref(), Some("2"));
+        assert_eq!(online_count("/info").await.as_deref(), Some("2"));
+        let logoff = testing::request(&state, &format!("/logoff?username=bob&key={}", bob)).await;
+        assert_eq!(logoff.header_value("X-Online-Count"), Some("1"));
+        assert_eq!(online_count("/list").await.as_deref(), Some("1"));
+        assert_eq!(online_count("/info").await.as_deref(), Some("1"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sweeps_write_only_changes() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[("idle_timeout_secs", "60")]).await;
+        let alice = testing::register(&state, "alice").await;
+        let bob = testing::register(&state, "bob").await;
+        testing::register(&state, "carol").await;
+        testing::register(&state, "dave").await;
+        testing::request(&state, &format!("/login?username=alice&key={}", alice)).await;
+        tokio::time::advance(Duration::from_secs(30)).await;
+        testing::request(&state, &format!("/login?username=bob&key={}", bob)).await;
+        state.lock().await.save_statuses().await;
+        let settled = writes(&state).await;
+
+        assert!(sweep(&state).await.is_empty());
+        assert_eq!(writes(&state).await, settled);
+
+        // only alice's row is written, not the three that didn't change
+        tokio::time::advance(Duration::from_secs(31)).await;
+        assert_eq!(sweep(&state).await, ["alice"]);
+        assert_eq!(writes(&state).await, settled + 1);
+        assert!(sweep(&state).await.is_empty());
+        assert_eq!(writes(&state).await, settled + 1);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(sweep(&state).await, ["bob"]);
+        assert_eq!(writes(&state).await, settled + 2);
+    }
+
+    #[tokio::test]
+    async fn usernames_that_read_as_something_else_are_refused() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[]).await;
+
+        // empty, blank, a batch, a forward and patterns
+        for username in ["", "%20%20", "a%2Cb", "a%40host", "a*", "a%3F", "a%5Bb%5D"] {
+            let refused =
+                testing::request(&state, &format!("/register?username={}", username)).await;
+            assert_eq!(refused.status(), networking::ResponseStatus::Bad);
+            match refused.json() {
+                Some(JSONResponse::Error(reason)) => assert!(reason.starts_with("usernames can't")),
+                other => panic!("registering {:?} answered {:?}", username, other),
+            }
+        }
+        assert!(state.lock().await.users.is_empty());
+        testing::register(&state, "alice").await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn freed_names_cool_down() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[("name_cooldown_secs", "600")]).await;
+        let key = testing::register(&state, "alice").await;
+        let deregistered =
+            testing::request(&state, &format!("/deregister?username=alice&key={}", key)).await;
+        assert_eq!(deregistered.status(), networking::ResponseStatus::Ok);
+
+        tokio::time::advance(Duration::from_secs(300)).await;
+        let refused = testing::request(&state, "/register?username=alice").await;
+        assert_eq!(refused.status(), networking::ResponseStatus::Conflict);
+        assert_eq!(refused.header_value("Retry-After"), Some("301"));
+
+        tokio::time::advance(Duration::from_secs(301)).await;
+        testing::register(&state, "alice").await;
+    }
+}
@@ -0,0 +1,129 @@
+//! Checks on what users write about themselves, for servers that are open to anyone.
+//!
+//! A check implements [`Filter`] and gets a variant in [`Check`]. [`Moderation`] runs the ones
+//! the `[moderation]` section of the config asks for on status texts and profile fields before
+//! they are saved.
+
+use std::{path::PathBuf, process::Stdio};
+
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command,
+    time::{Duration, timeout},
+};
+
+use crate::server::{config::ModerationConfig, prelude::*};
+
+// how long the command gets to make up its mind
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub trait Filter {
+    /// Why `text`, which `username` wants as their `field`, is refused, `None` if it is fine.
+    fn check(
+        &self,
+        username: &str,
+        field: &str,
+        text: &str,
+    ) -> impl Future<Output = Result<Option<String>>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    /// Longest text in characters.
+    MaxLength(usize),
+    /// Lowercase words that may not appear in a text.
+    BannedWords(Vec<String>),
+    /// A program that gets the username and field as arguments and the text on stdin, and
+    /// refuses it by exiting with anything but 0. The first line it prints says why.
+    Command(PathBuf),
+}
+
+impl Filter for Check {
+    async fn check(&self, username: &str, field: &str, text: &str) -> Result<Option<String>> {
+        match self {
+            Check::MaxLength(max) => Ok((text.chars().count() > *max)
+                .then(|| format!("it must be at most {} characters", max))),
+            Check::BannedWords(banned) => Ok(text
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|word| banned.contains(&word.to_lowercase()))
+                .then(|| "it contains a word that isn't allowed here".to_owned())),
+            Check::Command(command) => run(command, username, field, text).await,
+        }
+    }
+}
+
+async fn run(command: &PathBuf, username: &str, field: &str, text: &str) -> Result<Option<String>> {
+    let mut child = Command::new(command)
+        .args([username, field])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("failed to run {}: {}", command.display(), e))?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or(anyhow!("{} has no stdin", command.display()))?;
+    stdin.write_all(text.as_bytes()).await?;
+    drop(stdin);
+
+    let output = timeout(COMMAND_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow!("{} took too long", command.display()))??;
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    let reason = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .unwrap_or("it isn't allowed here")
+        .to_owned();
+    Ok(Some(reason))
+}
+
+/// The checks the config asks for, run in order until one refuses.
+#[derive(Debug, Clone, Default)]
+pub struct Moderation {
+    checks: Vec<Check>,
+}
+
+impl Moderation {
+    pub fn new(config: &ModerationConfig) -> Self {
+        let mut checks = vec![];
+        if let Some(max) = config.max_length {
+            checks.push(Check::MaxLength(max));
+        }
+        if !config.banned_words.is_empty() {
+            checks.push(Check::BannedWords(
+                config
+                    .banned_words
+                    .iter()
+                    .map(|word| word.to_lowercase())
+                    .collect(),
+            ));
+        }
+        // the command goes last, so it only sees what the cheap checks let through
+        if let Some(command) = &config.command {
+            checks.push(Check::Command(command.to_owned()));
+        }
+
+        Self { checks }
+    }
+}
+
+impl Filter for Moderation {
+    async fn check(&self, username: &str, field: &str, text: &str) -> Result<Option<String>> {
+        for check in &self.checks {
+            if let Some(reason) = check.check(username, field, text).await? {
+                return Ok(Some(reason));
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,750 @@
+//! The OpenAPI 3 description of the http api, served at `/openapi.json` for client authors.
+//!
+//! Every action takes its parameters in the query of a `GET` or in the body of a `POST`, so both
+//! are described from the same list, once for every api version.
+
+use crate::networking::{API_VERSION, Action, IMAGE_TYPES, OLDEST_API_VERSION};
+use serde_json::{Map, Value, json};
+
+/// Who may call an action.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Auth {
+    Anyone,
+    /// Anyone, and users who authenticate see more.
+    Optional,
+    /// A user with their key, or a session or signature.
+    User,
+    /// A user with their key or signature, never a session.
+    Key,
+    Admin,
+    Peer,
+}
+
+/// What the body of a successful answer is.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Body {
+    Json,
+    Atom,
+    Jrd,
+    Image,
+}
+
+struct Operation {
+    action: Action,
+    summary: &'static str,
+    params: &'static [&'static str],
+    auth: Auth,
+    body: Body,
+}
+
+const fn op(
+    action: Action,
+    summary: &'static str,
+    params: &'static [&'static str],
+    auth: Auth,
+) -> Operation {
+    Operation {
+        action,
+        summary,
+        params,
+        auth,
+        body: Body::Json,
+    }
+}
+
+const OPERATIONS: &[Operation] = &[
+    op(
+        Action::Login,
+        "Go online, optionally with a status text",
+        &["status", "presence", "code"],
+        Auth::Key,
+    ),
+    op(Action::Logoff, "Go offline", &[], Auth::User),
+    op(
+        Action::Finger,
+        "Show one or more users, a pattern of them or a group",
+        &["user", "group", "history"],
+        Auth::Optional,
+    ),
+    op(
+        Action::Check,
+        "Show who fingered you since the last check",
+        &["limit", "offset"],
+        Auth::User,
+    ),
+    op(
+        Action::History,
+        "Show the status texts a user set lately",
+        &["user"],
+        Auth::Optional,
+    ),
+    op(
+        Action::Stats,
+        "Show how often a user was fingered and online, or this week's most fingered users",
+        &["user"],
+        Auth::Optional,
+    ),
+    op(
+        Action::Info,
+        "Show the server's version, uptime, how many users it has and its message of the day",
+        &[],
+        Auth::Anyone,
+    ),
+    op(
+        Action::Bump,
+        "Stay online for another while",
+        &[],
+        Auth::User,
+    ),
+    op(
+        Action::Snooze,
+        "Stay online without bumping for a number of seconds",
+        &["duration"],
+        Auth::User,
+    ),
+    op(
+        Action::List,
+        "List the users",
+        &[
+            "online",
+            "prefix",
+            "search",
+            "sort",
+            "count",
+            "limit",
+            "offset",
+            "following",
+        ],
+        Auth::Optional,
+    ),
+    op(
+        Action::Register,
+        "Make an account and answer with its key",
+        &["username", "regkey", "invite", "grace"],
+        Auth::Anyone,
+    ),
+    op(
+        Action::Deregister,
+        "Remove your account",
+        &["code"],
+        Auth::Key,
+    ),
+    op(
+        Action::SetPresence,
+        "Change your presence while logged on",
+        &["presence"],
+        Auth::User,
+    ),
+    op(Action::Follow, "Follow users", &["user"], Auth::User),
+    op(
+        Action::Unfollow,
+        "Stop following users",
+        &["user"],
+        Auth::User,
+    ),
+    op(
+        Action::JoinGroup,
+        "Join a group, starting it if there is none",
+        &["group"],
+        Auth::User,
+    ),
+    op(Action::LeaveGroup, "Leave a group", &["group"], Auth::User),
+    op(
+        Action::Enroll2fa,
+        "Turn on a second factor and answer with its otpauth uri",
+        &["code"],
+        Auth::Key,
+    ),
+    op(
+        Action::Disable2fa,
+        "Turn off the second factor",
+        &["code"],
+        Auth::Key,
+    ),
+    op(
+        Action::SetPubkey,
+        "Set the ed25519 key to sign requests with, an empty one removes it",
+        &["pubkey"],
+        Auth::Key,
+    ),
+    op(
+        Action::Passwd,
+        "Replace your key with one of your choosing",
+        &["new_key", "code"],
+        Auth::Key,
+    ),
+    op(
+        Action::RotateKey,
+        "Replace your key with a random one and answer with it",
+        &["code"],
+        Auth::Key,
+    ),
+    op(
+        Action::Settings,
+        "Change your settings",
+        &[
+            "private",
+            "idle_timeout",
+            "hidden",
+            "friends_only",
+            "friends",
+            "blocked",
+            "ntfy",
+            "gotify",
+            "push",
+        ],
+        Auth::User,
+    ),
+    op(Action::SetBio, "Set your bio", &["bio"], Auth::User),
+    op(
+        Action::SetWebsite,
+        "Set your website",
+        &["website"],
+        Auth::User,
+    ),
+    op(
+        Action::SetSocial,
+        "Set your social handle",
+        &["social"],
+        Auth::User,
+    ),
+    op(
+        Action::SetPronouns,
+        "Set your pronouns",
+        &["pronouns"],
+        Auth::User,
+    ),
+    op(
+        Action::SetLocation,
+        "Set where you are",
+        &["location"],
+        Auth::User,
+    ),
+    op(
+        Action::SetTimezone,
+        "Set your IANA timezone",
+        &["timezone"],
+        Auth::User,
+    ),
+    op(
+        Action::SetAvatar,
+        "Point your avatar at a url, or upload an image as the body",
+        &["avatar"],
+        Auth::User,
+    ),
+    Operation {
+        action: Action::Avatar,
+        summary: "Answer with a user's uploaded avatar or redirect to its url",
+        params: &["user"],
+        auth: Auth::Anyone,
+        body: Body::Image,
+    },
+    Operation {
+        action: Action::Feed,
+        summary: "An atom feed of recent changes, of one user if given",
+        params: &["user"],
+        auth: Auth::Anyone,
+        body: Body::Atom,
+    },
+    Operation {
+        action: Action::WebFinger,
+        summary: "Describe a user as a WebFinger document",
+        params: &["resource"],
+        auth: Auth::Anyone,
+        body: Body::Jrd,
+    },
+    op(
+        Action::Peer,
+        "Take a presence change pushed by a peer server",
+        &["user", "online", "since", "presence", "status"],
+        Auth::Peer,
+    ),
+    op(
+        Action::AdminLogoff,
+        "Set a user offline and end their sessions",
+        &["user"],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminDelete,
+        "Remove a user with everything about them",
+        &["user"],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminResetKey,
+        "Give a user a new key and answer with it",
+        &["user"],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminStats,
+        "Answer with how the server is doing",
+        &[],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminReload,
+        "Read the config and users again",
+        &[],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminBan,
+        "Ban a user, for a while if a duration is given",
+        &["user", "duration", "reason"],
+        Auth::Admin,
+    ),
+    op(Action::AdminUnban, "Lift a ban", &["user"], Auth::Admin),
+    op(
+        Action::AdminInvite,
+        "Make a single use invite code",
+        &[],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminPending,
+        "List the registrations waiting for approval",
+        &[],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminApprove,
+        "Let a pending user log in",
+        &["user"],
+        Auth::Admin,
+    ),
+    op(
+        Action::AdminGroup,
+        "Set the members of a group only admins can change, none removes it",
+        &["group", "members"],
+        Auth::Admin,
+    ),
+];
+
+/// The type and description of every parameter an action takes.
+const PARAMETERS: &[(&str, &str, &str)] = &[
+    ("username", "string", "Your username"),
+    ("key", "string", "Your key, the uuid register answered with"),
+    (
+        "user",
+        "string",
+        "A username, several separated with commas where more are taken",
+    ),
+    ("status", "string", "A status text"),
+    ("presence", "string", "One of online, away, dnd or offline"),
+    (
+        "code",
+        "string",
+        "A code from your authenticator, with 2fa on",
+    ),
+    ("group", "string", "A group name"),
+    ("members", "string", "Comma separated usernames"),
+    ("history", "boolean", "Add the recent status texts"),
+    ("limit", "integer", "How many to answer with at most"),
+    ("offset", "integer", "How many to skip"),
+    ("duration", "integer", "Seconds"),
+    ("online", "boolean", "Whether the user is online"),
+    ("prefix", "string", "Only usernames starting with it"),
+    (
+        "search",
+        "string",
+        "Only users whose status text has it, ignoring case",
+    ),
+    ("sort", "string", "One of name, since or recent"),
+    ("count", "boolean", "Answer with only the number of users"),
+    ("following", "boolean", "Only the users you follow"),
+    ("since", "integer", "Seconds since the status was set"),
+    ("regkey", "string", "The server's registration key"),
+    ("invite", "string", "An invite code from an admin"),
+    (
+        "grace",
+        "string",
+        "The grace token deregistering answered with",
+    ),
+    ("pubkey", "string", "A hex ed25519 public key"),
+    ("new_key", "string", "A uuid to use as your key from now on"),
+    (
+        "private",
+        "boolean",
+        "Keep your lookups out of others' checks",
+    ),
+    (
+        "idle_timeout",
+        "integer",
+        "Seconds until you go away without a bump, 0 for the server's",
+    ),
+    ("hidden", "boolean", "Keep you out of list"),
+    (
+        "friends_only",
+        "boolean",
+        "Only let your friends finger you",
+    ),
+    ("friends", "string", "Comma separated usernames"),
+    ("blocked", "string", "Comma separated usernames"),
+    (
+        "ntfy",
+        "string",
+        "An ntfy topic or topic url to push notifications to, empty for none",
+    ),
+    (
+        "gotify",
+        "string",
+        "A gotify message url with an app token, empty for none",
+    ),
+    (
+        "push",
+        "string",
+        "What to push, comma separated online and finger",
+    ),
+    ("bio", "string", "A one line bio, empty removes it"),
+    (
+        "website",
+        "string",
+        "An http or https url, empty removes it",
+    ),
+    ("social", "string", "A social handle, empty removes it"),
+    ("pronouns", "string", "Empty removes them"),
+    ("location", "string", "Empty removes it"),
+    ("timezone", "string", "Like Europe/Berlin, empty removes it"),
+    ("avatar", "string", "An image url, empty removes the avatar"),
+    ("resource", "string", "Like acct:user@host"),
+    ("reason", "string", "Shown to the banned user"),
+    (
+        "format",
+        "string",
+        "One of json, text or html, overriding Accept",
+    ),
+];
+
+/// The document, with `public_url` as the server.
+pub fn document(public_url: &str) -> Value {
+    let mut paths = Map::new();
+    for operation in OPERATIONS {
+        let mut names: Vec<&str> = match operation.auth {
+            Auth::Optional | Auth::User | Auth::Key => vec!["username", "key"],
+            _ => vec![],
+        };
+        names.extend(operation.params);
+        if operation.body == Body::Json {
+            names.push("format");
+        }
+
+        for version in OLDEST_API_VERSION..=API_VERSION {
+            let get = describe(operation, &names, version, false);
+            let post = describe(operation, &names, version, true);
+            paths.insert(
+                format!("/v{}/{}", version, operation.action),
+                json!({ "get": get, "post": post }),
+            );
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "fngr",
+            "description": "A finger server with an http api. Version 1 answers in JSONResponse and is also served without the prefix, version 2 in ApiResponse.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": public_url }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "session": { "type": "http", "scheme": "bearer", "description": "The session login answered with" },
+                "admin": { "type": "apiKey", "in": "header", "name": "X-Fngr-Admin-Key" },
+                "peer": { "type": "apiKey", "in": "header", "name": "X-Fngr-Peer-Key" },
+            },
+            "schemas": schemas(),
+        },
+    })
+}
+
+/// One method of an operation in api `version`, with its parameters in the query or in the body.
+fn describe(operation: &Operation, names: &[&str], version: u32, post: bool) -> Value {
+    let id = format!(
+        "{}_v{}{}",
+        operation.action.to_string().replace(['/', '.'], "_"),
+        version,
+        if post { "_post" } else { "" }
+    );
+    let mut description = json!({
+        "summary": operation.summary,
+        "operationId": id,
+        "responses": responses(operation.body, version),
+    });
+
+    if post {
+        let properties: Map<String, Value> = names
+            .iter()
+            .map(|name| (name.to_string(), schema(name)))
+            .collect();
+        let form = json!({ "schema": { "type": "object", "properties": properties } });
+        let mut content = json!({
+            "application/x-www-form-urlencoded": form,
+            "application/json": form,
+        });
+        if matches!(operation.action, Action::SetAvatar) {
+            for image in IMAGE_TYPES {
+                content[image] = json!({ "schema": { "type": "string", "format": "binary" } });
+            }
+        }
+        description["requestBody"] = json!({ "content": content });
+    } else {
+        description["parameters"] = names
+            .iter()
+            .map(|name| json!({ "name": name, "in": "query", "schema": schema(name) }))
+            .collect();
+    }
+
+    let security = match operation.auth {
+        Auth::User | Auth::Optional => Some(json!([{}, { "session": [] }])),
+        Auth::Admin => Some(json!([{ "admin": [] }])),
+        Auth::Peer => Some(json!([{ "peer": [] }])),
+        Auth::Anyone | Auth::Key => None,
+    };
+    if let Some(security) = security {
+        description["security"] = security;
+    }
+
+    description
+}
+
+fn schema(name: &str) -> Value {
+    let (kind, description) = PARAMETERS
+        .iter()
+        .find(|(param, ..)| *param == name)
+        .map(|(_, kind, description)| (*kind, *description))
+        .unwrap_or(("string", ""));
+
+    json!({ "type": kind, "description": description })
+}
+
+fn responses(body: Body, version: u32) -> Value {
+    let (answer, error) = match version {
+        1 => ("JSONResponse", "Error"),
+        _ => ("ApiResponse", "ApiResponse"),
+    };
+    let ok = match body {
+        Body::Json => json!({
+            "description": "The answer, in the format asked for",
+            "content": {
+                "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", answer) } },
+                "text/plain": { "schema": { "type": "string" } },
+                "text/html": { "schema": { "type": "string" } },
+            },
+        }),
+        Body::Atom => json!({
+            "description": "An atom feed",
+            "content": { "application/atom+xml": { "schema": { "type": "string" } } },
+        }),
+        Body::Jrd => json!({
+            "description": "A WebFinger document",
+            "content": { "application/jrd+json": { "schema": { "type": "object" } } },
+        }),
+        Body::Image => {
+            let images: Map<String, Value> = IMAGE_TYPES
+                .iter()
+                .map(|image| {
+                    (
+                        image.to_string(),
+                        json!({ "schema": { "type": "string", "format": "binary" } }),
+                    )
+                })
+                .collect();
+            json!({ "description": "The uploaded image", "content": images })
+        }
+    };
+
+    let mut responses = json!({
+        "200": ok,
+        "default": {
+            "description": "An error",
+            "content": {
+                "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", error) } },
+            },
+        },
+    });
+    if body == Body::Image {
+        responses["302"] = json!({ "description": "A redirect to the avatar's url" });
+    }
+
+    responses
+}
+
+/// `JSONResponse`, `ApiResponse` and what they are made of, as serde writes them.
+fn schemas() -> Value {
+    let nullable = |kind: &str| json!({ "type": kind, "nullable": true });
+    let array = |of: &str| json!({ "type": "array", "items": { "$ref": format!("#/components/schemas/{}", of) } });
+    let tagged = |tag: &str, value: Value| {
+        json!({
+            "type": "object",
+            "required": [tag],
+            "properties": { tag: value },
+            "additionalProperties": false,
+        })
+    };
+
+    json!({
+        "Availability": { "type": "string", "enum": ["online", "away", "dnd", "offline"] },
+        "Status": {
+            "type": "object",
+            "required": ["online", "since"],
+            "properties": {
+                "online": { "type": "boolean" },
+                "presence": { "$ref": "#/components/schemas/Availability" },
+                "text": nullable("string"),
+                "since": { "type": "integer", "description": "Seconds since the status was set" },
+                "expires_in": nullable("integer"),
+                "last_seen": { "type": "string", "format": "date-time", "nullable": true },
+            },
+        },
+        "Profile": {
+            "type": "object",
+            "properties": {
+                "bio": nullable("string"),
+                "website": nullable("string"),
+                "social": nullable("string"),
+                "pronouns": nullable("string"),
+                "location": nullable("string"),
+                "timezone": nullable("string"),
+                "local_time": { "type": "string", "format": "date-time", "nullable": true },
+                "avatar": nullable("string"),
+                "mail": { "type": "string" },
+                "history": array("StatusChange"),
+                "follows_you": { "type": "boolean" },
+                "fingered_you": { "type": "integer" },
+                "fingered_rank": { "type": "integer" },
+            },
+        },
+        "StatusChange": {
+            "type": "object",
+            "required": ["at"],
+            "properties": { "text": nullable("string"), "at": { "type": "integer" } },
+        },
+        "Lookup": {
+            "type": "object",
+            "required": ["username", "count", "first", "last"],
+            "properties": {
+                "username": { "type": "string" },
+                "ip": nullable("string"),
+                "count": { "type": "integer" },
+                "first": { "type": "integer" },
+                "last": { "type": "integer" },
+                "you_fingered": { "type": "integer" },
+            },
+        },
+        "Stats": {
+            "type": "object",
+            "properties": {
+                "users": { "type": "integer" },
+                "online": { "type": "integer" },
+                "remote_users": { "type": "integer" },
+                "sessions": { "type": "integer" },
+                "uptime_secs": { "type": "integer" },
+            },
+        },
+        "Info": {
+            "type": "object",
+            "required": ["version", "uptime_secs", "users", "online"],
+            "properties": {
+                "version": { "type": "string" },
+                "uptime_secs": { "type": "integer" },
+                "users": { "type": "integer" },
+                "online": { "type": "integer" },
+                "registration": { "type": "string", "enum": ["open", "invite", "approval"] },
+                "motd": { "type": "string" },
+            },
+        },
+        "UserStats": {
+            "type": "object",
+            "required": ["username", "fingered", "fingered_this_week", "logins", "online_secs"],
+            "properties": {
+                "username": { "type": "string" },
+                "fingered": { "type": "integer" },
+                "fingered_this_week": { "type": "integer" },
+                "logins": { "type": "integer" },
+                "online_secs": { "type": "integer" },
+                "rank": { "type": "integer" },
+            },
+        },
+        "User": {
+            "type": "object",
+            "required": ["username", "status"],
+            "properties": {
+                "username": { "type": "string" },
+                "status": { "$ref": "#/components/schemas/Status" },
+                "profile": { "$ref": "#/components/schemas/Profile" },
+            },
+        },
+        "ApiResponse": {
+            "type": "object",
+            "required": ["type"],
+            "properties": {
+                "type": {
+                    "type": "string",
+                    "enum": ["ok", "error", "user", "users", "count", "group", "login", "deregistered", "lookups", "history", "log", "stats", "user_stats", "leaderboard", "info"],
+                },
+                "error": { "type": "string" },
+                "message": { "type": "string" },
+                "user": { "$ref": "#/components/schemas/User" },
+                "users": array("User"),
+                "not_found": { "type": "array", "items": { "type": "string" } },
+                "count": { "type": "integer" },
+                "group": { "type": "string" },
+                "online": { "type": "integer" },
+                "already_online": { "type": "boolean" },
+                "session": { "type": "string" },
+                "grace_token": { "type": "string" },
+                "lookups": array("Lookup"),
+                "history": array("StatusChange"),
+                "log": { "type": "array", "items": { "type": "string" } },
+                "stats": { "$ref": "#/components/schemas/Stats" },
+                "user_stats": { "$ref": "#/components/schemas/UserStats" },
+                "leaderboard": array("UserStats"),
+                "info": { "$ref": "#/components/schemas/Info" },
+            },
+        },
+        "Error": tagged("Error", json!({ "type": "string" })),
+        "JSONResponse": {
+            "oneOf": [
+                { "$ref": "#/components/schemas/Error" },
+                tagged("User", json!({ "$ref": "#/components/schemas/User" })),
+                tagged("List", array("JSONResponse")),
+                tagged("OK", json!({ "type": "string" })),
+                tagged("Log", json!({ "type": "array", "items": { "type": "string" } })),
+                tagged("Lookups", array("Lookup")),
+                tagged("History", array("StatusChange")),
+                tagged("Count", json!({ "type": "integer" })),
+                tagged("NotFound", json!({ "type": "string" })),
+                tagged("Group", json!({
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "online": { "type": "integer" },
+                        "members": array("JSONResponse"),
+                    },
+                })),
+                tagged("Login", json!({
+                    "type": "object",
+                    "properties": {
+                        "message": { "type": "string" },
+                        "already_online": { "type": "boolean" },
+                        "session": { "type": "string" },
+                    },
+                })),
+                tagged("Deregistered", json!({
+                    "type": "object",
+                    "properties": {
+                        "message": { "type": "string" },
+                        "grace_token": nullable("string"),
+                    },
+                })),
+                tagged("Stats", json!({ "$ref": "#/components/schemas/Stats" })),
+                tagged("UserStats", json!({ "$ref": "#/components/schemas/UserStats" })),
+                tagged("Leaderboard", array("UserStats")),
+                tagged("Info", json!({ "$ref": "#/components/schemas/Info" })),
+            ],
+        },
+    })
+}
@@ -0,0 +1,153 @@
+//! OpenTelemetry export of the request spans and a few metrics over OTLP, for watching the server
+//! in Grafana, Jaeger or anything else that takes OTLP.
+//!
+//! It is set up with the standard `OTEL_*` variables like any other OpenTelemetry program, and
+//! only runs when `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+
+use std::{
+    env,
+    sync::{Arc, OnceLock},
+};
+
+use opentelemetry::{
+    KeyValue, global,
+    metrics::{Counter, Histogram},
+    trace::TracerProvider,
+};
+use opentelemetry_otlp::{MetricExporter, OTEL_EXPORTER_OTLP_ENDPOINT, SpanExporter};
+use opentelemetry_sdk::{
+    Resource,
+    metrics::SdkMeterProvider,
+    trace::{SdkTracerProvider, Tracer},
+};
+use tokio::sync::Mutex;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::server::{Fingr, accesslog::Entry, prelude::*};
+
+const SERVICE_NAME: &str = "fingr-server";
+
+/// The exporters, flushed and shut down when dropped.
+pub struct Telemetry {
+    tracer: SdkTracerProvider,
+    meter: SdkMeterProvider,
+}
+
+impl Telemetry {
+    /// Starts exporting if an endpoint is set.
+    pub fn init() -> Result<Option<Self>> {
+        if env::var_os(OTEL_EXPORTER_OTLP_ENDPOINT).is_none() {
+            return Ok(None);
+        }
+
+        let mut resource = Resource::builder();
+        if env::var_os("OTEL_SERVICE_NAME").is_none() {
+            resource = resource.with_service_name(SERVICE_NAME);
+        }
+        let resource = resource.build();
+
+        let tracer = SdkTracerProvider::builder()
+            .with_batch_exporter(SpanExporter::builder().with_http().build()?)
+            .with_resource(resource.clone())
+            .build();
+        let meter = SdkMeterProvider::builder()
+            .with_periodic_exporter(MetricExporter::builder().with_http().build()?)
+            .with_resource(resource)
+            .build();
+        global::set_meter_provider(meter.clone());
+
+        Ok(Some(Self { tracer, meter }))
+    }
+
+    /// A layer that sends spans to the collector.
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.tracer.tracer(SERVICE_NAME))
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.tracer.shutdown() {
+            error!("exporting spans: {}", e);
+        }
+        if let Err(e) = self.meter.shutdown() {
+            error!("exporting metrics: {}", e);
+        }
+    }
+}
+
+struct Instruments {
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+fn instruments() -> &'static Instruments {
+    static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+    INSTRUMENTS.get_or_init(|| {
+        let meter = global::meter(SERVICE_NAME);
+        Instruments {
+            requests: meter
+                .u64_counter("fngr.requests")
+                .with_description("Requests answered")
+                .build(),
+            duration: meter
+                .f64_histogram("fngr.request.duration")
+                .with_description("Time from reading a request to answering it")
+                .with_unit("s")
+                .build(),
+        }
+    })
+}
+
+/// Counts a request, the same one the access log gets.
+pub fn record(entry: &Entry) {
+    let instruments = instruments();
+    let action = KeyValue::new("action", entry.action.clone().unwrap_or_default());
+    instruments.requests.add(
+        1,
+        &[
+            action.clone(),
+            KeyValue::new("status", i64::from(entry.status)),
+        ],
+    );
+    instruments
+        .duration
+        .record(entry.latency.as_secs_f64(), &[action]);
+}
+
+/// Reports the number of users, sessions and connections at every export.
+pub fn observe(state: &Arc<Mutex<Fingr>>) {
+    let meter = global::meter(SERVICE_NAME);
+    // a busy server skips an export instead of holding up the exporter
+    let gauge = |name: &'static str, description: &'static str, value: fn(&Fingr) -> usize| {
+        let state = state.clone();
+        meter
+            .u64_observable_gauge(name)
+            .with_description(description)
+            .with_callback(move |observer| {
+                if let Ok(lock) = state.try_lock() {
+                    observer.observe(value(&lock) as u64, &[]);
+                }
+            })
+            .build();
+    };
+
+    gauge("fngr.users", "Registered users", |fingr| fingr.users.len());
+    gauge("fngr.users.online", "Users online", |fingr| {
+        fingr.users.online_count()
+    });
+    gauge("fngr.remote_users", "Users of peer servers", |fingr| {
+        fingr.remote.len()
+    });
+    gauge("fngr.sessions", "Open sessions", |fingr| {
+        fingr.sessions.len()
+    });
+    gauge("fngr.connections", "Connections being served", |fingr| {
+        fingr.config.max_connections - fingr.connections.available_permits()
+    });
+}
@@ -0,0 +1,73 @@
+//! Finger pages for browsers, rendered from a template so a server can look like a tiny tilde
+//! host. Users get their own template or stylesheet by putting `<username>.html` or
+//! `<username>.css` in the `[pages]` directory.
+//!
+//! Templates are minijinja, with html escaped unless it is marked `safe`. They get `username`,
+//! `status` and `profile` as in the json, `stylesheet` with the user's css and `server` with the
+//! server's public url.
+
+use std::{io, path::Path};
+
+use crate::networking::{JSONResponse, Response};
+use minijinja::{Environment, context};
+
+use crate::server::{config::PagesConfig, prelude::*};
+
+const PAGE: &str = include_str!("page.html");
+
+/// `response` as a page if it is a single user. A template that fails leaves it as it is.
+pub async fn render(config: &PagesConfig, server: &str, response: Response) -> Response {
+    let Some(JSONResponse::User {
+        username,
+        status,
+        profile,
+    }) = response.json().cloned()
+    else {
+        return response;
+    };
+
+    let profile = profile.map(|profile| *profile).unwrap_or_default();
+    let context = context! { username, status, profile, server };
+    match page(config, &username, context).await {
+        Ok(page) => response.with_body(page.into_bytes()),
+        Err(e) => {
+            error!(?username, "rendering page: {}", e);
+            response
+        }
+    }
+}
+
+async fn page(config: &PagesConfig, username: &str, context: minijinja::Value) -> Result<String> {
+    // names are checked on registration, this is for names that came from elsewhere
+    let own = config
+        .dir
+        .as_deref()
+        .filter(|_| !username.starts_with('.') && !username.contains(['/', '\\']));
+
+    let mut template = None;
+    let mut stylesheet = None;
+    if let Some(dir) = own {
+        template = read(&dir.join(format!("{}.html", username))).await?;
+        stylesheet = read(&dir.join(format!("{}.css", username))).await?;
+    }
+    if template.is_none()
+        && let Some(path) = &config.template
+    {
+        template = read(path).await?;
+    }
+
+    let source = template.as_deref().unwrap_or(PAGE);
+    let env = Environment::new();
+    // the name turns on html escaping
+    let template = env.template_from_named_str("page.html", source)?;
+    Ok(template.render(context! { stylesheet, ..context })?)
+}
+
+/// The file at `path`, `None` if there is none.
+async fn read(path: &Path) -> Result<Option<String>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow!("{}: {}", path.display(), e)),
+    }
+}
@@ -0,0 +1,242 @@
+//! Presence federation with other fngr servers, whose users show up in `list` as `user@host`.
+//!
+//! Changes of local users are pushed to every peer as they happen, and every peer's list is pulled
+//! now and then to catch up on what was missed while either side was down.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    Client,
+    networking::{Availability, JSONResponse, JSONStatus, Presence},
+};
+use tokio::{
+    sync::{Mutex, broadcast::error::RecvError},
+    time::{Instant, sleep, timeout},
+};
+
+use crate::server::{Fingr, config::PeerConfig, prelude::*};
+
+/// Carries the key shared with a peer, which also tells who is pushing.
+pub const KEY_HEADER: &str = "X-Fngr-Peer-Key";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The users of every peer, keyed by `user@host`.
+pub struct Remote {
+    users: HashMap<String, RemoteUser>,
+    // bumped whenever a user changes, like the version of the user list
+    version: u64,
+    modified: SystemTime,
+}
+
+struct RemoteUser {
+    status: JSONStatus,
+    /// When the status was received, its `since` and `expires_in` count from then.
+    received: Instant,
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Self {
+            users: HashMap::new(),
+            version: rand::random(),
+            modified: SystemTime::now(),
+        }
+    }
+}
+
+impl Remote {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+
+    fn touch(&mut self) {
+        self.version = self.version.wrapping_add(1);
+        self.modified = SystemTime::now();
+    }
+
+    pub fn update(&mut self, host: &str, username: &str, mut status: JSONStatus) {
+        // older peers only tell whether a user is online
+        if status.online && !status.presence.is_online() {
+            status.presence = Availability::Online;
+        }
+        self.users.insert(
+            format!("{}@{}", username, host),
+            RemoteUser {
+                status,
+                received: Instant::now(),
+            },
+        );
+        self.touch();
+    }
+
+    /// Replaces every user of `host` with those of its list.
+    pub fn replace(&mut self, host: &str, users: Vec<(String, JSONStatus)>) {
+        self.remove_host(host);
+        for (username, status) in users {
+            self.update(host, &username, status);
+        }
+    }
+
+    fn remove_host(&mut self, host: &str) {
+        let suffix = format!("@{}", host);
+        self.users.retain(|name, _| !name.ends_with(&suffix));
+        self.touch();
+    }
+
+    /// Forgets the users of hosts that are no longer peers.
+    pub fn retain_hosts(&mut self, hosts: &[&str]) {
+        self.users.retain(|name, _| {
+            name.rsplit_once('@')
+                .is_some_and(|(_, host)| hosts.contains(&host))
+        });
+        self.touch();
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    /// Every remote user as a `User` response, sorted by name.
+    pub fn responses(&self) -> Vec<JSONResponse> {
+        let mut users: Vec<_> = self.users.iter().collect();
+        users.sort_by_key(|(name, _)| *name);
+
+        users
+            .into_iter()
+            .map(|(name, user)| {
+                let elapsed = user.received.elapsed().as_secs();
+                let mut status = user.status.clone();
+                // peers send whatever they like
+                status.since = status.since.saturating_add(elapsed);
+                status.expires_in = status.expires_in.map(|left| left.saturating_sub(elapsed));
+                JSONResponse::User {
+                    username: name.to_owned(),
+                    status,
+                    profile: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The peer whose shared key is `key`.
+pub fn find<'a>(peers: &'a [PeerConfig], key: &str) -> Option<&'a PeerConfig> {
+    peers.iter().find(|peer| peer.key == key)
+}
+
+/// Pushes every presence change of a local user anyone may list to the peers.
+pub async fn push_worker(state: Arc<Mutex<Fingr>>) {
+    let mut updates = state.lock().await.presence.subscribe();
+
+    loop {
+        let presence = match updates.recv().await {
+            Ok(presence) => presence,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("peers missed {} presence changes", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        // read the peers for every change so a reload picks up new ones
+        let peers = {
+            let lock = state.lock().await;
+            // peers list what they get to anyone
+            let listed = lock
+                .users
+                .get(&presence.username)
+                .is_some_and(|user| user.listed_for(None));
+            if !listed {
+                continue;
+            }
+            lock.config.peers.clone()
+        };
+        for peer in peers {
+            tokio::spawn(push(peer, presence.clone()));
+        }
+    }
+}
+
+async fn push(peer: PeerConfig, presence: Presence) {
+    let status = presence.status;
+    let online = status.online.to_string();
+    let availability = status.presence.to_string();
+    let since = status.since.to_string();
+    let mut params = vec![
+        ("user", presence.username.as_str()),
+        ("online", online.as_str()),
+        ("presence", availability.as_str()),
+        ("since", since.as_str()),
+    ];
+    if let Some(text) = &status.text {
+        params.push(("status", text));
+    }
+
+    let client = Client::new(&peer.host).header(KEY_HEADER, &peer.key);
+    match timeout(TIMEOUT, client.get("peer", &params)).await {
+        Ok(Ok(JSONResponse::Error(e))) => warn!(host = ?peer.host, "peer refused update: {}", e),
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => warn!(host = ?peer.host, "can't push to peer: {}", e),
+        Err(_) => warn!(host = ?peer.host, "peer did not answer in time"),
+    }
+}
+
+/// Pulls the list of every peer every `peer_sync_secs`, starting right away.
+pub async fn sync_worker(state: Arc<Mutex<Fingr>>) {
+    loop {
+        let (peers, interval) = {
+            let lock = state.lock().await;
+            (lock.config.peers.clone(), lock.config.peer_sync_secs)
+        };
+
+        for peer in &peers {
+            match pull(peer).await {
+                Ok(users) => {
+                    let mut lock = state.lock().await;
+                    lock.remote.replace(&peer.host, users);
+                    lock.list_cache = None;
+                }
+                Err(e) => warn!(host = ?peer.host, "can't sync with peer: {}", e),
+            }
+        }
+
+        {
+            let hosts: Vec<&str> = peers.iter().map(|peer| peer.host.as_str()).collect();
+            let mut lock = state.lock().await;
+            lock.remote.retain_hosts(&hosts);
+            lock.list_cache = None;
+        }
+
+        sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+/// The local users of a peer, leaving out the ones it has from its own peers.
+async fn pull(peer: &PeerConfig) -> Result<Vec<(String, JSONStatus)>> {
+    let list = timeout(TIMEOUT, Client::new(&peer.host).list())
+        .await
+        .map_err(|_| anyhow!("no answer in time"))??;
+
+    let JSONResponse::List(list) = list else {
+        return Err(anyhow!("unexpected answer to list: {}", list));
+    };
+
+    Ok(list
+        .into_iter()
+        .filter_map(|user| match user {
+            JSONResponse::User {
+                username, status, ..
+            } if !username.contains('@') => Some((username, status)),
+            _ => None,
+        })
+        .collect())
+}
@@ -1,5 +1,15 @@
+pub use crate::prelude::*;
 pub use anyhow::{Result, anyhow};
-pub use tracing::{debug, error, info, subscriber, warn};
+
+/// `e` as the error the library's public api fails with, keeping anyhow to the server itself.
+pub fn server_error(e: anyhow::Error) -> FngrError {
+    FngrError::Server(e.into())
+}
+
+/// `e` as the error of a store.
+pub fn storage(e: anyhow::Error) -> FngrError {
+    FngrError::Storage(e.into())
+}
 
 /// Helper function to return an error if path is relative using `Path::is_relative`. The check is disabled in a debug binary.
 #[inline]
@@ -0,0 +1,137 @@
+//! Giving up root once the listeners are bound, for a server started as root to bind port 79.
+
+use std::{
+    ffi::{CString, c_char, c_int},
+    io,
+};
+
+use crate::server::prelude::*;
+
+// the buffer for a passwd or group entry doesn't grow past this
+const MAX_BUFFER: usize = 1 << 20;
+
+/// Switches to `user`, and to `group` or else the user's own group, if we run as root. Either
+/// can be a name or a numeric id.
+pub fn drop_to(user: &str, group: Option<&str>) -> Result<()> {
+    // SAFETY: geteuid has no preconditions and can't fail
+    if unsafe { libc::geteuid() } != 0 {
+        warn!("not running as root, so not switching to user {}", user);
+        return Ok(());
+    }
+
+    let (uid, user_gid, name) = lookup_user(user)?;
+    let gid = match (group, user_gid) {
+        (Some(group), _) => lookup_group(group)?,
+        (None, Some(gid)) => gid,
+        (None, None) => {
+            return Err(anyhow!(
+                "user {} has no passwd entry, set run_as_group as well",
+                user
+            ));
+        }
+    };
+
+    // the supplementary groups and the group need root, so they go before the user
+    // SAFETY: `name` is a nul terminated string and `gid` outlives the setgroups call
+    let groups = match &name {
+        Some(name) => unsafe { libc::initgroups(name.as_ptr(), gid as _) },
+        None => unsafe { libc::setgroups(1, &gid) },
+    };
+    if groups != 0 {
+        return Err(anyhow!(
+            "can't set supplementary groups: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    // SAFETY: setgid and setuid only take ids
+    if unsafe { libc::setgid(gid) } != 0 {
+        return Err(anyhow!(
+            "can't switch to group {}: {}",
+            gid,
+            io::Error::last_os_error()
+        ));
+    }
+    if unsafe { libc::setuid(uid) } != 0 {
+        return Err(anyhow!(
+            "can't switch to user {}: {}",
+            uid,
+            io::Error::last_os_error()
+        ));
+    }
+    // SAFETY: as above
+    if uid != 0 && unsafe { libc::setuid(0) } == 0 {
+        return Err(anyhow!(
+            "could become root again after switching to {}",
+            user
+        ));
+    }
+
+    info!("running as user {} and group {}", uid, gid);
+    Ok(())
+}
+
+/// The uid and group of `user`, and its name for its supplementary groups. A numeric id without
+/// a passwd entry has neither.
+fn lookup_user(user: &str) -> Result<(libc::uid_t, Option<libc::gid_t>, Option<CString>)> {
+    let name = CString::new(user)?;
+    // SAFETY: passwd is plain old data, all zeroes is a valid empty one
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    with_buffer(|buffer| unsafe {
+        // SAFETY: every pointer is valid for the call and the buffer is as long as we say
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    })
+    .map_err(|e| anyhow!("can't look up user {}: {}", user, e))?;
+
+    if !result.is_null() {
+        return Ok((passwd.pw_uid, Some(passwd.pw_gid), Some(name)));
+    }
+    match user.parse() {
+        Ok(uid) => Ok((uid, None, None)),
+        Err(_) => Err(anyhow!("there is no user {}", user)),
+    }
+}
+
+fn lookup_group(group: &str) -> Result<libc::gid_t> {
+    let name = CString::new(group)?;
+    // SAFETY: group is plain old data, all zeroes is a valid empty one
+    let mut entry: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    with_buffer(|buffer| unsafe {
+        // SAFETY: every pointer is valid for the call and the buffer is as long as we say
+        libc::getgrnam_r(
+            name.as_ptr(),
+            &mut entry,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    })
+    .map_err(|e| anyhow!("can't look up group {}: {}", group, e))?;
+
+    if !result.is_null() {
+        return Ok(entry.gr_gid);
+    }
+    group
+        .parse()
+        .map_err(|_| anyhow!("there is no group {}", group))
+}
+
+/// Calls `lookup` with a buffer for the strings of an entry, a larger one each time it is too
+/// small.
+fn with_buffer(mut lookup: impl FnMut(&mut [c_char]) -> c_int) -> io::Result<()> {
+    let mut buffer = vec![0; 1024];
+    loop {
+        match lookup(&mut buffer) {
+            0 => return Ok(()),
+            libc::ERANGE if buffer.len() < MAX_BUFFER => buffer.resize(buffer.len() * 2, 0),
+            e => return Err(io::Error::from_raw_os_error(e)),
+        }
+    }
+}
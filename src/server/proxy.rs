@@ -0,0 +1,61 @@
+//! Finding the client behind a reverse proxy, from the headers the proxies we trust add.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::server::ban::IpRange;
+
+/// The client `peer` forwarded the request for, if it is a trusted proxy. The addresses in
+/// `Forwarded`, or else `X-Forwarded-For`, are walked from the last one added, and the first
+/// that isn't a trusted proxy is the client, as anything before it could be made up.
+pub fn client(peer: IpAddr, headers: &HashMap<String, String>, trusted: &[IpRange]) -> IpAddr {
+    let is_trusted = |ip| trusted.iter().any(|range| range.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let forwarded: Vec<_> = match headers.get("forwarded") {
+        Some(value) => value.split(',').map(forwarded_for).collect(),
+        None => match headers.get("x-forwarded-for") {
+            Some(value) => value.split(',').map(parse_ip).collect(),
+            None => vec![],
+        },
+    };
+
+    let mut client = peer;
+    // an address that can't be read ends the walk, the proxy before it is as far as we know
+    for ip in forwarded.into_iter().rev().map_while(|ip| ip) {
+        client = ip;
+        if !is_trusted(ip) {
+            break;
+        }
+    }
+
+    client
+}
+
+/// The `for=` address of one element of a `Forwarded` header.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("for")
+            .then(|| parse_ip(value))
+            .flatten()
+    })
+}
+
+/// An address as proxies write it, maybe quoted, in brackets or with a port.
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+
+    value.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
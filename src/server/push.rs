@@ -0,0 +1,262 @@
+//! Push notifications to users' phones through ntfy or gotify, when someone they follow logs on
+//! or someone fingers them. Users set where they get them with `settings`, and a worker delivers
+//! them in the background.
+
+#[cfg(feature = "webhooks")]
+use std::{net::SocketAddr, time::Duration};
+use std::{str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, mpsc};
+
+#[cfg(feature = "webhooks")]
+use crate::server::forward;
+use crate::server::{Fingr, prelude::*, store::Settings};
+
+// notifications waiting for the worker, more are dropped
+const QUEUE: usize = 256;
+// longest ntfy topic, as ntfy.sh has it
+const MAX_TOPIC: usize = 64;
+#[cfg(feature = "webhooks")]
+const ATTEMPTS: u32 = 5;
+#[cfg(feature = "webhooks")]
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a user is pushed about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushEvent {
+    /// Someone the user follows logged on.
+    Online,
+    /// Someone fingered the user.
+    Finger,
+}
+
+impl FromStr for PushEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "online" => Ok(Self::Online),
+            "finger" => Ok(Self::Finger),
+            _ => Err(format!("push takes online and finger, not '{}'", s)),
+        }
+    }
+}
+
+/// Where a notification goes.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "webhooks"), allow(dead_code))]
+pub enum Target {
+    /// The url of a topic on the configured `ntfy_server`, posted the message as text.
+    Topic(String),
+    /// An ntfy topic url, posted the message as text.
+    Ntfy(String),
+    /// A gotify message url with its app token, posted the message as json.
+    Gotify(String),
+}
+
+#[derive(Debug)]
+#[cfg_attr(not(feature = "webhooks"), allow(dead_code))]
+pub struct Notification {
+    pub target: Target,
+    pub title: String,
+    pub message: String,
+}
+
+pub struct Pusher {
+    queue: mpsc::Sender<Notification>,
+    // taken by the worker when the server starts
+    receiver: Option<mpsc::Receiver<Notification>>,
+}
+
+impl Pusher {
+    pub fn new() -> Self {
+        let (queue, receiver) = mpsc::channel(QUEUE);
+        Self {
+            queue,
+            receiver: Some(receiver),
+        }
+    }
+
+    /// The notifications for the worker, `None` once taken.
+    pub fn take_receiver(&mut self) -> Option<mpsc::Receiver<Notification>> {
+        self.receiver.take()
+    }
+
+    /// Queues `message` for every target in `settings` if the user wants `event`, dropping it when
+    /// the worker is too far behind.
+    pub fn push(&self, settings: &Settings, ntfy_server: &str, event: PushEvent, message: &str) {
+        if !settings.push.contains(&event) {
+            return;
+        }
+
+        for target in targets(settings, ntfy_server) {
+            let notification = Notification {
+                target,
+                title: "fngr".to_owned(),
+                message: message.to_owned(),
+            };
+            if self.queue.try_send(notification).is_err() {
+                warn!("push queue is full, dropping a notification");
+            }
+        }
+    }
+}
+
+/// Where `settings` wants notifications, bare ntfy topics on `ntfy_server`.
+fn targets(settings: &Settings, ntfy_server: &str) -> Vec<Target> {
+    let mut targets = vec![];
+    if let Some(topic) = &settings.ntfy {
+        targets.push(if is_url(topic) {
+            Target::Ntfy(topic.to_owned())
+        } else {
+            Target::Topic(format!("{}/{}", ntfy_server.trim_end_matches('/'), topic))
+        });
+    }
+    if let Some(url) = &settings.gotify {
+        targets.push(Target::Gotify(url.to_owned()));
+    }
+    targets
+}
+
+pub fn is_url(value: &str) -> bool {
+    value.starts_with("https://") || value.starts_with("http://")
+}
+
+/// Whether `topic` is a topic name ntfy takes, or an ntfy url.
+pub fn is_topic(topic: &str) -> bool {
+    is_url(topic)
+        || (topic.len() <= MAX_TOPIC
+            && !topic.is_empty()
+            && topic
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'))
+}
+
+/// Delivers the notifications that come in.
+pub async fn worker(state: Arc<Mutex<Fingr>>) {
+    let Some(mut receiver) = state.lock().await.pusher.take_receiver() else {
+        return;
+    };
+
+    #[cfg(feature = "webhooks")]
+    while let Some(notification) = receiver.recv().await {
+        tokio::spawn(deliver(notification));
+    }
+
+    // settings don't take targets without it, so this is for a config turning them on
+    #[cfg(not(feature = "webhooks"))]
+    while receiver.recv().await.is_some() {
+        warn!("push notifications need a server built with the webhooks feature");
+    }
+}
+
+/// A client for posting to `target`. Urls users gave must resolve to public addresses only, and
+/// the client connects to the one that was checked.
+#[cfg(feature = "webhooks")]
+async fn client(target: &Target) -> Result<reqwest::Client> {
+    let builder = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    let url = match target {
+        // the admin's own server may well be on the local network
+        Target::Topic(_) => return Ok(builder.build()?),
+        Target::Ntfy(url) | Target::Gotify(url) => reqwest::Url::parse(url)?,
+    };
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("{} has no host", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses = forward::resolve(&format!("{}:{}", host, port)).await?;
+    let address: SocketAddr =
+        forward::public(&addresses).ok_or_else(|| anyhow!("{} is not a public address", host))?;
+    Ok(builder
+        .resolve(host.trim_matches(['[', ']']), address)
+        .build()?)
+}
+
+/// Posts `notification`, retrying with exponential backoff until it answers with a 2xx.
+#[cfg(feature = "webhooks")]
+async fn deliver(notification: Notification) {
+    // topics and tokens in the url are secrets, the host is enough for the log
+    let (Target::Topic(url) | Target::Ntfy(url) | Target::Gotify(url)) = &notification.target;
+    let host = url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split(['/', '?']).next())
+        .unwrap_or_default();
+
+    let client = match client(&notification.target).await {
+        Ok(client) => client,
+        Err(e) => {
+            warn!(host, "not pushing: {}", e);
+            return;
+        }
+    };
+
+    for attempt in 1..=ATTEMPTS {
+        let request = match &notification.target {
+            Target::Topic(url) | Target::Ntfy(url) => client
+                .post(url)
+                .header("Title", &notification.title)
+                .body(notification.message.clone()),
+            Target::Gotify(url) => client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(
+                    serde_json::json!({
+                        "title": notification.title,
+                        "message": notification.message,
+                    })
+                    .to_string(),
+                ),
+        };
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => warn!(host, attempt, "push answered {}", response.status()),
+            Err(e) => warn!(host, attempt, "push failed: {}", e),
+        }
+
+        if attempt < ATTEMPTS {
+            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+        }
+    }
+
+    error!(host, "giving up on push after {} attempts", ATTEMPTS);
+}
+
+#[cfg(all(test, feature = "webhooks"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn private_targets_are_refused() {
+        for url in [
+            "http://127.0.0.1/message?token=x",
+            "http://169.254.169.254/latest",
+            "https://10.0.0.1:8443/topic",
+            "http://[::1]/topic",
+            "http://localhost:8080/topic",
+        ] {
+            assert!(
+                client(&Target::Gotify(url.to_owned())).await.is_err(),
+                "{}",
+                url
+            );
+            assert!(
+                client(&Target::Ntfy(url.to_owned())).await.is_err(),
+                "{}",
+                url
+            );
+        }
+        // bare topics go to the configured server, wherever it is
+        assert!(
+            client(&Target::Topic("http://127.0.0.1/topic".to_owned()))
+                .await
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use tokio::time::Instant;
+
+/// Token buckets per client address.
+///
+/// Every bucket starts full with `burst` tokens and refills at `refill` tokens a second.
+pub struct RateLimiter {
+    buckets: HashMap<IpAddr, Bucket>,
+    burst: f64,
+    refill: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// A `burst` of 0 turns rate limiting off.
+    pub fn new(burst: u32, refill: f64) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            burst: burst as f64,
+            refill,
+        }
+    }
+
+    /// Changes the limits, keeping the buckets clients already have.
+    pub fn set_limits(&mut self, burst: u32, refill: f64) {
+        self.burst = burst as f64;
+        self.refill = refill;
+    }
+
+    fn enabled(&self) -> bool {
+        self.burst > 0.0
+    }
+
+    /// Takes `cost` tokens from the bucket of `ip`. Returns the seconds to wait if there are not enough.
+    pub fn take(&mut self, ip: IpAddr, cost: f64) -> Result<(), u64> {
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let bucket = self.buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.refill;
+        bucket.tokens = (bucket.tokens + refilled).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(((cost - bucket.tokens) / self.refill).ceil() as u64)
+        }
+    }
+
+    /// Takes `cost` tokens even if that leaves the bucket in debt, for penalising after the fact.
+    pub fn charge(&mut self, ip: IpAddr, cost: f64) {
+        if self.take(ip, cost).is_err()
+            && let Some(bucket) = self.buckets.get_mut(&ip)
+        {
+            bucket.tokens -= cost;
+        }
+    }
+
+    /// Forgets buckets that have refilled completely.
+    pub fn prune(&mut self) {
+        let now = Instant::now();
+        let (burst, refill) = (self.burst, self.refill);
+        self.buckets.retain(|_, bucket| {
+            bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * refill < burst
+        });
+    }
+}
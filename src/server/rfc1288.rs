@@ -0,0 +1,316 @@
+//! Plain text finger protocol (RFC 1288) so stock `finger user@host` clients work.
+
+use std::{net::IpAddr, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    time::{Instant, timeout},
+};
+
+use crate::networking::{Availability, JSONResponse, JSONStatus};
+use crate::server::{
+    Fingr, REQUEST_COST, forward,
+    prelude::*,
+    userlist::{self, User},
+    utmp::{self, SystemUsers},
+};
+
+// a query is a username and an optional `/W`, anything longer is not a finger query
+const MAX_QUERY: u64 = 512;
+
+pub async fn serve(state: Arc<Mutex<Fingr>>, listener: TcpListener) {
+    let connections = state.lock().await.connections.clone();
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                let Ok(permit) = connections.clone().try_acquire_owned() else {
+                    warn!(?addr, "too many connections, dropping it");
+                    continue;
+                };
+                info!(?addr, "finger connection...");
+                let state = state.clone();
+
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    if let Err(e) = answer(state, stream).await {
+                        error!("{}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        }
+    }
+}
+
+async fn answer(state: Arc<Mutex<Fingr>>, stream: TcpStream) -> Result<()> {
+    let ip = stream.peer_addr()?.ip();
+    if state
+        .lock()
+        .await
+        .rate_limiter
+        .take(ip, REQUEST_COST)
+        .is_err()
+    {
+        warn!(?ip, "rate limited");
+        return Ok(());
+    }
+
+    let (read_timeout, write_timeout) = {
+        let lock = state.lock().await;
+        (lock.config.read_timeout(), lock.config.write_timeout())
+    };
+
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    timeout(
+        read_timeout,
+        (&mut stream).take(MAX_QUERY).read_line(&mut line),
+    )
+    .await
+    .map_err(|_| anyhow!("timed out waiting for a finger query from {}", ip))??;
+
+    let output = query(&state, &line, Some(ip)).await;
+
+    let stream = stream.get_mut();
+    timeout(write_timeout, async {
+        stream.write_all(output.as_bytes()).await?;
+        stream.shutdown().await
+    })
+    .await
+    .map_err(|_| anyhow!("timed out answering a finger query from {}", ip))??;
+    Ok(())
+}
+
+/// The answer to the finger query `line`, from `ip` if it came over the network. Queries from
+/// chat bridges are answered like anonymous ones.
+pub async fn query(state: &Arc<Mutex<Fingr>>, line: &str, ip: Option<IpAddr>) -> String {
+    let line = line.trim();
+    // `/W` only asks for a more verbose answer, which we always give
+    let line = line
+        .strip_prefix("/W")
+        .or(line.strip_prefix("/w"))
+        .unwrap_or(line)
+        .trim();
+
+    let mut lock = state.lock().await;
+    let mut line = line;
+    if let Some((query, host)) = forward::split(line) {
+        if forward::is_local(host, &lock.config.public_url) {
+            line = query;
+        } else {
+            if !lock.config.finger_forwarding {
+                return "finger: forwarding is disabled on this server\r\n".to_owned();
+            }
+            if line.matches('@').count() as u32 > lock.config.max_finger_hops {
+                return "finger: too many hosts\r\n".to_owned();
+            }
+            drop(lock);
+
+            let address = match forward::resolve(host)
+                .await
+                .map(|addresses| forward::public(&addresses))
+            {
+                Ok(Some(address)) => address,
+                Ok(None) => return format!("finger: {} is not a public address\r\n", host),
+                Err(e) => return format!("finger: {}: {}\r\n", host, e),
+            };
+            info!(?query, ?host, "forwarding finger");
+            return match forward::finger(query, host, address, 1).await {
+                Ok(response) => response.to_text().replace('\n', "\r\n"),
+                Err(e) => format!("finger: {}: {}\r\n", host, e),
+            };
+        }
+    }
+
+    let ip = ip.filter(|_| lock.config.log_lookup_ips);
+    let max = lock.config.max_lookups;
+    let system_users = lock.config.system_users;
+
+    if line.is_empty() {
+        let mut users: Vec<JSONResponse> = match system_users {
+            SystemUsers::Only => vec![],
+            _ => lock
+                .users
+                .values()
+                .filter(|user| user.listed_for(None))
+                .map(Into::into)
+                .collect(),
+        };
+        if system_users != SystemUsers::Off {
+            match utmp::users(&lock.config.utmp).await {
+                Ok(system) => users.extend(system.into_iter().filter(|user| {
+                    !matches!(user, JSONResponse::User { username, .. } if lock.users.contains_key(username))
+                        || system_users == SystemUsers::Only
+                })),
+                Err(e) => error!("{}", e),
+            }
+        }
+        return render_list(&users);
+    }
+
+    if userlist::is_glob(line) {
+        let users: Vec<JSONResponse> = lock
+            .users
+            .glob(line, None)
+            .into_iter()
+            .take(lock.config.max_glob_results)
+            .map(Into::into)
+            .collect();
+        if users.is_empty() {
+            return format!("finger: {}: no such user.\r\n", line);
+        }
+        return render_list(&users);
+    }
+
+    let mail = lock.mail(line);
+    if system_users == SystemUsers::Only
+        || (system_users == SystemUsers::Merge && !lock.users.contains_key(line))
+    {
+        let (utmp, wtmp) = (lock.config.utmp.clone(), lock.config.wtmp.clone());
+        drop(lock);
+        return match utmp::user(&utmp, &wtmp, line).await {
+            Ok(Some(JSONResponse::User {
+                username, status, ..
+            })) => render_system_user(&username, &status, mail.as_deref()),
+            Ok(_) => format!("finger: {}: no such user.\r\n", line),
+            Err(e) => {
+                error!("{}", e);
+                format!("finger: {}: can't look up system users.\r\n", line)
+            }
+        };
+    }
+
+    match lock.users.get_mut(line) {
+        Some(user) if user.blocks(None) || !user.allows(None) => {
+            format!("finger: {}: only their friends can finger them.\r\n", line)
+        }
+        Some(user) => {
+            user.add_log("anonymous", ip, max);
+            let rendered = render_user(user, mail.as_deref());
+            lock.fingered(line, None);
+            rendered
+        }
+        None => format!("finger: {}: no such user.\r\n", line),
+    }
+}
+
+fn render_list(users: &[JSONResponse]) -> String {
+    let mut output = "Login            Status   Since    Text\r\n".to_owned();
+
+    for user in users {
+        let JSONResponse::User {
+            username, status, ..
+        } = user
+        else {
+            continue;
+        };
+        let line = format!(
+            "{:<16} {:<8} {:<8} {}",
+            username,
+            status.presence.to_string(),
+            duration(status.since),
+            status.text.as_deref().unwrap_or("")
+        );
+        output.push_str(line.trim_end());
+        output.push_str("\r\n");
+    }
+
+    output
+}
+
+/// A user of the system, whose status says which terminals they are on.
+fn render_system_user(username: &str, status: &JSONStatus, mail: Option<&str>) -> String {
+    let mut output = format!("Login: {}\r\n", username);
+    if status.online {
+        output.push_str(&format!("Online for {}\r\n", duration(status.since)));
+    } else if let Some(last) = status
+        .last_seen
+        .as_deref()
+        .and_then(|last| DateTime::parse_from_rfc3339(last).ok())
+    {
+        let last = last.with_timezone(&Utc).format("%a %b %e %H:%M %Y (UTC)");
+        output.push_str(&format!("Last login {}\r\n", last));
+    }
+    if let Some(text) = &status.text {
+        output.push_str(&format!("Status: {}\r\n", text));
+    }
+    if let Some(mail) = mail {
+        output.push_str(&format!("{}\r\n", mail));
+    }
+    output
+}
+
+fn render_user(user: &User, mail: Option<&str>) -> String {
+    let status = user.status();
+    let since = duration(status.since.elapsed().as_secs());
+
+    let mut output = format!("Login: {}\r\n", user.username());
+    if user.online() {
+        output.push_str(&format!("Online for {}\r\n", since));
+        match status.presence {
+            Availability::Away => output.push_str("Away\r\n"),
+            Availability::DoNotDisturb => output.push_str("Do not disturb\r\n"),
+            _ => {}
+        }
+        if let Some(expires) = status.expires {
+            let left = expires.saturating_duration_since(Instant::now()).as_secs();
+            output.push_str(&format!("Goes offline in {}\r\n", duration(left)));
+        }
+    } else {
+        output.push_str(&format!("Offline for {}\r\n", since));
+        if let Some(last_seen) = status.last_seen {
+            let last_seen = DateTime::<Utc>::from(last_seen).format("%a %b %e %H:%M %Y (UTC)");
+            output.push_str(&format!("Last seen {}\r\n", last_seen));
+        }
+    }
+
+    match &status.text {
+        Some(text) => output.push_str(&format!("Status: {}\r\n", text)),
+        None => output.push_str("No status.\r\n"),
+    }
+
+    let profile = user.profile();
+    if let Some(pronouns) = &profile.pronouns {
+        output.push_str(&format!("Pronouns: {}\r\n", pronouns));
+    }
+    if let Some(location) = &profile.location {
+        output.push_str(&format!("Location: {}\r\n", location));
+    }
+    if let Some(time) = user.local_time() {
+        output.push_str(&format!(
+            "It is {} where {} is.\r\n",
+            time.format("%H:%M"),
+            user.username()
+        ));
+    }
+    if let Some(website) = &profile.website {
+        output.push_str(&format!("Website: {}\r\n", website));
+    }
+    if let Some(social) = &profile.social {
+        output.push_str(&format!("Social: {}\r\n", social));
+    }
+    if let Some(bio) = &profile.bio {
+        output.push_str(&format!("Bio: {}\r\n", bio));
+    }
+    if let Some(mail) = mail {
+        output.push_str(&format!("{}\r\n", mail));
+    }
+
+    output
+}
+
+pub(crate) fn duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h{}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
@@ -0,0 +1,66 @@
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Tokens handed out by `login` so later requests don't have to send the key.
+pub struct Sessions {
+    sessions: HashMap<String, Session>,
+    ttl: Duration,
+}
+
+struct Session {
+    username: String,
+    expires: Instant,
+}
+
+impl Sessions {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Changes how long new sessions last.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Starts a session for `username` and returns its token.
+    pub fn create(&mut self, username: &str) -> String {
+        let token = Uuid::from_bytes(rand::random()).simple().to_string();
+        self.sessions.insert(
+            token.to_owned(),
+            Session {
+                username: username.to_owned(),
+                expires: Instant::now() + self.ttl,
+            },
+        );
+        token
+    }
+
+    /// The user a token belongs to, if it hasn't expired.
+    pub fn get(&self, token: &str) -> Option<&str> {
+        self.sessions
+            .get(token)
+            .filter(|session| session.expires > Instant::now())
+            .map(|session| session.username.as_str())
+    }
+
+    /// Ends every session of `username`.
+    pub fn end(&mut self, username: &str) {
+        self.sessions
+            .retain(|_, session| session.username != username);
+    }
+
+    /// How many sessions there are, counting expired ones not pruned yet.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn prune(&mut self) {
+        let now = Instant::now();
+        self.sessions.retain(|_, session| session.expires > now);
+    }
+}
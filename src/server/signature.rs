@@ -0,0 +1,82 @@
+//! Ed25519 signatures, to authenticate without sending a secret that could be read off the wire.
+//!
+//! A signed request has its signature, a unix timestamp and a random nonce in headers. What is
+//! signed is the action, the username, the timestamp and the nonce, each on a line of their own.
+
+use std::{collections::HashMap, time::SystemTime};
+
+use data_encoding::HEXLOWER_PERMISSIVE;
+use ed25519_dalek::{Signature, VerifyingKey};
+use tokio::time::{Duration, Instant};
+
+pub const SIGNATURE_HEADER: &str = "X-Fngr-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Fngr-Timestamp";
+pub const NONCE_HEADER: &str = "X-Fngr-Nonce";
+
+// how far a timestamp may be from our clock, nonces are remembered for long enough to cover it
+const MAX_SKEW: u64 = 300;
+const MAX_NONCE: usize = 64;
+
+/// Parses a hex encoded public key, `None` if it isn't one.
+pub fn parse_key(key: &str) -> Option<VerifyingKey> {
+    let bytes = HEXLOWER_PERMISSIVE.decode(key.trim().as_bytes()).ok()?;
+    VerifyingKey::from_bytes(&bytes.try_into().ok()?).ok()
+}
+
+/// What a client signs for a request.
+pub fn message(action: &str, username: &str, timestamp: &str, nonce: &str) -> String {
+    format!("{}\n{}\n{}\n{}", action, username, timestamp, nonce)
+}
+
+/// Whether `signature`, in hex, is one of `message` by the hex encoded `key`.
+pub fn verify(key: &str, message: &str, signature: &str) -> bool {
+    let Some(key) = parse_key(key) else {
+        return false;
+    };
+    let Some(signature) = HEXLOWER_PERMISSIVE
+        .decode(signature.trim().as_bytes())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+
+    key.verify_strict(message.as_bytes(), &signature).is_ok()
+}
+
+/// Whether `timestamp` is close enough to now.
+pub fn is_fresh(timestamp: &str) -> bool {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    timestamp
+        .trim()
+        .parse::<u64>()
+        .is_ok_and(|timestamp| timestamp.abs_diff(now) <= MAX_SKEW)
+}
+
+/// The nonces of recent signed requests, so none of them can be sent again.
+#[derive(Default)]
+pub struct Nonces {
+    seen: HashMap<(String, String), Instant>,
+}
+
+impl Nonces {
+    /// Remembers `nonce` for `username`, `false` if it was used already or is no good.
+    pub fn insert(&mut self, username: &str, nonce: &str) -> bool {
+        if nonce.is_empty() || nonce.len() > MAX_NONCE {
+            return false;
+        }
+
+        self.seen
+            .insert((username.to_owned(), nonce.to_owned()), Instant::now())
+            .is_none()
+    }
+
+    /// Forgets nonces whose timestamps would be refused anyway.
+    pub fn prune(&mut self) {
+        let max_age = Duration::from_secs(MAX_SKEW * 2);
+        self.seen.retain(|_, seen| seen.elapsed() < max_age);
+    }
+}
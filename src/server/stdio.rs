@@ -0,0 +1,94 @@
+//! Answering a single connection on stdin and stdout, for inetd and for trying requests from a
+//! shell.
+//!
+//! No lock is taken and none of the workers run, so a stdio server shouldn't share its users list
+//! with a running server.
+
+use std::{net::IpAddr, sync::Arc};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, join, stdin, stdout},
+    sync::Mutex,
+};
+
+use crate::server::{Fingr, prelude::*};
+
+/// Answers the requests on stdin until it closes or a response closes the connection.
+pub async fn serve(fingr: Fingr) -> Result<()> {
+    answer(fingr, join(stdin(), stdout()), peer()).await
+}
+
+/// Answers the requests on `stream` as [`serve`] does, which is stdin and stdout outside of tests.
+async fn answer(
+    fingr: Fingr,
+    stream: impl AsyncRead + AsyncWrite + Unpin,
+    peer: Option<IpAddr>,
+) -> Result<()> {
+    let permit = fingr.connections.clone().try_acquire_owned()?;
+    let state = Arc::new(Mutex::new(fingr));
+
+    Fingr::serve_connection(state.clone(), stream, peer, permit).await;
+    state.lock().await.save_statuses().await;
+
+    Ok(())
+}
+
+/// The address of the client, when inetd passed its socket as stdin.
+fn peer() -> Option<IpAddr> {
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+
+        let fd = std::io::stdin().as_fd().try_clone_to_owned().ok()?;
+        std::net::TcpStream::from(fd)
+            .peer_addr()
+            .ok()
+            .map(|addr| addr.ip())
+    }
+
+    #[cfg(not(unix))]
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::{self, TempDir};
+
+    #[tokio::test]
+    async fn requests_are_answered_on_the_stream() {
+        let dir = TempDir::new();
+        let state = testing::fingr(&dir, &[]).await;
+        let key = testing::register(&state, "alice").await;
+        let fingr = Arc::into_inner(state).unwrap().into_inner();
+
+        let input = format!(
+            "GET /login?username=alice&key={} HTTP/1.1\r\n\r\nGET /info HTTP/1.1\r\nConnection: close\r\n\r\n",
+            key
+        );
+        let mut output = Vec::new();
+        answer(fingr, join(input.as_bytes(), &mut output), None)
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let responses: Vec<&str> = output
+            .split("HTTP/1.1 ")
+            .filter(|response| !response.is_empty())
+            .collect();
+        assert_eq!(responses.len(), 2, "{}", output);
+        assert!(responses[0].starts_with("200 OK\r\n"), "{}", output);
+        assert!(responses[0].contains("you are now logged on"), "{}", output);
+        assert!(responses[1].starts_with("200 OK\r\n"), "{}", output);
+        assert!(
+            responses[1].contains("\r\nX-Online-Count: 1\r\n"),
+            "{}",
+            output
+        );
+        assert!(
+            responses[1].contains(env!("CARGO_PKG_VERSION")),
+            "{}",
+            output
+        );
+    }
+}
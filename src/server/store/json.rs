@@ -0,0 +1,397 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use super::{
+    Ban, Loaded, Profile, SavedStatus, Settings, StoredUser, UserStore,
+    legacy::{self, Converted},
+};
+use crate::error::Result;
+use crate::server::prelude::*;
+
+/// Users in a json list of usernames and key hashes, statuses in a separate json file.
+pub struct JsonStore {
+    users_list: PathBuf,
+    status_file: PathBuf,
+    // how many statuses were written, one for each user in a save
+    #[cfg(test)]
+    status_writes: std::sync::atomic::AtomicUsize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct InitialUser {
+    username: String,
+    hash: String,
+    #[serde(default, skip_serializing_if = "Settings::is_default")]
+    settings: Settings,
+    #[serde(default, skip_serializing_if = "Profile::is_empty")]
+    profile: Profile,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ban: Option<Ban>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pending: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    totp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pubkey: Option<String>,
+}
+
+/// An entry of the users list, the ones `load` skips are written back as they were.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    User(Box<InitialUser>),
+    Malformed(serde_json::Value),
+}
+
+impl JsonStore {
+    pub fn new(users_list: PathBuf, status_file: PathBuf) -> Self {
+        Self {
+            users_list,
+            status_file,
+            #[cfg(test)]
+            status_writes: Default::default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn status_writes(&self) -> usize {
+        self.status_writes
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    async fn read_users(&self) -> Result<Vec<Entry>> {
+        let buffer = tokio::fs::read(&self.users_list).await?;
+        Ok(serde_json::from_slice(&buffer)?)
+    }
+
+    async fn write_users(&self, users: &[Entry]) -> Result<()> {
+        let new = serde_json::to_string_pretty(users)?;
+        write_atomic(&self.users_list, new.as_bytes()).await
+    }
+
+    /// Changes the entry of `username` in the users list.
+    async fn update_user(&self, username: &str, f: impl FnOnce(&mut InitialUser)) -> Result<()> {
+        let mut users = self.read_users().await?;
+        let user = users
+            .iter_mut()
+            .find_map(|entry| match entry {
+                Entry::User(user) if user.username == username => Some(user),
+                _ => None,
+            })
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?;
+        f(user);
+        self.write_users(&users).await
+    }
+
+    async fn write_statuses(&self, statuses: &HashMap<String, SavedStatus>) -> Result<()> {
+        let new = serde_json::to_string_pretty(statuses)?;
+        write_atomic(&self.status_file, new.as_bytes()).await
+    }
+
+    async fn read_statuses(&self) -> Result<HashMap<String, SavedStatus>> {
+        is_relative("status file", &self.status_file).map_err(storage)?;
+
+        match tokio::fs::read(&self.status_file).await {
+            Ok(buffer) => Ok(serde_json::from_slice(&buffer)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl UserStore for JsonStore {
+    async fn load(&self) -> Result<Loaded> {
+        info!("loading users from {}", self.users_list.display());
+        is_relative("userlist", &self.users_list).map_err(storage)?;
+
+        if let Some(converted) = migrate(&self.users_list, false).await? {
+            for skipped in &converted.skipped {
+                warn!("left out of the migration: {}", skipped);
+            }
+            info!(
+                "migrated {} users from {}, the old list is kept with a .legacy extension",
+                converted.users.len(),
+                converted.format
+            );
+        }
+
+        let buffer = tokio::fs::read(&self.users_list).await?;
+        let users: Vec<serde_json::Value> = serde_json::from_slice(&buffer)?;
+
+        let mut statuses = self.read_statuses().await.unwrap_or_else(|e| {
+            warn!(
+                "could not restore statuses from {}: {}",
+                self.status_file.display(),
+                e
+            );
+            HashMap::new()
+        });
+
+        let mut loaded = vec![];
+        let mut skipped = 0;
+        for (i, user) in users.into_iter().enumerate() {
+            match serde_json::from_value::<InitialUser>(user) {
+                Ok(user) => loaded.push(StoredUser {
+                    status: statuses.remove(&user.username),
+                    username: user.username,
+                    hash: user.hash,
+                    settings: user.settings,
+                    profile: user.profile,
+                    ban: user.ban,
+                    pending: user.pending,
+                    totp: user.totp,
+                    pubkey: user.pubkey,
+                }),
+                Err(e) => {
+                    warn!("skipping malformed user entry {}: {}", i, e);
+                    skipped += 1;
+                }
+            }
+        }
+
+        Ok(Loaded {
+            users: loaded,
+            skipped,
+        })
+    }
+
+    async fn register(&self, username: &str, hash: &str, pending: bool) -> Result<()> {
+        let mut users = self.read_users().await?;
+        users.push(Entry::User(Box::new(InitialUser {
+            username: username.to_owned(),
+            hash: hash.to_owned(),
+            settings: Settings::default(),
+            profile: Profile::default(),
+            ban: None,
+            pending,
+            totp: None,
+            pubkey: None,
+        })));
+        self.write_users(&users).await
+    }
+
+    async fn approve(&self, username: &str) -> Result<()> {
+        self.update_user(username, |user| user.pending = false)
+            .await
+    }
+
+    async fn remove(&self, username: &str) -> Result<()> {
+        let mut users = self.read_users().await?;
+        users.retain(|entry| !matches!(entry, Entry::User(user) if user.username == username));
+        self.write_users(&users).await?;
+
+        // a later user of the same name starts without it
+        let mut statuses = self.read_statuses().await?;
+        if statuses.remove(username).is_some() {
+            self.write_statuses(&statuses).await?;
+        }
+        Ok(())
+    }
+
+    async fn update_hash(&self, username: &str, hash: &str) -> Result<()> {
+        self.update_user(username, |user| user.hash = hash.to_owned())
+            .await
+    }
+
+    async fn update_ban(&self, username: &str, ban: Option<&Ban>) -> Result<()> {
+        self.update_user(username, |user| user.ban = ban.cloned())
+            .await
+    }
+
+    async fn update_totp(&self, username: &str, secret: Option<&str>) -> Result<()> {
+        self.update_user(username, |user| user.totp = secret.map(str::to_owned))
+            .await
+    }
+
+    async fn update_pubkey(&self, username: &str, pubkey: Option<&str>) -> Result<()> {
+        self.update_user(username, |user| user.pubkey = pubkey.map(str::to_owned))
+            .await
+    }
+
+    async fn update_settings(&self, username: &str, settings: &Settings) -> Result<()> {
+        self.update_user(username, |user| user.settings = settings.to_owned())
+            .await
+    }
+
+    async fn update_profile(&self, username: &str, profile: &Profile) -> Result<()> {
+        self.update_user(username, |user| user.profile = profile.to_owned())
+            .await
+    }
+
+    async fn update_status(&self, username: &str, status: &SavedStatus) -> Result<()> {
+        self.save(&HashMap::from([(username.to_owned(), status.to_owned())]))
+            .await
+    }
+
+    /// The file holds every status, so the ones saved are merged into what it has.
+    async fn save(&self, statuses: &HashMap<String, SavedStatus>) -> Result<()> {
+        let mut saved = self.read_statuses().await?;
+        saved.extend(statuses.iter().map(|(k, v)| (k.to_owned(), v.to_owned())));
+        #[cfg(test)]
+        self.status_writes
+            .fetch_add(statuses.len(), std::sync::atomic::Ordering::Relaxed);
+        self.write_statuses(&saved).await
+    }
+}
+
+/// Converts the users list at `path` from the format of an older server, keeping the original
+/// next to it with a `.legacy` extension. `None` if it isn't in an old format. With `dry_run`
+/// nothing is written.
+pub async fn migrate(path: &Path, dry_run: bool) -> Result<Option<Converted>> {
+    let buffer = tokio::fs::read(path).await?;
+    let Some(converted) = legacy::convert(&buffer) else {
+        return Ok(None);
+    };
+    if converted.users.is_empty() {
+        return Err(FngrError::Storage(
+            format!(
+                "{} is a users list of an older server, but none of its users can be migrated",
+                path.display()
+            )
+            .into(),
+        ));
+    }
+    if dry_run {
+        return Ok(Some(converted));
+    }
+
+    let backup = path.with_extension("legacy");
+    if tokio::fs::try_exists(&backup).await? {
+        return Err(FngrError::Storage(
+            format!(
+                "{} exists already, move it out of the way to migrate {}",
+                backup.display(),
+                path.display()
+            )
+            .into(),
+        ));
+    }
+    tokio::fs::copy(path, &backup).await?;
+
+    let users: Vec<InitialUser> = converted
+        .users
+        .iter()
+        .map(|(username, hash)| InitialUser {
+            username: username.to_owned(),
+            hash: hash.to_owned(),
+            settings: Settings::default(),
+            profile: Profile::default(),
+            ban: None,
+            pending: false,
+            totp: None,
+            pubkey: None,
+        })
+        .collect();
+    write_atomic(path, serde_json::to_string_pretty(&users)?.as_bytes()).await?;
+
+    Ok(Some(converted))
+}
+
+/// Replaces `path` with `contents` so a crash leaves either the old or the new file, never half of one.
+///
+/// The contents go to a temporary file next to `path` which is synced and then renamed over it.
+pub async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| FngrError::Storage(format!("'{}' is not a file", path.display()).into()))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let mut file = File::create(&tmp).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp, path).await?;
+
+    // the rename itself only survives a crash once the directory is synced
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        File::open(dir).await?.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(debug_assertions)]
+impl Default for InitialUser {
+    fn default() -> Self {
+        Self {
+            username: "pockets".to_owned(),
+            hash: "whaa".to_owned(),
+            settings: Settings::default(),
+            profile: Profile::default(),
+            ban: None,
+            pending: false,
+            totp: None,
+            pubkey: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::server::{
+        store::Store,
+        testing::TempDir,
+        userlist::{UserList, hash_key},
+    };
+
+    #[tokio::test]
+    async fn malformed_users_are_skipped_and_kept() {
+        let dir = TempDir::new();
+        let good = hash_key(uuid::Uuid::from_bytes([1; 16]));
+        dir.users_list(&format!(
+            r#"[
+                {{"username": "alice", "hash": "{good}"}},
+                {{"username": "bob", "hash": "not-a-hash"}},
+                {{"username": 7}},
+                {{"username": "carol", "hash": "{good}"}}
+            ]"#
+        ));
+        let store = || {
+            JsonStore::new(
+                dir.path().join("users.list"),
+                dir.path().join("users.status"),
+            )
+        };
+
+        let users = UserList::load(Store::Json(store()), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(users.contains_key("alice") && users.contains_key("carol"));
+        // bob for his hash, 7 for not being a user at all
+        assert_eq!(users.skipped(), 2);
+
+        // writes go through and leave the entries that couldn't be read as they were
+        store().register("dave", &good, false).await.unwrap();
+        store().remove("alice").await.unwrap();
+        store().update_hash("carol", &good).await.unwrap();
+        let entries: Vec<serde_json::Value> =
+            serde_json::from_slice(&std::fs::read(dir.path().join("users.list")).unwrap()).unwrap();
+        let names: Vec<_> = entries.iter().map(|entry| &entry["username"]).collect();
+        assert_eq!(
+            names,
+            [&json!("bob"), &json!(7), &json!("carol"), &json!("dave")]
+        );
+        assert_eq!(entries[1], json!({"username": 7}));
+    }
+}
@@ -0,0 +1,106 @@
+//! Users lists of older fngr servers, which kept keys as they are instead of their hashes.
+//!
+//! The first servers kept a `username,key` line for each user, later ones a json list of
+//! usernames and keys. Either is converted to usernames and key hashes for [`super::JsonStore`].
+
+use std::{collections::HashSet, fmt::Display};
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::server::userlist::hash_key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyFormat {
+    /// A `username,key` line for each user.
+    Lines,
+    /// A json list of usernames and keys.
+    Keys,
+}
+
+impl Display for LegacyFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LegacyFormat::Lines => "username,key lines",
+            LegacyFormat::Keys => "json with keys",
+        }
+        .fmt(f)
+    }
+}
+
+/// An old users list, converted.
+pub struct Converted {
+    pub format: LegacyFormat,
+    /// Usernames and the hashes of their keys.
+    pub users: Vec<(String, String)>,
+    /// The entries that were left out, and why.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct KeyedUser {
+    username: String,
+    key: String,
+}
+
+/// Converts `buffer` if it is an old users list, `None` if it is in the current format or not a
+/// users list at all.
+pub fn convert(buffer: &[u8]) -> Option<Converted> {
+    let (format, entries) = match serde_json::from_slice::<Vec<KeyedUser>>(buffer) {
+        // an empty list is the current format just as well
+        Ok(users) if users.is_empty() => return None,
+        Ok(users) => (
+            LegacyFormat::Keys,
+            users
+                .into_iter()
+                .map(|user| (user.username, user.key))
+                .collect(),
+        ),
+        Err(_) => (
+            LegacyFormat::Lines,
+            parse_lines(std::str::from_utf8(buffer).ok()?)?,
+        ),
+    };
+
+    let mut seen = HashSet::new();
+    let mut users = vec![];
+    let mut skipped = vec![];
+    for (username, key) in entries {
+        let username = username.trim().to_owned();
+        if username.is_empty() {
+            skipped.push("an entry without a username".to_owned());
+        } else if username.contains([',', '@']) {
+            skipped.push(format!("'{}': usernames can't contain , or @", username));
+        } else if seen.contains(&username) {
+            skipped.push(format!("'{}': listed more than once", username));
+        } else {
+            match key.trim().parse::<Uuid>() {
+                Ok(key) => {
+                    seen.insert(username.clone());
+                    users.push((username, hash_key(key)));
+                }
+                Err(_) => skipped.push(format!("'{}': their key is not a uuid", username)),
+            }
+        }
+    }
+
+    Some(Converted {
+        format,
+        users,
+        skipped,
+    })
+}
+
+/// The usernames and keys of `username,key` lines, `None` unless every line is one.
+fn parse_lines(contents: &str) -> Option<Vec<(String, String)>> {
+    let entries: Vec<_> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_once(',')
+                .map(|(username, key)| (username.to_owned(), key.to_owned()))
+        })
+        .collect::<Option<_>>()?;
+
+    (!entries.is_empty()).then_some(entries)
+}
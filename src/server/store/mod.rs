@@ -0,0 +1,342 @@
+//! Where users and their statuses are kept between restarts.
+//!
+//! A backend implements [`UserStore`] and gets a variant in [`Store`], which the server picks from
+//! its config.
+
+mod json;
+mod legacy;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::collections::HashMap;
+
+use crate::networking::Availability;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::server::{
+    config::{Config, StoreKind},
+    push::PushEvent,
+};
+pub use json::{JsonStore, migrate, write_atomic};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+/// Everything a store could read.
+pub struct Loaded {
+    pub users: Vec<StoredUser>,
+    /// How many entries were left out because the store couldn't make sense of them.
+    pub skipped: usize,
+}
+
+/// A user as it comes out of a store.
+pub struct StoredUser {
+    pub username: String,
+    pub hash: String,
+    pub status: Option<SavedStatus>,
+    pub settings: Settings,
+    pub profile: Profile,
+    pub ban: Option<Ban>,
+    /// Registered but not approved by an admin yet.
+    pub pending: bool,
+    /// The base32 totp secret of users who enrolled a second factor.
+    pub totp: Option<String>,
+    /// The hex ed25519 public key that signed requests of the user are checked with.
+    pub pubkey: Option<String>,
+}
+
+/// What a user can change about how the server treats them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Keeps the user's lookups out of other users' `check`.
+    pub private: bool,
+    /// Replaces the server's `idle_timeout_secs` for this user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_secs: Option<u64>,
+    /// Keeps the user out of `list`.
+    pub hidden: bool,
+    /// Only lets the users in `friends` finger the user.
+    pub friends_only: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub friends: Vec<String>,
+    /// Users who can't finger the user or see them in `list`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked: Vec<String>,
+    /// Users the user follows, shown by `list?following=1`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub following: Vec<String>,
+    /// An ntfy topic, or the url of one, to push notifications to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ntfy: Option<String>,
+    /// A gotify message url with an app token, to push notifications to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gotify: Option<String>,
+    /// What the user is pushed about.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub push: Vec<PushEvent>,
+}
+
+impl Settings {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What a user tells others about themselves.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    pub bio: Option<String>,
+    pub website: Option<String>,
+    pub social: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pronouns: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// IANA name like `Europe/Berlin`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Url of an avatar hosted elsewhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// Content type of an avatar uploaded to the server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_type: Option<String>,
+}
+
+impl Profile {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Why and for how long a user is kept out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ban {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Unix timestamp a suspension ends at, a ban without one is for good.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+}
+
+/// What is kept of a user's status across restarts, with times as unix timestamps.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedStatus {
+    pub online: bool,
+    /// Missing from statuses saved before there were presences, see `online` then.
+    #[serde(default)]
+    pub presence: Option<Availability>,
+    pub text: Option<String>,
+    pub since: u64,
+    /// When an online user is due to be set offline.
+    pub expires: Option<u64>,
+    pub last_seen: Option<u64>,
+    #[serde(default, skip_serializing_if = "Counters::is_empty")]
+    pub counters: Counters,
+}
+
+/// How often a user was fingered, logged on and online, kept along with their status.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Counters {
+    pub fingered: u64,
+    /// Fingers during `week`.
+    pub fingered_week: u64,
+    /// Weeks since the unix epoch, starting on mondays in UTC.
+    pub week: u64,
+    pub logins: u64,
+    /// Seconds online, up to the last time the user went offline.
+    pub online_secs: u64,
+}
+
+impl Counters {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub trait UserStore {
+    /// Every stored user, entries the store can't make sense of are skipped.
+    fn load(&self) -> impl Future<Output = Result<Loaded>> + Send;
+    fn register(
+        &self,
+        username: &str,
+        hash: &str,
+        pending: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn approve(&self, username: &str) -> impl Future<Output = Result<()>> + Send;
+    fn remove(&self, username: &str) -> impl Future<Output = Result<()>> + Send;
+    fn update_hash(&self, username: &str, hash: &str) -> impl Future<Output = Result<()>> + Send;
+    /// Bans `username`, or lifts their ban with `None`.
+    fn update_ban(
+        &self,
+        username: &str,
+        ban: Option<&Ban>,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn update_totp(
+        &self,
+        username: &str,
+        secret: Option<&str>,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn update_pubkey(
+        &self,
+        username: &str,
+        pubkey: Option<&str>,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn update_settings(
+        &self,
+        username: &str,
+        settings: &Settings,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn update_profile(
+        &self,
+        username: &str,
+        profile: &Profile,
+    ) -> impl Future<Output = Result<()>> + Send;
+    fn update_status(
+        &self,
+        username: &str,
+        status: &SavedStatus,
+    ) -> impl Future<Output = Result<()>> + Send;
+    /// Writes the statuses in `statuses`, leaving the ones of other users as they are.
+    fn save(
+        &self,
+        statuses: &HashMap<String, SavedStatus>,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+pub enum Store {
+    Json(JsonStore),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteStore),
+}
+
+impl Store {
+    /// Opens the store `config` asks for.
+    pub fn open(config: &Config) -> Result<Self> {
+        match config.store {
+            StoreKind::Json => Ok(Store::Json(JsonStore::new(
+                config.users_list.clone(),
+                config.status_file.clone(),
+            ))),
+            #[cfg(feature = "sqlite")]
+            StoreKind::Sqlite => Ok(Store::Sqlite(SqliteStore::open(&config.database)?)),
+            #[cfg(not(feature = "sqlite"))]
+            StoreKind::Sqlite => Err(crate::FngrError::Storage(
+                "the sqlite store needs a server built with the sqlite feature".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Store {
+    /// How many statuses were written to the store.
+    pub fn status_writes(&self) -> usize {
+        match self {
+            Store::Json(store) => store.status_writes(),
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(_) => unimplemented!("only the json store counts its writes"),
+        }
+    }
+}
+
+impl UserStore for Store {
+    async fn load(&self) -> Result<Loaded> {
+        match self {
+            Store::Json(store) => store.load().await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.load().await,
+        }
+    }
+
+    async fn register(&self, username: &str, hash: &str, pending: bool) -> Result<()> {
+        match self {
+            Store::Json(store) => store.register(username, hash, pending).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.register(username, hash, pending).await,
+        }
+    }
+
+    async fn approve(&self, username: &str) -> Result<()> {
+        match self {
+            Store::Json(store) => store.approve(username).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.approve(username).await,
+        }
+    }
+
+    async fn remove(&self, username: &str) -> Result<()> {
+        match self {
+            Store::Json(store) => store.remove(username).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.remove(username).await,
+        }
+    }
+
+    async fn update_hash(&self, username: &str, hash: &str) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_hash(username, hash).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_hash(username, hash).await,
+        }
+    }
+
+    async fn update_ban(&self, username: &str, ban: Option<&Ban>) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_ban(username, ban).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_ban(username, ban).await,
+        }
+    }
+
+    async fn update_totp(&self, username: &str, secret: Option<&str>) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_totp(username, secret).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_totp(username, secret).await,
+        }
+    }
+
+    async fn update_pubkey(&self, username: &str, pubkey: Option<&str>) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_pubkey(username, pubkey).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_pubkey(username, pubkey).await,
+        }
+    }
+
+    async fn update_settings(&self, username: &str, settings: &Settings) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_settings(username, settings).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_settings(username, settings).await,
+        }
+    }
+
+    async fn update_profile(&self, username: &str, profile: &Profile) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_profile(username, profile).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_profile(username, profile).await,
+        }
+    }
+
+    async fn update_status(&self, username: &str, status: &SavedStatus) -> Result<()> {
+        match self {
+            Store::Json(store) => store.update_status(username, status).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.update_status(username, status).await,
+        }
+    }
+
+    async fn save(&self, statuses: &HashMap<String, SavedStatus>) -> Result<()> {
+        match self {
+            Store::Json(store) => store.save(statuses).await,
+            #[cfg(feature = "sqlite")]
+            Store::Sqlite(store) => store.save(statuses).await,
+        }
+    }
+}
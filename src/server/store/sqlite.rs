@@ -0,0 +1,359 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use rusqlite::{Connection, params};
+
+use super::{Ban, Loaded, Profile, SavedStatus, Settings, StoredUser, UserStore};
+use crate::error::Result;
+use crate::server::prelude::*;
+
+/// Users and statuses in a SQLite database.
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+// each entry moves the schema up one version, sqlite's `user_version` records how far a database is
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE users (
+        username TEXT PRIMARY KEY NOT NULL,
+        hash TEXT NOT NULL
+    );
+    CREATE TABLE statuses (
+        username TEXT PRIMARY KEY NOT NULL REFERENCES users (username) ON DELETE CASCADE,
+        online INTEGER NOT NULL,
+        text TEXT,
+        since INTEGER NOT NULL,
+        bumped INTEGER
+    );
+",
+    // settings are json so adding one doesn't need a migration
+    "ALTER TABLE users ADD COLUMN settings TEXT NOT NULL DEFAULT '{}';",
+    // online users restored without a deadline get a fresh one
+    "
+    ALTER TABLE statuses ADD COLUMN expires INTEGER;
+    ALTER TABLE statuses DROP COLUMN bumped;
+",
+    "ALTER TABLE statuses ADD COLUMN last_seen INTEGER;",
+    "ALTER TABLE users ADD COLUMN profile TEXT NOT NULL DEFAULT '{}';",
+    // json like settings, `NULL` when the user isn't banned
+    "ALTER TABLE users ADD COLUMN ban TEXT;",
+    "ALTER TABLE users ADD COLUMN pending INTEGER NOT NULL DEFAULT 0;",
+    "ALTER TABLE users ADD COLUMN totp TEXT;",
+    "ALTER TABLE users ADD COLUMN pubkey TEXT;",
+    // `NULL` for statuses saved before there were presences
+    "ALTER TABLE statuses ADD COLUMN presence TEXT;",
+    // json like settings
+    "ALTER TABLE statuses ADD COLUMN counters TEXT NOT NULL DEFAULT '{}';",
+];
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it and bringing its schema up to date as needed.
+    pub fn open(path: &Path) -> Result<Self> {
+        info!("opening database {}", path.display());
+        is_relative("database", path).map_err(storage)?;
+
+        let mut conn = Connection::open(path).map_err(|e| FngrError::Storage(e.into()))?;
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|e| FngrError::Storage(e.into()))?;
+        migrate(&mut conn).map_err(storage)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` on the connection without blocking the runtime.
+    async fn with<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| anyhow!("database connection is poisoned"))?;
+            f(&mut conn)
+        })
+        .await
+        .map_err(|e| FngrError::Storage(e.into()))?
+        .map_err(storage)
+    }
+}
+
+fn migrate(conn: &mut Connection) -> anyhow::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(anyhow!(
+            "database schema version {} is newer than this server supports",
+            version
+        ));
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+        info!("migrated database to schema version {}", i + 1);
+    }
+
+    Ok(())
+}
+
+const UPSERT_STATUS: &str = "
+    INSERT INTO statuses (username, online, text, since, expires, last_seen, presence, counters)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+    ON CONFLICT (username) DO UPDATE SET
+        online = excluded.online, text = excluded.text, since = excluded.since,
+        expires = excluded.expires, last_seen = excluded.last_seen, presence = excluded.presence,
+        counters = excluded.counters
+";
+
+impl UserStore for SqliteStore {
+    async fn load(&self) -> Result<Loaded> {
+        self.with(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT username, hash, online, text, since, expires, settings, last_seen, profile, ban,
+                    pending, totp, pubkey, presence, counters
+                 FROM users LEFT JOIN statuses USING (username)",
+            )?;
+
+            let users = stmt
+                .query_map([], |row| {
+                    let online: Option<bool> = row.get(2)?;
+                    let status = match online {
+                        Some(online) => Some(SavedStatus {
+                            online,
+                            text: row.get(3)?,
+                            since: row.get(4)?,
+                            expires: row.get(5)?,
+                            last_seen: row.get(7)?,
+                            presence: row
+                                .get::<_, Option<String>>(13)?
+                                .and_then(|presence| presence.parse().ok()),
+                            counters: row
+                                .get::<_, Option<String>>(14)?
+                                .and_then(|counters| serde_json::from_str(&counters).ok())
+                                .unwrap_or_default(),
+                        }),
+                        None => None,
+                    };
+
+                    let settings: String = row.get(6)?;
+                    let profile: String = row.get(8)?;
+                    let ban: Option<String> = row.get(9)?;
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get(1)?,
+                        status,
+                        settings,
+                        profile,
+                        ban,
+                        row.get::<_, bool>(10)?,
+                        row.get::<_, Option<String>>(11)?,
+                        row.get::<_, Option<String>>(12)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let users = users
+                .into_iter()
+                .map(|(username, hash, status, settings, profile, ban, pending, totp, pubkey)| {
+                    let settings = serde_json::from_str(&settings).unwrap_or_else(|e| {
+                        warn!("ignoring malformed settings of '{}': {}", username, e);
+                        Settings::default()
+                    });
+                    let profile = serde_json::from_str(&profile).unwrap_or_else(|e| {
+                        warn!("ignoring malformed profile of '{}': {}", username, e);
+                        Profile::default()
+                    });
+                    let ban = ban.and_then(|ban| {
+                        serde_json::from_str(&ban)
+                            .inspect_err(|e| warn!("ignoring malformed ban of '{}': {}", username, e))
+                            .ok()
+                    });
+                    StoredUser {
+                        username,
+                        hash,
+                        status,
+                        settings,
+                        profile,
+                        ban,
+                        pending,
+                        totp,
+                        pubkey,
+                    }
+                })
+                .collect();
+
+            // rows always have a username and a hash, the rest falls back to defaults
+            Ok(Loaded { users, skipped: 0 })
+        })
+        .await
+    }
+
+    async fn register(&self, username: &str, hash: &str, pending: bool) -> Result<()> {
+        let (username, hash) = (username.to_owned(), hash.to_owned());
+        self.with(move |conn| {
+            conn.execute(
+                "INSERT INTO users (username, hash, pending) VALUES (?1, ?2, ?3)",
+                params![username, hash, pending],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn approve(&self, username: &str) -> Result<()> {
+        let username = username.to_owned();
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET pending = 0 WHERE username = ?1",
+                params![username],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove(&self, username: &str) -> Result<()> {
+        let username = username.to_owned();
+        self.with(move |conn| {
+            conn.execute("DELETE FROM users WHERE username = ?1", params![username])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_hash(&self, username: &str, hash: &str) -> Result<()> {
+        let (username, hash) = (username.to_owned(), hash.to_owned());
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET hash = ?2 WHERE username = ?1",
+                params![username, hash],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_ban(&self, username: &str, ban: Option<&Ban>) -> Result<()> {
+        let username = username.to_owned();
+        let ban = ban.map(serde_json::to_string).transpose()?;
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET ban = ?2 WHERE username = ?1",
+                params![username, ban],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_totp(&self, username: &str, secret: Option<&str>) -> Result<()> {
+        let (username, secret) = (username.to_owned(), secret.map(str::to_owned));
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET totp = ?2 WHERE username = ?1",
+                params![username, secret],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_pubkey(&self, username: &str, pubkey: Option<&str>) -> Result<()> {
+        let (username, pubkey) = (username.to_owned(), pubkey.map(str::to_owned));
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET pubkey = ?2 WHERE username = ?1",
+                params![username, pubkey],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_settings(&self, username: &str, settings: &Settings) -> Result<()> {
+        let (username, settings) = (username.to_owned(), serde_json::to_string(settings)?);
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET settings = ?2 WHERE username = ?1",
+                params![username, settings],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_profile(&self, username: &str, profile: &Profile) -> Result<()> {
+        let (username, profile) = (username.to_owned(), serde_json::to_string(profile)?);
+        self.with(move |conn| {
+            let updated = conn.execute(
+                "UPDATE users SET profile = ?2 WHERE username = ?1",
+                params![username, profile],
+            )?;
+            if updated == 0 {
+                return Err(anyhow!("unknown user '{}'", username));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_status(&self, username: &str, status: &SavedStatus) -> Result<()> {
+        let (username, status) = (username.to_owned(), status.to_owned());
+        self.with(move |conn| upsert_status(conn, &username, &status))
+            .await
+    }
+
+    /// Only the given rows are written, the same way as [`update_status`](Self::update_status).
+    async fn save(&self, statuses: &HashMap<String, SavedStatus>) -> Result<()> {
+        let statuses = statuses.to_owned();
+        self.with(move |conn| {
+            let tx = conn.transaction()?;
+            for (username, status) in &statuses {
+                upsert_status(&tx, username, status)?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn upsert_status(conn: &Connection, username: &str, status: &SavedStatus) -> anyhow::Result<()> {
+    conn.prepare_cached(UPSERT_STATUS)?.execute(params![
+        username,
+        status.online,
+        status.text,
+        status.since,
+        status.expires,
+        status.last_seen,
+        status.presence.map(|presence| presence.to_string()),
+        serde_json::to_string(&status.counters)?
+    ])?;
+    Ok(())
+}
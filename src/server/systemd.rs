@@ -0,0 +1,183 @@
+//! Running as a systemd service: sockets bound by systemd and the `sd_notify` protocol.
+//!
+//! Sockets passed with `LISTEN_FDS` are matched to listeners by their `FileDescriptorName=`,
+//! one of `http`, `finger`, `tls` and `unix`, several of which can have the same name. A socket
+//! without one of those names is taken as an http one. Without `NOTIFY_SOCKET` every notification is dropped.
+
+use std::sync::Arc;
+
+use tokio::{
+    net::TcpListener,
+    sync::Mutex,
+    time::{Duration, interval},
+};
+
+use crate::server::{Fingr, prelude::*};
+
+// the names a socket unit can give its sockets
+#[cfg(unix)]
+const NAMES: [&str; 4] = ["http", "finger", "tls", "unix"];
+
+/// The sockets systemd bound for us, by name.
+#[derive(Default)]
+pub struct Activated {
+    #[cfg(unix)]
+    fds: Vec<(String, std::os::fd::OwnedFd)>,
+}
+
+impl Activated {
+    /// Takes the sockets passed to us in `LISTEN_FDS`, none if we weren't socket activated or
+    /// they were taken already.
+    #[cfg(unix)]
+    pub fn take() -> Self {
+        use std::{
+            os::fd::{FromRawFd, OwnedFd},
+            sync::atomic::{AtomicBool, Ordering},
+        };
+
+        // the first passed socket, as in sd_listen_fds(3)
+        const FIRST_FD: i32 = 3;
+        static TAKEN: AtomicBool = AtomicBool::new(false);
+
+        let env = |name| std::env::var(name).ok();
+        // the variables are inherited by children, which must leave the sockets alone
+        if env("LISTEN_PID").and_then(|pid| pid.parse().ok()) != Some(std::process::id()) {
+            return Self::default();
+        }
+        let Some(count) = env("LISTEN_FDS").and_then(|count| count.parse::<i32>().ok()) else {
+            return Self::default();
+        };
+        if TAKEN.swap(true, Ordering::SeqCst) {
+            return Self::default();
+        }
+
+        let names = env("LISTEN_FDNAMES").unwrap_or_default();
+        let mut names = names.split(':');
+        let fds = (FIRST_FD..FIRST_FD.saturating_add(count))
+            .map(|fd| {
+                let name = names.next().unwrap_or_default().to_owned();
+                // SAFETY: systemd passed us these fds and nothing else takes them, see `TAKEN`
+                (name, unsafe { OwnedFd::from_raw_fd(fd) })
+            })
+            .collect::<Vec<_>>();
+        info!("systemd passed {} sockets", fds.len());
+
+        Self { fds }
+    }
+
+    #[cfg(not(unix))]
+    pub fn take() -> Self {
+        Self::default()
+    }
+
+    /// The tcp sockets named `name`, none if systemd didn't pass any.
+    pub fn tcp(&mut self, name: &str) -> Result<Vec<TcpListener>> {
+        #[allow(unused_mut)]
+        let mut listeners = vec![];
+        #[cfg(unix)]
+        while let Some(fd) = self.remove(name) {
+            let listener = std::net::TcpListener::from(fd);
+            listener.set_nonblocking(true)?;
+            info!("listening for {} on a socket from systemd", name);
+            listeners.push(TcpListener::from_std(listener)?);
+        }
+
+        Ok(listeners)
+    }
+
+    /// The unix socket named `unix`, `None` if systemd didn't pass one.
+    #[cfg(unix)]
+    pub fn unix(&mut self) -> Result<Option<tokio::net::UnixListener>> {
+        let Some(fd) = self.remove("unix") else {
+            return Ok(None);
+        };
+        let listener = std::os::unix::net::UnixListener::from(fd);
+        listener.set_nonblocking(true)?;
+        info!("listening for unix on the socket from systemd");
+
+        Ok(Some(tokio::net::UnixListener::from_std(listener)?))
+    }
+
+    /// Warns about the sockets that no listener took.
+    pub fn finish(self) {
+        #[cfg(unix)]
+        for (name, _) in self.fds {
+            warn!("systemd passed a socket named '{}' that isn't used", name);
+        }
+    }
+
+    #[cfg(unix)]
+    fn remove(&mut self, name: &str) -> Option<std::os::fd::OwnedFd> {
+        let index = self.fds.iter().position(|(fd, _)| fd == name).or_else(|| {
+            (name == "http")
+                .then(|| {
+                    self.fds
+                        .iter()
+                        .position(|(fd, _)| !NAMES.contains(&fd.as_str()))
+                })
+                .flatten()
+        })?;
+
+        Some(self.fds.remove(index).1)
+    }
+}
+
+/// Tells systemd how we are doing, like `READY=1`, if it is listening.
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    if let Some(path) = std::env::var_os("NOTIFY_SOCKET")
+        && let Err(e) = send(&path, state)
+    {
+        warn!("can't notify systemd: {}", e);
+    }
+}
+
+#[cfg(unix)]
+fn send(path: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::{ffi::OsStrExt, net::UnixDatagram};
+
+    let socket = UnixDatagram::unbound()?;
+    // `@` starts an abstract socket, which only linux has
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.as_bytes().strip_prefix(b"@") {
+        use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+        let addr = SocketAddr::from_abstract_name(name)?;
+        socket.send_to_addr(state.as_bytes(), &addr)?;
+        return Ok(());
+    }
+    socket.send_to(state.as_bytes(), path)?;
+
+    Ok(())
+}
+
+/// Pings the watchdog at half the `WatchdogSec=` of the service, if it has one. Each ping waits
+/// for the state, so a server stuck holding it is restarted.
+pub async fn watchdog(state: Arc<Mutex<Fingr>>) {
+    let Some(timeout) = watchdog_timeout() else {
+        return;
+    };
+    info!("pinging the systemd watchdog every {:?}", timeout / 2);
+
+    let mut ticker = interval(timeout / 2);
+    loop {
+        ticker.tick().await;
+        drop(state.lock().await);
+        notify("WATCHDOG=1");
+    }
+}
+
+fn watchdog_timeout() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse().ok() != Some(std::process::id())
+    {
+        return None;
+    }
+
+    std::env::var("WATCHDOG_USEC")
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
@@ -0,0 +1,106 @@
+//! What the tests share: servers and stores in a directory of their own.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::sync::Mutex;
+
+use crate::networking::{JSONResponse, Method, Request, Response};
+use crate::{
+    Fngr,
+    plugin::Plugins,
+    server::{
+        Fingr,
+        config::{Config, ConfigSource},
+        store::Store,
+    },
+};
+
+/// A directory that is removed with everything in it when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("fngr-test-{:016x}", rand::random::<u64>()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `users` as the users list, the json of its entries.
+    pub fn users_list(&self, users: &str) {
+        std::fs::write(self.0.join("users.list"), users).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A config keeping its files in `dir`, with `overrides` over the defaults.
+pub async fn config(dir: &TempDir, overrides: &[(&str, &str)]) -> Config {
+    let path = |name: &str| dir.path().join(name).display().to_string();
+    let mut all = vec![
+        ("address".to_owned(), "127.0.0.1".to_owned()),
+        ("port".to_owned(), "38273".to_owned()),
+        ("registration".to_owned(), "true".to_owned()),
+        ("users_list".to_owned(), path("users.list")),
+        ("lock".to_owned(), path("finger.lock")),
+    ];
+    all.extend(
+        overrides
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+    );
+
+    let source = ConfigSource {
+        path: Some(dir.path().join("config")),
+        overrides: all,
+    };
+    Config::load(&source).await.unwrap()
+}
+
+/// A server keeping its files in `dir`, with no users unless `dir` has a users list.
+pub async fn fingr(dir: &TempDir, overrides: &[(&str, &str)]) -> Arc<Mutex<Fingr>> {
+    if !dir.path().join("users.list").exists() {
+        dir.users_list("[]");
+    }
+    let config = config(dir, overrides).await;
+    let store = Store::open(&config).unwrap();
+    let fingr = Fingr::new(config, None, store, Plugins::default())
+        .await
+        .unwrap();
+    Arc::new(Mutex::new(fingr))
+}
+
+/// A `GET` of `target`, like `/list?format=json`.
+pub fn get(target: &str) -> Request {
+    Request::new(Method::Get, target, "HTTP/1.1", HashMap::new()).unwrap()
+}
+
+/// Has `state` answer `req` like it answers a request on a connection.
+pub async fn send(state: &Arc<Mutex<Fingr>>, req: Request) -> Response {
+    Fingr::run_request(state.clone(), req).await.unwrap()
+}
+
+/// Has `state` answer a `GET` of `target`.
+pub async fn request(state: &Arc<Mutex<Fingr>>, target: &str) -> Response {
+    send(state, get(target)).await
+}
+
+/// Registers `username` and answers with their key.
+pub async fn register(state: &Arc<Mutex<Fingr>>, username: &str) -> String {
+    let response = request(state, &format!("/register?username={}", username)).await;
+    match response.json() {
+        Some(JSONResponse::OK(key)) => key.to_owned(),
+        other => panic!("registering {} answered {:?}", username, other),
+    }
+}
@@ -0,0 +1,67 @@
+use std::{path::Path, sync::Arc};
+
+use tokio::{net::TcpListener, sync::Mutex, time::timeout};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        ServerConfig,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+};
+
+use crate::server::{Fingr, prelude::*};
+
+/// Builds an acceptor from a pem certificate chain and private key.
+pub async fn acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor> {
+    is_relative("tls certificate", cert)?;
+    is_relative("tls key", key)?;
+    info!("loading tls certificate from {}", cert.display());
+
+    let cert = tokio::fs::read(cert).await?;
+    let certs = CertificateDer::pem_slice_iter(&cert)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("failed to parse tls certificate: {}", e))?;
+    let key = tokio::fs::read(key).await?;
+    let key = PrivateKeyDer::from_pem_slice(&key)
+        .map_err(|e| anyhow!("failed to parse tls key: {}", e))?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+pub async fn serve(state: Arc<Mutex<Fingr>>, listener: TcpListener, acceptor: TlsAcceptor) {
+    let connections = state.lock().await.connections.clone();
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                let Ok(permit) = connections.clone().try_acquire_owned() else {
+                    warn!(?addr, "too many connections, dropping it");
+                    continue;
+                };
+                info!(?addr, "tls connection...");
+                let state = state.clone();
+                let acceptor = acceptor.clone();
+
+                tokio::spawn(async move {
+                    // the handshake gets as long as a request would
+                    let handshake = state.lock().await.config.read_timeout();
+                    match timeout(handshake, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            Fingr::serve_connection(state, stream, Some(addr.ip()), permit).await
+                        }
+                        Ok(Err(e)) => error!(?addr, "tls handshake failed: {}", e),
+                        Err(_) => info!(?addr, "tls handshake timed out"),
+                    }
+                });
+            }
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        }
+    }
+}
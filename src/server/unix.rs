@@ -0,0 +1,46 @@
+use std::{path::Path, sync::Arc};
+
+use tokio::{net::UnixListener, sync::Mutex};
+
+use crate::server::{Fingr, prelude::*};
+
+/// Binds `path`, replacing a socket left behind by a previous run.
+pub async fn bind(path: &Path) -> Result<UnixListener> {
+    is_relative("unix socket", path)?;
+
+    if let Ok(meta) = tokio::fs::symlink_metadata(path).await {
+        use std::os::unix::fs::FileTypeExt;
+
+        if !meta.file_type().is_socket() {
+            return Err(anyhow!(
+                "{} exists and is not a socket, refusing to replace it",
+                path.display()
+            ));
+        }
+
+        tokio::fs::remove_file(path).await?;
+    }
+
+    Ok(UnixListener::bind(path)?)
+}
+
+pub async fn serve(state: Arc<Mutex<Fingr>>, listener: UnixListener) {
+    let connections = state.lock().await.connections.clone();
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let Ok(permit) = connections.clone().try_acquire_owned() else {
+                    warn!("too many connections, dropping a unix one");
+                    continue;
+                };
+                info!("unix connection...");
+                tokio::spawn(Fingr::serve_connection(state.clone(), stream, None, permit));
+            }
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        }
+    }
+}
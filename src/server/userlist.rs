@@ -0,0 +1,1348 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+    net::IpAddr,
+    ops::{Deref, DerefMut},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::Result;
+use crate::networking::{
+    Availability, JSONLookup, JSONProfile, JSONResponse, JSONStatus, JSONStatusChange,
+    JSONUserStats, Request,
+};
+use crate::server::{
+    prelude::*,
+    store::{Ban, Counters, Profile, SavedStatus, Settings, Store, UserStore},
+    totp,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use sha_rs::{Sha, Sha256};
+use tokio::time::Instant;
+
+impl From<Status> for JSONStatus {
+    fn from(value: Status) -> Self {
+        Self {
+            online: value.presence.is_online(),
+            presence: value.presence,
+            text: value.text,
+            since: value.since.elapsed().as_secs(),
+            expires_in: value
+                .expires
+                .map(|expires| expires.saturating_duration_since(Instant::now()).as_secs()),
+            last_seen: value.last_seen.map(|last_seen| {
+                DateTime::<Utc>::from(last_seen).to_rfc3339_opts(SecondsFormat::Secs, true)
+            }),
+        }
+    }
+}
+
+use uuid::Uuid;
+
+pub struct UserList {
+    users: HashMap<String, User>,
+    // bumped whenever something visible in `list` changes, from a random start so versions of
+    // an earlier run don't come back
+    version: u64,
+    // when the version was last bumped
+    modified: SystemTime,
+    // users whose status changed since it was last saved
+    changed: HashSet<String>,
+    // the most fingered users of the week, most first
+    leaderboard: Vec<String>,
+    // users left out of the last load for a malformed entry or key hash
+    skipped: usize,
+    store: Store,
+}
+
+// how many users make it onto the leaderboard
+const LEADERBOARD_SIZE: usize = 10;
+
+impl UserList {
+    /// Sets users whose deadline has passed away or offline and returns the usernames that changed.
+    pub fn check_statuses(&mut self, idle_timeout: Duration) -> Vec<String> {
+        let changed: Vec<String> = self
+            .users
+            .values_mut()
+            .filter_map(|user| {
+                user.check_status(idle_timeout)
+                    .then(|| user.username.to_owned())
+            })
+            .collect();
+        self.changed.extend(changed.iter().cloned());
+
+        // also starts the board over on mondays
+        let reranked = self.rank();
+        if !changed.is_empty() || reranked {
+            self.touch();
+        }
+
+        changed
+    }
+
+    /// Counts a finger of `username` by someone else.
+    pub fn count_finger(&mut self, username: &str) {
+        if let Some(user) = self.get_mut(username) {
+            user.count_finger();
+            if self.rank() {
+                self.touch();
+            }
+        }
+    }
+
+    /// The most fingered users of the week, most first.
+    pub fn leaderboard(&self) -> impl Iterator<Item = &User> {
+        self.leaderboard
+            .iter()
+            .filter_map(|username| self.users.get(username))
+    }
+
+    /// Places the users fingered most this week, out of the ones anybody may see in `list`.
+    /// Returns whether the places changed.
+    fn rank(&mut self) -> bool {
+        let mut ranked: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| user.listed_for(None) && !user.pending && user.fingered_this_week() > 0)
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.fingered_this_week()
+                .cmp(&a.fingered_this_week())
+                .then_with(|| a.username.cmp(&b.username))
+        });
+        let leaderboard: Vec<String> = ranked
+            .into_iter()
+            .take(LEADERBOARD_SIZE)
+            .map(|user| user.username.to_owned())
+            .collect();
+        if leaderboard == self.leaderboard {
+            return false;
+        }
+
+        for user in self.users.values_mut() {
+            user.rank = leaderboard
+                .iter()
+                .position(|username| *username == user.username)
+                .map(|i| i + 1);
+        }
+        self.leaderboard = leaderboard;
+        true
+    }
+
+    /// Drops every user's lookups that are older than `retention`.
+    pub fn expire_logs(&mut self, retention: Duration) {
+        for user in self.users.values_mut() {
+            user.expire_log(retention);
+        }
+    }
+
+    pub fn online_count(&self) -> usize {
+        self.users.values().filter(|user| user.online()).count()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+
+    /// The users `viewer` may see in `list` whose names match `pattern`, sorted by name.
+    pub fn glob(&self, pattern: &str, viewer: Option<&str>) -> Vec<&User> {
+        let mut found: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| {
+                user.listed_for(viewer)
+                    && !user.blocks(viewer)
+                    && glob_matches(pattern, &user.username)
+            })
+            .collect();
+        found.sort_by(|a, b| a.username.cmp(&b.username));
+        found
+    }
+
+    /// The users `viewer` may see in `list` that `query` asks for.
+    pub fn search<'a>(
+        &'a self,
+        viewer: Option<&'a str>,
+        query: &'a ListQuery,
+    ) -> impl Iterator<Item = &'a User> {
+        self.users.values().filter(move |user| {
+            user.listed_for(viewer)
+                && query.matches(&user.username, user.online(), user.status.text.as_deref())
+        })
+    }
+
+    /// How many users the load left out, for an entry the store couldn't read or a malformed key
+    /// hash.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Marks the list as changed, invalidating anything cached against the previous version.
+    pub fn touch(&mut self) {
+        self.version = self.version.wrapping_add(1);
+        self.modified = SystemTime::now();
+    }
+}
+
+/// Whether `query` has `*` or `?` in it, so it is a pattern rather than a username.
+pub fn is_glob(query: &str) -> bool {
+    query.contains(['*', '?'])
+}
+
+/// Why `username` can't be registered, if it can't. Fingers read the characters it refuses as
+/// something other than a username.
+pub fn invalid_username(username: &str) -> Option<&'static str> {
+    if username.trim().is_empty() {
+        Some("usernames can't be empty")
+    } else if username.contains(',') {
+        // commas separate the users of a batch finger
+        Some("usernames can't contain commas")
+    } else if username.contains('@') {
+        // the part after it is the host to forward to
+        Some("usernames can't contain @")
+    } else if username.contains(['*', '?', '[']) {
+        // these make patterns
+        Some("usernames can't contain *, ? or [")
+    } else {
+        None
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` is any number of characters and `?` is one,
+/// ignoring case like classic finger did.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // where the last `*` was and the part of the name it matched up to, to backtrack to
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// What `list` narrows its users down to, from the request's parameters.
+#[derive(Default)]
+pub struct ListQuery {
+    online: Option<bool>,
+    prefix: Option<String>,
+    // lowercase, matched anywhere in the status text
+    search: Option<String>,
+    sort: Option<ListSort>,
+    count: bool,
+    page: Page,
+    following: bool,
+    // the only users to list, the ones the viewer follows with `following`
+    only: Option<Vec<String>>,
+}
+
+/// Which part of a long response to send, from `limit` and `offset`.
+#[derive(Default)]
+pub struct Page {
+    offset: usize,
+    // everything after `offset` if `None`
+    limit: Option<usize>,
+}
+
+impl Page {
+    /// The page `req` asks for, or what is wrong with it.
+    pub fn from_request(req: &Request) -> std::result::Result<Self, String> {
+        let number = |name: &str, value: Option<&str>| {
+            value
+                .map(str::parse::<usize>)
+                .transpose()
+                .map_err(|_| format!("{} must be a whole number", name))
+        };
+
+        Ok(Self {
+            offset: number("offset", req.offset.as_deref())?.unwrap_or(0),
+            limit: number("limit", req.limit.as_deref())?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offset == 0 && self.limit.is_none()
+    }
+
+    /// Whether nothing comes after this page of `total` items.
+    pub fn is_last(&self, total: usize) -> bool {
+        self.limit
+            .is_none_or(|limit| self.offset.saturating_add(limit) >= total)
+    }
+
+    pub fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ListSort {
+    Name,
+    /// Most recent logon or logoff first.
+    Since,
+    /// Online users first, then the ones who were online most recently.
+    Recent,
+}
+
+impl ListQuery {
+    /// The query in `req`, or what is wrong with it.
+    pub fn from_request(req: &Request) -> std::result::Result<Self, String> {
+        let flag = |name: &str, value: Option<&str>| match value {
+            None => Ok(None),
+            Some("true" | "1") => Ok(Some(true)),
+            Some("false" | "0") => Ok(Some(false)),
+            Some(_) => Err(format!("{} must be true or false", name)),
+        };
+
+        Ok(Self {
+            online: flag("online", req.online.as_deref())?,
+            prefix: req.prefix.to_owned().filter(|prefix| !prefix.is_empty()),
+            search: req
+                .search
+                .as_deref()
+                .filter(|search| !search.is_empty())
+                .map(str::to_lowercase),
+            sort: match req.sort.as_deref() {
+                None => None,
+                Some("name") => Some(ListSort::Name),
+                Some("since") => Some(ListSort::Since),
+                Some("recent") => Some(ListSort::Recent),
+                Some(_) => return Err("sort must be name, since or recent".to_owned()),
+            },
+            count: flag("count", req.count.as_deref())?.unwrap_or(false),
+            page: Page::from_request(req)?,
+            following: flag("following", req.following.as_deref())?.unwrap_or(false),
+            only: None,
+        })
+    }
+
+    /// Whether only the users the viewer follows are asked for.
+    pub fn following(&self) -> bool {
+        self.following
+    }
+
+    /// Lists only `usernames`, sorted by who was online most recently unless asked otherwise.
+    pub fn only(&mut self, usernames: Vec<String>) {
+        self.only = Some(usernames);
+        self.sort = self.sort.or(Some(ListSort::Recent));
+    }
+
+    /// Whether the query leaves `list` as it is.
+    pub fn is_empty(&self) -> bool {
+        self.online.is_none()
+            && self.prefix.is_none()
+            && self.search.is_none()
+            && self.sort.is_none()
+            && !self.count
+            && self.page.is_empty()
+            && !self.following
+    }
+
+    pub fn matches(&self, username: &str, online: bool, text: Option<&str>) -> bool {
+        self.only
+            .as_ref()
+            .is_none_or(|only| only.iter().any(|name| name == username))
+            && self.online.is_none_or(|wanted| wanted == online)
+            && self
+                .prefix
+                .as_deref()
+                .is_none_or(|prefix| username.starts_with(prefix))
+            && self
+                .search
+                .as_deref()
+                .is_none_or(|search| text.is_some_and(|text| text.to_lowercase().contains(search)))
+    }
+
+    /// Whether a user from elsewhere, like a peer or utmp, matches.
+    pub fn matches_response(&self, response: &JSONResponse) -> bool {
+        match response {
+            JSONResponse::User {
+                username, status, ..
+            } => self.matches(username, status.online, status.text.as_deref()),
+            _ => true,
+        }
+    }
+
+    /// Sorts `users` and makes the asked for page of them the body of the response, or just
+    /// counts them. Also returns how many users there are in all.
+    pub fn finish(&self, mut users: Vec<JSONResponse>) -> (JSONResponse, usize) {
+        let total = users.len();
+        if self.count {
+            return (JSONResponse::Count(total), total);
+        }
+
+        let key = |response: &JSONResponse| match response {
+            JSONResponse::User {
+                username, status, ..
+            } => (status.since, username.to_owned()),
+            _ => (0, String::new()),
+        };
+        match self.sort {
+            Some(ListSort::Name) => users.sort_by_key(|response| key(response).1),
+            Some(ListSort::Since) => users.sort_by_key(key),
+            // rfc 3339 times in utc sort like the times they are
+            Some(ListSort::Recent) => users.sort_by_key(|response| match response {
+                JSONResponse::User { status, .. } => {
+                    (!status.online, Reverse(status.last_seen.to_owned()))
+                }
+                _ => (true, Reverse(None)),
+            }),
+            // pages need an order that holds from one request to the next
+            None if !self.page.is_empty() => users.sort_by_key(|response| key(response).1),
+            None => {}
+        }
+
+        (JSONResponse::List(self.page.apply(users)), total)
+    }
+}
+
+impl Deref for UserList {
+    type Target = HashMap<String, User>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.users
+    }
+}
+
+impl DerefMut for UserList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.users
+    }
+}
+
+impl Display for User {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let json: JSONResponse = self.into();
+        write!(f, "{}", json)
+    }
+}
+
+pub struct User {
+    username: String,
+    hash: String,
+    status: Status,
+    // oldest first
+    log: VecDeque<Lookup>,
+    // status texts they set, oldest first
+    history: VecDeque<StatusChange>,
+    // when each user who fingered them last did, kept past `check` for poking back
+    fingered_by: HashMap<String, SystemTime>,
+    settings: Settings,
+    profile: Profile,
+    ban: Option<Ban>,
+    // registered but not approved yet
+    pending: bool,
+    totp: Option<String>,
+    // the last step a totp code was accepted for, so a code only works once
+    totp_step: u64,
+    pubkey: Option<String>,
+    counters: Counters,
+    // when the user last went online, for counting their time online
+    online_from: Option<Instant>,
+    // their place on the leaderboard
+    rank: Option<usize>,
+}
+
+/// Someone fingering a user.
+struct Lookup {
+    from: String,
+    ip: Option<IpAddr>,
+    at: SystemTime,
+}
+
+/// A status text a user set.
+struct StatusChange {
+    text: Option<String>,
+    at: SystemTime,
+}
+
+impl Into<JSONResponse> for User {
+    fn into(self) -> JSONResponse {
+        JSONResponse::User {
+            username: self.username.to_owned(),
+
+            profile: Some(Box::new(self.json_profile())),
+            status: self.status.into(),
+        }
+    }
+}
+
+impl Into<JSONResponse> for &User {
+    fn into(self) -> JSONResponse {
+        JSONResponse::User {
+            username: self.username.to_owned(),
+
+            status: self.status.clone().into(),
+            profile: Some(Box::new(self.json_profile())),
+        }
+    }
+}
+
+impl Into<JSONResponse> for &mut User {
+    fn into(self) -> JSONResponse {
+        JSONResponse::User {
+            username: self.username.to_owned(),
+
+            status: self.status.clone().into(),
+            profile: Some(Box::new(self.json_profile())),
+        }
+    }
+}
+
+impl User {
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    // fn uuid(&self) -> Uuid {
+    //     self.uuid
+    // }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// Sets the status, recording the text in the history if it changed and dropping the oldest
+    /// entries beyond `max_history`.
+    pub fn set_status(&mut self, s: Status, max_history: usize) {
+        if s.text != self.status.text && max_history > 0 {
+            self.history.push_back(StatusChange {
+                text: s.text.to_owned(),
+                at: SystemTime::now(),
+            });
+            while self.history.len() > max_history {
+                self.history.pop_front();
+            }
+        }
+        self.status = s;
+        self.count_online();
+    }
+
+    /// The status texts the user set, newest first.
+    pub fn history(&self) -> Vec<JSONStatusChange> {
+        self.history
+            .iter()
+            .rev()
+            .map(|change| JSONStatusChange {
+                text: change.text.to_owned(),
+                at: change
+                    .at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            })
+            .collect()
+    }
+
+    pub fn online(&self) -> bool {
+        self.status.presence.is_online()
+    }
+
+    /// How long the user stays online without a bump, their own setting or else `default`.
+    pub fn idle_timeout(&self, default: Duration) -> Duration {
+        self.settings
+            .idle_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(default)
+    }
+
+    /// Keeps an online user online for their idle timeout from now.
+    pub fn bump(&mut self, idle_timeout: Duration) -> bool {
+        let timeout = self.idle_timeout(idle_timeout);
+        self.extend(timeout)
+    }
+
+    /// Changes the presence of an online user, which counts as a bump.
+    pub fn set_presence(&mut self, presence: Availability, idle_timeout: Duration) -> bool {
+        if !self.online() {
+            return false;
+        }
+
+        self.status.presence = presence;
+        self.bump(idle_timeout)
+    }
+
+    /// Keeps an online user from being set offline by the worker until `duration` has passed.
+    pub fn snooze(&mut self, duration: Duration) -> bool {
+        self.extend(duration)
+    }
+
+    /// Moves the deadline of an online user to `duration` from now, unless it is already later.
+    fn extend(&mut self, duration: Duration) -> bool {
+        if !self.online() {
+            return false;
+        }
+
+        let deadline = Instant::now() + duration;
+        self.status.expires = Some(match self.status.expires {
+            Some(expires) => expires.max(deadline),
+            None => deadline,
+        });
+        self.status.last_seen = Some(SystemTime::now());
+        true
+    }
+
+    pub fn compare_key(&self, key: Uuid) -> bool {
+        hash_key(key) == self.hash
+    }
+
+    /// Online users who missed their deadline are away for another idle timeout, everyone else
+    /// goes offline straight away.
+    fn check_status(&mut self, idle_timeout: Duration) -> bool {
+        let now = Instant::now();
+
+        match self.status.expires {
+            Some(expires) if self.online() && expires <= now => {
+                if self.status.presence == Availability::Online {
+                    self.status.presence = Availability::Away;
+                    self.status.expires = Some(now + self.idle_timeout(idle_timeout));
+                } else {
+                    self.status.presence = Availability::Offline;
+                    self.status.since = now;
+                    self.status.expires = None;
+                    self.count_online();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Whether the user blocked `viewer`.
+    pub fn blocks(&self, viewer: Option<&str>) -> bool {
+        viewer.is_some_and(|viewer| self.settings.blocked.iter().any(|name| name == viewer))
+    }
+
+    /// Whether `viewer`, `None` for anonymous, may finger the user.
+    pub fn allows(&self, viewer: Option<&str>) -> bool {
+        match viewer {
+            Some(viewer) if viewer == self.username => true,
+            _ if self.blocks(viewer) => false,
+            _ if !self.settings.friends_only => true,
+            Some(viewer) => self.settings.friends.iter().any(|name| name == viewer),
+            None => false,
+        }
+    }
+
+    /// Whether the user shows up in the `list` of `viewer`, users always see themselves.
+    pub fn listed_for(&self, viewer: Option<&str>) -> bool {
+        viewer == Some(self.username.as_str()) || (!self.settings.hidden && self.allows(viewer))
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Whether the user still waits for an admin to approve their registration.
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Whether the user enrolled a second factor.
+    pub fn has_totp(&self) -> bool {
+        self.totp.is_some()
+    }
+
+    /// Checks a totp `code`, each code is accepted once.
+    pub fn check_totp(&mut self, code: &str) -> bool {
+        match self
+            .totp
+            .as_deref()
+            .and_then(|secret| totp::verify(secret, code))
+        {
+            Some(step) if step > self.totp_step => {
+                self.totp_step = step;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The hex ed25519 key the user signs requests with, if they set one.
+    pub fn pubkey(&self) -> Option<&str> {
+        self.pubkey.as_deref()
+    }
+
+    /// The user's ban, unless they have none or their suspension is over.
+    pub fn ban(&self) -> Option<&Ban> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.ban
+            .as_ref()
+            .filter(|ban| ban.until.is_none_or(|until| until > now))
+    }
+
+    /// The current time where the user is, if they set a timezone.
+    pub fn local_time(&self) -> Option<DateTime<Tz>> {
+        let tz = self.profile.timezone.as_deref()?.parse::<Tz>().ok()?;
+        Some(Utc::now().with_timezone(&tz))
+    }
+
+    fn json_profile(&self) -> JSONProfile {
+        let profile = &self.profile;
+        let avatar = match (&profile.avatar, &profile.avatar_type) {
+            (Some(url), _) => Some(url.to_owned()),
+            (None, Some(_)) => Some(format!(
+                "/avatar?user={}",
+                utf8_percent_encode(&self.username, NON_ALPHANUMERIC)
+            )),
+            (None, None) => None,
+        };
+
+        JSONProfile {
+            bio: profile.bio.to_owned(),
+            website: profile.website.to_owned(),
+            social: profile.social.to_owned(),
+            pronouns: profile.pronouns.to_owned(),
+            location: profile.location.to_owned(),
+            timezone: profile.timezone.to_owned(),
+            local_time: self
+                .local_time()
+                .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            avatar,
+            mail: None,
+            history: None,
+            follows_you: false,
+            fingered_you: None,
+            fingered_rank: self.rank,
+        }
+    }
+
+    /// Starts the clock when the user goes online, counting it as a login, and adds the time to
+    /// their counters when they go offline.
+    fn count_online(&mut self) {
+        match (self.online_from, self.online()) {
+            (None, true) => {
+                self.online_from = Some(Instant::now());
+                self.counters.logins += 1;
+            }
+            (Some(from), false) => {
+                self.counters.online_secs += from.elapsed().as_secs();
+                self.online_from = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn count_finger(&mut self) {
+        let week = current_week();
+        if self.counters.week != week {
+            self.counters.week = week;
+            self.counters.fingered_week = 0;
+        }
+        self.counters.fingered += 1;
+        self.counters.fingered_week += 1;
+    }
+
+    fn fingered_this_week(&self) -> u64 {
+        if self.counters.week == current_week() {
+            self.counters.fingered_week
+        } else {
+            0
+        }
+    }
+
+    /// The user's counters, with the time they have been online so far.
+    pub fn stats(&self) -> JSONUserStats {
+        let online = self.online_from.map(|from| from.elapsed().as_secs());
+        JSONUserStats {
+            username: self.username.to_owned(),
+            fingered: self.counters.fingered,
+            fingered_this_week: self.fingered_this_week(),
+            logins: self.counters.logins,
+            online_secs: self.counters.online_secs + online.unwrap_or_default(),
+            rank: self.rank,
+        }
+    }
+
+    /// Records that `from` fingered this user, dropping the oldest lookups beyond `max`.
+    pub fn add_log(&mut self, from: &str, ip: Option<IpAddr>, max: usize) {
+        let now = SystemTime::now();
+        self.log.push_back(Lookup {
+            from: from.to_owned(),
+            ip,
+            at: now,
+        });
+        if from != "anonymous" {
+            self.fingered_by.insert(from.to_owned(), now);
+        }
+
+        while self.log.len() > max {
+            self.log.pop_front();
+        }
+    }
+
+    /// When `username` last fingered this user, as a unix timestamp, if they did lately and
+    /// didn't keep their lookups private.
+    pub fn fingered_by(&self, username: &str) -> Option<u64> {
+        self.fingered_by
+            .get(username)
+            .map(|at| at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+    }
+
+    /// Drops lookups older than `retention`.
+    fn expire_log(&mut self, retention: Duration) {
+        let now = SystemTime::now();
+        self.fingered_by
+            .retain(|_, at| now.duration_since(*at).is_ok_and(|age| age <= retention));
+        while self.log.front().is_some_and(|lookup| {
+            now.duration_since(lookup.at)
+                .is_ok_and(|age| age > retention)
+        }) {
+            self.log.pop_front();
+        }
+    }
+
+    /// The `page` of the log and how many entries it has in all, with repeated lookups by the same
+    /// user from the same address counted together. The log is taken once its last page is.
+    pub fn log(&mut self, page: &Page) -> (Vec<JSONLookup>, usize) {
+        let mut lookups: Vec<JSONLookup> = vec![];
+
+        for entry in &self.log {
+            let ip = entry.ip.map(|ip| ip.to_string());
+            let at = entry
+                .at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+
+            match lookups
+                .iter_mut()
+                .find(|lookup| lookup.username == entry.from && lookup.ip == ip)
+            {
+                Some(lookup) => {
+                    lookup.count += 1;
+                    lookup.last = at;
+                }
+                None => lookups.push(JSONLookup {
+                    username: entry.from.to_owned(),
+                    ip,
+                    count: 1,
+                    first: at,
+                    last: at,
+                    you_fingered: None,
+                }),
+            }
+        }
+
+        let total = lookups.len();
+        if page.is_last(total) {
+            self.log.clear();
+        }
+        (page.apply(lookups), total)
+    }
+}
+
+impl From<&User> for SavedStatus {
+    fn from(user: &User) -> Self {
+        Self {
+            online: user.online(),
+            presence: Some(user.status.presence),
+            text: user.status.text.to_owned(),
+            since: to_unix(user.status.since),
+            expires: user.status.expires.map(to_unix),
+            last_seen: user.status.last_seen.map(|last_seen| {
+                last_seen
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            }),
+            counters: user.counters.to_owned(),
+        }
+    }
+}
+
+/// Weeks since the unix epoch, which was on a thursday, starting on mondays.
+fn current_week() -> u64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400;
+    (days + 3) / 7
+}
+
+fn to_unix(instant: Instant) -> u64 {
+    let now = Instant::now();
+    let time = if instant > now {
+        SystemTime::now() + (instant - now)
+    } else {
+        SystemTime::now() - (now - instant)
+    };
+
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The instant matching a unix timestamp, clamped to now if it is too far in the past to represent.
+fn from_unix(secs: u64) -> Instant {
+    let time = UNIX_EPOCH + Duration::from_secs(secs);
+    let now = Instant::now();
+
+    match time.duration_since(SystemTime::now()) {
+        Ok(ahead) => now + ahead,
+        Err(e) => now.checked_sub(e.duration()).unwrap_or(now),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub presence: Availability,
+    pub text: Option<String>,
+    pub since: Instant,
+    /// When an online user will be set offline unless they bump first.
+    pub expires: Option<Instant>,
+    /// When the user last logged on, logged off, bumped or snoozed.
+    pub last_seen: Option<SystemTime>,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = JSONStatus::from(self.clone());
+
+        let output = serde_json::to_string(&s).map_err(|_| std::fmt::Error)?;
+
+        write!(f, "{}", output)
+    }
+}
+
+impl Status {
+    fn default() -> Status {
+        Self {
+            presence: Availability::Offline,
+            text: None,
+            since: Instant::now(),
+            expires: None,
+            last_seen: None,
+        }
+    }
+}
+
+impl UserList {
+    /// Loads every user from `store`. Online users restored without a deadline get `idle_timeout` from now.
+    pub async fn load(store: Store, idle_timeout: Duration) -> Result<Self> {
+        let version = rand::random();
+        let mut fin = Self {
+            users: HashMap::new(),
+            version,
+            modified: SystemTime::now(),
+            changed: HashSet::new(),
+            leaderboard: vec![],
+            skipped: 0,
+            store,
+        };
+        let mut restored = 0;
+
+        let loaded = fin.store.load().await?;
+        fin.skipped = loaded.skipped;
+        for user in loaded.users {
+            if !is_valid_hash(&user.hash) {
+                warn!(
+                    "skipping user '{}': key hash '{}' is not a sha256 digest",
+                    user.username, user.hash
+                );
+                fin.skipped += 1;
+                continue;
+            }
+
+            let counters = user
+                .status
+                .as_ref()
+                .map(|saved| saved.counters.to_owned())
+                .unwrap_or_default();
+            let status = match user.status {
+                Some(saved) => {
+                    restored += 1;
+                    let expires = match (saved.online, saved.expires) {
+                        (true, Some(expires)) => Some(from_unix(expires)),
+                        // saved before statuses had a deadline
+                        (true, None) => Some(Instant::now() + idle_timeout),
+                        (false, _) => None,
+                    };
+                    Status {
+                        presence: saved.presence.unwrap_or(match saved.online {
+                            true => Availability::Online,
+                            false => Availability::Offline,
+                        }),
+                        text: saved.text,
+                        since: from_unix(saved.since),
+                        expires,
+                        last_seen: saved
+                            .last_seen
+                            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                    }
+                }
+                None => Status::default(),
+            };
+            // time offline servers can't tell about is left out
+            let online_from = status.presence.is_online().then(Instant::now);
+
+            fin.users.insert(
+                user.username.to_owned(),
+                User {
+                    username: user.username,
+                    hash: user.hash,
+                    status,
+                    log: VecDeque::new(),
+                    history: VecDeque::new(),
+                    fingered_by: HashMap::new(),
+                    settings: user.settings,
+                    profile: user.profile,
+                    ban: user.ban,
+                    pending: user.pending,
+                    totp: user.totp,
+                    totp_step: 0,
+                    pubkey: user.pubkey,
+                    counters,
+                    online_from,
+                    rank: None,
+                },
+            );
+        }
+        fin.rank();
+
+        if fin.skipped > 0 {
+            warn!("skipped {} malformed users", fin.skipped);
+        }
+
+        info!("loaded {} users, restored {} statuses", fin.len(), restored);
+
+        Ok(fin)
+    }
+
+    /// Re-reads the store, adding new users, dropping removed ones and picking up changed keys.
+    /// Users that were already loaded keep their status.
+    ///
+    /// Returns the usernames that were removed or had their key changed.
+    pub async fn reload(&mut self) -> Result<Vec<String>> {
+        let mut seen = HashSet::new();
+        let mut revoked = vec![];
+        let mut added = 0;
+        // profiles show up in `list`
+        let mut changed = 0;
+
+        for user in self.store.load().await?.users {
+            if !is_valid_hash(&user.hash) {
+                warn!(
+                    "skipping user '{}': key hash '{}' is not a sha256 digest",
+                    user.username, user.hash
+                );
+                continue;
+            }
+
+            seen.insert(user.username.to_owned());
+            match self.users.get_mut(&user.username) {
+                Some(existing) => {
+                    if existing.profile != user.profile {
+                        existing.profile = user.profile;
+                        changed += 1;
+                    }
+                    existing.settings = user.settings;
+                    existing.ban = user.ban;
+                    existing.pending = user.pending;
+                    existing.totp = user.totp;
+                    existing.pubkey = user.pubkey;
+                    if existing.hash != user.hash {
+                        existing.hash = user.hash;
+                        revoked.push(user.username);
+                    }
+                }
+                None => {
+                    self.users.insert(
+                        user.username.to_owned(),
+                        User {
+                            username: user.username,
+                            hash: user.hash,
+                            status: Status::default(),
+                            log: VecDeque::new(),
+                            history: VecDeque::new(),
+                            fingered_by: HashMap::new(),
+                            settings: user.settings,
+                            profile: user.profile,
+                            ban: user.ban,
+                            pending: user.pending,
+                            totp: user.totp,
+                            totp_step: 0,
+                            pubkey: user.pubkey,
+                            counters: Counters::default(),
+                            online_from: None,
+                            rank: None,
+                        },
+                    );
+                    added += 1;
+                }
+            }
+        }
+
+        let removed: Vec<String> = self
+            .users
+            .keys()
+            .filter(|username| !seen.contains(*username))
+            .cloned()
+            .collect();
+        for username in &removed {
+            self.users.remove(username);
+        }
+
+        info!(
+            "reloaded users: {} added, {} removed, {} with a new key",
+            added,
+            removed.len(),
+            revoked.len()
+        );
+
+        let reranked = self.rank();
+        if added > 0 || changed > 0 || !removed.is_empty() || reranked {
+            self.touch();
+        }
+
+        revoked.extend(removed);
+        Ok(revoked)
+    }
+
+    #[cfg(test)]
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// The user named `username` to change, whose status is saved with the next
+    /// [`save_statuses`](Self::save_statuses).
+    pub fn get_mut(&mut self, username: &str) -> Option<&mut User> {
+        let user = self.users.get_mut(username)?;
+        if !self.changed.contains(username) {
+            self.changed.insert(username.to_owned());
+        }
+        Some(user)
+    }
+
+    /// Writes the statuses that changed since the last save to the store, nothing if none did.
+    pub async fn save_statuses(&mut self) -> Result<()> {
+        if self.changed.is_empty() {
+            return Ok(());
+        }
+
+        let saved: HashMap<String, SavedStatus> = self
+            .changed
+            .iter()
+            .filter_map(|username| self.users.get(username))
+            .map(|user| (user.username.to_owned(), user.into()))
+            .collect();
+
+        self.store.save(&saved).await?;
+        self.changed.clear();
+
+        Ok(())
+    }
+
+    /// Writes the status of `username` to the store straight away.
+    pub async fn save_status(&mut self, username: &str) -> Result<()> {
+        let user = self
+            .users
+            .get(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?;
+        self.store.update_status(username, &user.into()).await?;
+        self.changed.remove(username);
+        Ok(())
+    }
+
+    /// Replaces the settings of `username`, in the store first.
+    pub async fn set_settings(&mut self, username: &str, settings: Settings) -> Result<()> {
+        self.store.update_settings(username, &settings).await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .settings = settings;
+        // hidden users leave the board
+        self.rank();
+        self.touch();
+
+        Ok(())
+    }
+
+    /// Replaces the profile of `username`, in the store first.
+    pub async fn set_profile(&mut self, username: &str, profile: Profile) -> Result<()> {
+        self.store.update_profile(username, &profile).await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .profile = profile;
+        self.touch();
+
+        Ok(())
+    }
+
+    /// Adds `username` with a new key and returns it, a `pending` user can't log in until approved.
+    pub async fn register(&mut self, username: String, pending: bool) -> Result<Uuid> {
+        if self.contains_key(&username) {
+            return Err(FngrError::UserExists(username));
+        }
+
+        let uuid = Uuid::from_bytes(rand::random());
+        let hash = hash_key(uuid);
+
+        self.store.register(&username, &hash, pending).await?;
+
+        self.insert(
+            username.to_owned(),
+            User {
+                username,
+                hash,
+                status: Status::default(),
+                log: VecDeque::new(),
+                history: VecDeque::new(),
+                fingered_by: HashMap::new(),
+                settings: Settings::default(),
+                profile: Profile::default(),
+                ban: None,
+                pending,
+                totp: None,
+                totp_step: 0,
+                pubkey: None,
+                counters: Counters::default(),
+                online_from: None,
+                rank: None,
+            },
+        );
+        self.touch();
+
+        Ok(uuid)
+    }
+
+    /// Lets a pending user log in, in the store first.
+    pub async fn approve(&mut self, username: &str) -> Result<()> {
+        self.store.approve(username).await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .pending = false;
+        self.rank();
+        self.touch();
+
+        Ok(())
+    }
+
+    /// Sets the totp secret of `username`, or removes it with `None`, in the store first.
+    pub async fn set_totp(&mut self, username: &str, secret: Option<String>) -> Result<()> {
+        self.store.update_totp(username, secret.as_deref()).await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .totp = secret;
+
+        Ok(())
+    }
+
+    /// Sets the public key of `username`, or removes it with `None`, in the store first.
+    pub async fn set_pubkey(&mut self, username: &str, pubkey: Option<String>) -> Result<()> {
+        self.store
+            .update_pubkey(username, pubkey.as_deref())
+            .await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .pubkey = pubkey;
+
+        Ok(())
+    }
+
+    /// Bans `username`, or lifts their ban with `None`, in the store first.
+    pub async fn set_ban(&mut self, username: &str, ban: Option<Ban>) -> Result<()> {
+        self.store.update_ban(username, ban.as_ref()).await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .ban = ban;
+        self.touch();
+
+        Ok(())
+    }
+
+    /// Gives `username` a new key, in the store first, and returns it.
+    pub async fn reset_key(&mut self, username: &str) -> Result<Uuid> {
+        let uuid = Uuid::from_bytes(rand::random());
+        self.set_key(username, uuid).await?;
+
+        Ok(uuid)
+    }
+
+    /// Replaces the key of `username` with `key`, in the store first.
+    pub async fn set_key(&mut self, username: &str, key: Uuid) -> Result<()> {
+        let hash = hash_key(key);
+
+        self.store.update_hash(username, &hash).await?;
+
+        self.users
+            .get_mut(username)
+            .ok_or_else(|| FngrError::UserNotFound(username.to_owned()))?
+            .hash = hash;
+
+        Ok(())
+    }
+
+    pub async fn remove(&mut self, username: String) -> Result<()> {
+        self.store.remove(&username).await?;
+
+        self.users
+            .remove(&username)
+            .ok_or_else(|| FngrError::UserNotFound(username.clone()))?;
+        self.changed.remove(&username);
+        self.rank();
+        self.touch();
+
+        Ok(())
+    }
+}
+
+/// The hash of a key as it is stored, the lowercase hex sha256 digest of the key's bytes.
+pub fn hash_key(key: Uuid) -> String {
+    Sha256::new().digest(key.as_bytes())
+}
+
+/// A stored key hash is the lowercase hex sha256 digest of the user's key.
+fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
@@ -0,0 +1,173 @@
+//! Users logged in to the system, read from utmp, and their last logins from wtmp, like classic fingerd.
+//!
+//! Records are read in the layout glibc uses on Linux.
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::networking::{Availability, JSONResponse, JSONStatus};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{prelude::*, rfc1288::duration};
+
+const RECORD_SIZE: usize = 384;
+const USER_PROCESS: i16 = 7;
+
+/// Whether system users are shown and how they mix with the registered ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemUsers {
+    #[default]
+    Off,
+    /// Next to the registered users, which win when a name is both.
+    Merge,
+    /// Instead of the registered users.
+    Only,
+}
+
+/// One login session of a system user.
+struct Session {
+    user: String,
+    line: String,
+    host: String,
+    login: SystemTime,
+}
+
+fn parse(buffer: &[u8]) -> Vec<Session> {
+    buffer
+        .chunks_exact(RECORD_SIZE)
+        .filter(|record| i16::from_ne_bytes([record[0], record[1]]) == USER_PROCESS)
+        .map(|record| Session {
+            line: field(&record[8..40]),
+            user: field(&record[44..76]),
+            host: field(&record[76..332]),
+            login: UNIX_EPOCH
+                + Duration::from_secs(u32::from_ne_bytes(
+                    record[340..344].try_into().unwrap_or_default(),
+                ) as u64),
+        })
+        .filter(|session| !session.user.is_empty())
+        .collect()
+}
+
+/// A nul padded string field.
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end])
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect()
+}
+
+async fn read(path: &Path) -> Result<Vec<Session>> {
+    match tokio::fs::read(path).await {
+        Ok(buffer) => Ok(parse(&buffer)),
+        // no utmp means nobody is logged in
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(anyhow!("can't read {}: {}", path.display(), e)),
+    }
+}
+
+/// How long ago the terminal on `line` was last typed on.
+fn idle(line: &str) -> Option<Duration> {
+    let accessed = std::fs::metadata(Path::new("/dev").join(line))
+        .ok()?
+        .accessed()
+        .ok()?;
+    SystemTime::now().duration_since(accessed).ok()
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn secs_since(time: SystemTime) -> u64 {
+    SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A user with their sessions, which are all of the same user.
+fn response(user: String, sessions: &[Session]) -> JSONResponse {
+    let terminals: Vec<String> = sessions
+        .iter()
+        .map(|session| {
+            let mut terminal = session.line.to_owned();
+            if !session.host.is_empty() {
+                terminal.push_str(&format!(" from {}", session.host));
+            }
+            if let Some(idle) = idle(&session.line) {
+                terminal.push_str(&format!(", idle {}", duration(idle.as_secs())));
+            }
+            terminal
+        })
+        .collect();
+    let first = sessions.iter().map(|session| session.login).min();
+    let last = sessions.iter().map(|session| session.login).max();
+
+    JSONResponse::User {
+        username: user,
+        status: JSONStatus {
+            online: true,
+            presence: Availability::Online,
+            text: Some(format!("on {}", terminals.join("; "))),
+            since: first.map(secs_since).unwrap_or(0),
+            expires_in: None,
+            last_seen: last.map(rfc3339),
+        },
+        profile: None,
+    }
+}
+
+/// Every user logged in to the system, sorted by name.
+pub async fn users(utmp: &Path) -> Result<Vec<JSONResponse>> {
+    let mut by_user: BTreeMap<String, Vec<Session>> = BTreeMap::new();
+    for session in read(utmp).await? {
+        by_user
+            .entry(session.user.to_owned())
+            .or_default()
+            .push(session);
+    }
+
+    Ok(by_user
+        .into_iter()
+        .map(|(user, sessions)| response(user, &sessions))
+        .collect())
+}
+
+/// `user` if they are logged in, or offline since their last login if they ever logged in.
+pub async fn user(utmp: &Path, wtmp: &Path, user: &str) -> Result<Option<JSONResponse>> {
+    let sessions: Vec<Session> = read(utmp)
+        .await?
+        .into_iter()
+        .filter(|session| session.user == user)
+        .collect();
+    if !sessions.is_empty() {
+        return Ok(Some(response(user.to_owned(), &sessions)));
+    }
+
+    let last = read(wtmp)
+        .await?
+        .into_iter()
+        .filter(|session| session.user == user)
+        .map(|session| session.login)
+        .max();
+
+    Ok(last.map(|last| JSONResponse::User {
+        username: user.to_owned(),
+        status: JSONStatus {
+            online: false,
+            presence: Availability::Offline,
+            text: None,
+            since: secs_since(last),
+            expires_in: None,
+            last_seen: Some(rfc3339(last)),
+        },
+        profile: None,
+    }))
+}
@@ -0,0 +1,55 @@
+//! WebFinger (RFC 7033) documents describing fngr users.
+
+use serde::Serialize;
+
+/// A JSON Resource Descriptor.
+#[derive(Debug, Serialize)]
+pub struct Jrd {
+    pub subject: String,
+    pub aliases: Vec<String>,
+    pub links: Vec<Link>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Link {
+    pub rel: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub href: String,
+}
+
+impl Jrd {
+    pub fn user(username: &str, host: &str, public_url: &str, website: Option<&str>) -> Self {
+        let finger = format!("{}/finger?user={}", public_url, username);
+
+        let mut links = vec![Link {
+            rel: "http://webfinger.net/rel/profile-page".to_owned(),
+            kind: "application/json".to_owned(),
+            href: finger.to_owned(),
+        }];
+        if let Some(website) = website {
+            links.push(Link {
+                rel: "http://webfinger.net/rel/profile-page".to_owned(),
+                kind: "text/html".to_owned(),
+                href: website.to_owned(),
+            });
+        }
+
+        Self {
+            subject: format!("acct:{}@{}", username, host),
+            aliases: vec![finger],
+            links,
+        }
+    }
+}
+
+/// Splits an `acct:user@host` resource into the user and host.
+pub fn parse_acct(resource: &str) -> Option<(&str, &str)> {
+    resource.strip_prefix("acct:")?.rsplit_once('@')
+}
+
+/// The host part of a url like `https://example.org:8080/path`.
+pub fn host(url: &str) -> &str {
+    let url = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    url.split(['/', ':']).next().unwrap_or(url)
+}
@@ -0,0 +1,122 @@
+//! Posts presence changes to the webhooks in the config.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::networking::Presence;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::{
+    sync::{Mutex, broadcast::error::RecvError},
+    time::sleep,
+};
+
+use crate::server::{Fingr, config::WebhookConfig, prelude::*};
+
+const ATTEMPTS: u32 = 5;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    presence: &'a Presence,
+    /// Unix timestamp of the change.
+    time: u64,
+}
+
+pub async fn worker(state: Arc<Mutex<Fingr>>) {
+    let mut updates = state.lock().await.presence.subscribe();
+    let client = match reqwest::Client::builder().timeout(TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("can't create the webhook client: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        let presence = match updates.recv().await {
+            Ok(presence) => presence,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("webhooks missed {} presence changes", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let body = match serde_json::to_string(&Payload {
+            presence: &presence,
+            time,
+        }) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("can't serialize webhook payload: {}", e);
+                continue;
+            }
+        };
+
+        // read the hooks for every change so a reload picks up new ones
+        let hooks = state.lock().await.config.webhooks.clone();
+        for hook in hooks {
+            if !hook.events.is_empty() && !hook.events.contains(&presence.change) {
+                continue;
+            }
+
+            tokio::spawn(deliver(client.clone(), hook, body.clone()));
+        }
+    }
+}
+
+/// Posts `body` to the hook, retrying with exponential backoff until it answers with a 2xx.
+async fn deliver(client: reqwest::Client, hook: WebhookConfig, body: String) {
+    for attempt in 1..=ATTEMPTS {
+        let mut request = client
+            .post(&hook.url)
+            .header("Content-Type", "application/json")
+            .body(body.clone());
+        if let Some(secret) = &hook.secret {
+            request = request.header("X-Fngr-Signature", sign(secret, &body));
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => warn!(
+                url = hook.url,
+                attempt,
+                "webhook answered {}",
+                response.status()
+            ),
+            Err(e) => warn!(url = hook.url, attempt, "webhook failed: {}", e),
+        }
+
+        if attempt < ATTEMPTS {
+            sleep(Duration::from_secs(1 << attempt)).await;
+        }
+    }
+
+    error!(
+        url = hook.url,
+        "giving up on webhook after {} attempts", ATTEMPTS
+    );
+}
+
+/// `sha256=` and the hex hmac-sha256 of `body` keyed with `secret`.
+fn sign(secret: &str, body: &str) -> String {
+    // hmac takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", digest)
+}
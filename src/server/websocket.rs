@@ -0,0 +1,179 @@
+//! Presence updates pushed over a websocket on `/ws`.
+//!
+//! Clients send json messages: `{"subscribe": ["alice"]}` answers with the current status of each
+//! user and then pushes every change to them, `{"unsubscribe": ["alice"]}` stops that, and
+//! `"bump"` bumps the user the connection was opened as.
+
+use std::{collections::HashSet, sync::Arc};
+
+use crate::networking::{self, JSONResponse, Request, Response};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    sync::{Mutex, broadcast::error::RecvError},
+};
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{Message, handshake::derive_accept_key, protocol::Role},
+};
+
+use crate::server::{Fingr, prelude::*};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe(Vec<String>),
+    Unsubscribe(Vec<String>),
+    Bump,
+}
+
+pub fn is_upgrade(req: &Request) -> bool {
+    req.headers
+        .get("upgrade")
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+/// Checks the upgrade request, returning who it authenticated as, if anyone.
+pub async fn accept(
+    state: &Arc<Mutex<Fingr>>,
+    req: &Request,
+) -> Result<std::result::Result<Option<String>, Response>> {
+    if !req.headers.contains_key("sec-websocket-key") {
+        return Ok(Err(Response::from(
+            networking::ResponseStatus::Bad,
+            JSONResponse::Error("missing Sec-WebSocket-Key".to_owned()),
+        )));
+    }
+
+    if req.username.is_none() && !req.headers.contains_key("authorization") {
+        return Ok(Ok(None));
+    }
+
+    Ok(Fingr::authenticate(state, req).await?.map(Some))
+}
+
+/// Finishes the handshake and serves the websocket until the client goes away.
+pub async fn serve<S: AsyncRead + AsyncWrite + Unpin>(
+    state: Arc<Mutex<Fingr>>,
+    mut stream: S,
+    req: Request,
+    username: Option<String>,
+) -> Result<()> {
+    let key = req
+        .headers
+        .get("sec-websocket-key")
+        .ok_or(anyhow!("missing Sec-WebSocket-Key"))?;
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    stream.write_all(handshake.as_bytes()).await?;
+    stream.flush().await?;
+
+    let mut ws = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+    let mut updates = state.lock().await.presence.subscribe();
+    let mut subscriptions = HashSet::new();
+
+    loop {
+        tokio::select! {
+            message = ws.next() => {
+                let reply = match message {
+                    Some(Ok(Message::Text(text))) => {
+                        answer(&state, &mut subscriptions, username.as_deref(), &text).await
+                    }
+                    Some(Ok(Message::Close(_))) | None => break,
+                    // pings are answered by tungstenite itself
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                };
+                ws.send(Message::text(reply.render(req.api_version))).await?;
+            }
+            update = updates.recv() => match update {
+                Ok(update) => {
+                    // users may have hidden themselves since the subscription
+                    let listed = subscriptions.contains(&update.username)
+                        && state
+                            .lock()
+                            .await
+                            .users
+                            .get(&update.username)
+                            .is_some_and(|user| user.listed_for(username.as_deref()));
+                    if listed {
+                        let update = JSONResponse::User {
+                            username: update.username,
+                            status: update.status,
+                            profile: None,
+                        };
+                        ws.send(Message::text(update.render(req.api_version))).await?;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => warn!("websocket missed {} presence updates", missed),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn answer(
+    state: &Arc<Mutex<Fingr>>,
+    subscriptions: &mut HashSet<String>,
+    username: Option<&str>,
+    text: &str,
+) -> JSONResponse {
+    let message: ClientMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => return JSONResponse::Error(format!("invalid message: {}", e)),
+    };
+
+    let mut lock = state.lock().await;
+    match message {
+        ClientMessage::Subscribe(usernames) => {
+            let mut statuses = vec![];
+            for name in usernames {
+                match lock.users.get(&name) {
+                    Some(user) if user.listed_for(username) => statuses.push(user.into()),
+                    Some(user) if !user.blocks(username) && !user.allows(username) => {
+                        return JSONResponse::Error(format!(
+                            "only friends of {} can finger them",
+                            name
+                        ));
+                    }
+                    // hidden and blocking users are as unknown as the ones that don't exist
+                    _ => return JSONResponse::Error(format!("unknown user '{}'", name)),
+                }
+                subscriptions.insert(name);
+            }
+            JSONResponse::List(statuses)
+        }
+        ClientMessage::Unsubscribe(usernames) => {
+            for name in usernames {
+                subscriptions.remove(&name);
+            }
+            JSONResponse::OK("unsubscribed".to_owned())
+        }
+        ClientMessage::Bump => {
+            let Some(username) = username else {
+                return JSONResponse::Error(
+                    "open the websocket with your credentials to bump".to_owned(),
+                );
+            };
+
+            let idle_timeout = lock.config.idle_timeout();
+            match lock
+                .users
+                .get_mut(username)
+                .map(|user| user.bump(idle_timeout))
+            {
+                Some(true) => {
+                    lock.users.touch();
+                    JSONResponse::OK("you are bumped".to_owned())
+                }
+                Some(false) => JSONResponse::Error("you must be logged on to bump".to_owned()),
+                None => JSONResponse::Error("user not found".to_owned()),
+            }
+        }
+    }
+}